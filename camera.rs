@@ -1,13 +1,20 @@
-use crate::canvas::Canvas;
-use crate::colors::Pixel;
+use crate::canvas::{write_ppm_header, Canvas, DynCanvas, PpmFormat};
+use crate::colors::{OutputColorSpace, Pixel};
 use crate::matrices::*;
 use crate::rays::*;
 #[cfg(test)]
-use crate::transformations::{rotation_y, translation, PI};
+use crate::canvas::assert_canvas_matches;
+#[cfg(test)]
+use crate::transformations::{rotation_y, translation, view_transform};
+use crate::transformations::PI;
 use crate::tuples::*;
 use crate::worlds::*;
 use rayon::prelude::*;
+use std::io::Write;
 use std::ops::Div;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 pub struct Camera<const HSIZE: usize, const VSIZE: usize> {
     field_of_view: Number,
     transform: Matrix<4, 4>,
@@ -23,17 +30,179 @@ pub struct Camera<const HSIZE: usize, const VSIZE: usize> {
     aperture: Number,
     focal_distance: Number,
     samples: usize,
+    // A cheap soft-focus look for previews: each of the `samples` rays per pixel
+    // has its direction tilted by up to `focus_blur` radians, so everything blurs
+    // evenly instead of only what's off the focal plane. 0.0 (the default) is off.
+    focus_blur: Number,
+    // Clip planes, as distances along each camera ray: only surfaces with
+    // near < t <= far are shaded, so a near plane past an object's front cuts
+    // it open (see `World::color_at_clipped`). 0 and infinity by default.
+    near: Number,
+    far: Number,
+    // How rendered colors are encoded into 8-bit pixels; sRGB by default.
+    color_space: OutputColorSpace,
+    // Mixed into every pixel's sample jitter (`lens_jitter`). Each pixel's
+    // samples depend only on (seed, x, y), never on which thread renders it or
+    // when, so a render is the same on any number of threads; changing the
+    // seed gives another, equally valid set of samples. 0 by default.
+    seed: u64,
+    // Where in each pixel `render_aa` (and a supersampled `render_id_mask`)
+    // places its samples. Jittered by default.
+    #[allow(dead_code)]
+    sample_pattern: SamplePattern,
+    // Whether `render_reporting` prints its progress; off by default so tests
+    // and embedders stay quiet.
+    report_progress: bool,
+}
+// Which extent of the image `Camera::with_fov_axis`'s field of view spans.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FovAxis {
+    Horizontal,
+    Vertical,
+    // No chapter in main.rs asks for it.
+    #[allow(dead_code)]
+    Diagonal,
+}
+// The least time between two `render_reporting` progress lines.
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+// How `SamplePattern::offsets` spreads n samples over a pixel. The chapters in
+// main.rs don't antialias, so the binary picks none of these.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SamplePattern {
+    // The centers of n equal-area cells: about sqrt(n) rows of about sqrt(n)
+    // cells, a row with fewer cells made shorter to match (a k x k grid when
+    // n = k²). Even, but every pixel gets the same offsets, so its aliasing is
+    // regular too.
+    Grid,
+    // One random point in each grid cell: still evenly spread, with the
+    // leftover error turned into noise.
+    #[default]
+    Jittered,
+    // The grid turned by atan(1/2) and wrapped back into the pixel, so no two
+    // samples share a row or column and near-horizontal and near-vertical
+    // edges get more distinct steps.
+    Rotated,
+    // Random points kept apart from each other (Mitchell's best candidate:
+    // each new sample is the farthest from the rest out of several tries).
+    Poisson,
+}
+// Candidates per existing sample for `SamplePattern::Poisson`.
+const POISSON_CANDIDATES: usize = 4;
+#[allow(dead_code)]
+impl SamplePattern {
+    // `n` sub-pixel offsets in [0, 1)², (0, 0) being the pixel's top-left
+    // corner. The random patterns draw from `seed` only, so the same seed
+    // always gives the same offsets.
+    pub fn offsets(self, n: usize, seed: u64) -> Vec<(Number, Number)> {
+        // Sample i's cell as (left, top, width, height). Row r holds samples
+        // row_start(r) up to row_start(r + 1) and is as tall as its share of
+        // them, so every cell covers 1/n of the pixel and an n that is not a
+        // square still balances on the pixel's center.
+        let rows = (n as Number).sqrt().round().max(1.0) as usize;
+        let row_start = |r: usize| n * r / rows;
+        let cell = |i: usize| {
+            let r = (0..rows).rev().find(|&r| row_start(r) <= i).unwrap_or(0);
+            let (start, count) = (row_start(r), row_start(r + 1) - row_start(r));
+            let (width, height) = (1.0 / count as Number, count as Number / n as Number);
+            let left = (i - start) as Number * width;
+            (left, start as Number / n as Number, width, height)
+        };
+        let center = |i: usize| {
+            let (left, top, width, height) = cell(i);
+            (left + width / 2.0, top + height / 2.0)
+        };
+        let random = |i: usize| {
+            let h = hash(seed ^ hash(i as u64));
+            (unit(h), unit(hash(h ^ 0x9e3779b97f4a7c15)))
+        };
+        match self {
+            SamplePattern::Grid => (0..n).map(center).collect(),
+            SamplePattern::Jittered => (0..n)
+                .map(|i| {
+                    let (left, top, width, height) = cell(i);
+                    let (rx, ry) = random(i);
+                    // `min` keeps f32 rounding from landing on the next cell.
+                    let inside = |v: Number| v.min(1.0 - EPSILON);
+                    (inside(left + rx * width), inside(top + ry * height))
+                })
+                .collect(),
+            SamplePattern::Rotated => {
+                let (sin, cos) = (0.5 as Number).atan().sin_cos();
+                (0..n)
+                    .map(|i| {
+                        let (cx, cy) = center(i);
+                        let (x, y) = (cx - 0.5, cy - 0.5);
+                        let wrap = |v: Number| (v + 0.5).rem_euclid(1.0).min(1.0 - EPSILON);
+                        (wrap(x * cos - y * sin), wrap(x * sin + y * cos))
+                    })
+                    .collect()
+            }
+            SamplePattern::Poisson => {
+                let mut points: Vec<(Number, Number)> = Vec::with_capacity(n);
+                let mut draw = 0;
+                for _ in 0..n {
+                    let tries = POISSON_CANDIDATES * points.len() + 1;
+                    let mut best = (0.0, 0.0);
+                    let mut best_distance = -1.0;
+                    for _ in 0..tries {
+                        let candidate = random(draw);
+                        draw += 1;
+                        let distance = points
+                            .iter()
+                            .map(|&(x, y)| {
+                                // Distance on a torus, so neighboring pixels'
+                                // samples keep apart across the shared edge too.
+                                let dx = (candidate.0 - x).abs().min(1.0 - (candidate.0 - x).abs());
+                                let dy = (candidate.1 - y).abs().min(1.0 - (candidate.1 - y).abs());
+                                dx * dx + dy * dy
+                            })
+                            .fold(Number::INFINITY, Number::min);
+                        if distance > best_distance {
+                            best = candidate;
+                            best_distance = distance;
+                        }
+                    }
+                    points.push(best);
+                }
+                points
+            }
+        }
+    }
 }
 const MAX_REFLECTION_DEPTH: usize = 5;
+// The shortest arm (pixels) of a `draw_light_gizmos` cross. No chapter in
+// main.rs draws gizmos.
+#[allow(dead_code)]
+const GIZMO_ARM: usize = 3;
+// How far `Camera::orbit` lets the eye climb above or below its center: just
+// short of straight up or down, where "up" stops meaning anything and the view
+// would flip. The flythrough in viewport.rs moves freely instead of orbiting.
+#[allow(dead_code)]
+const MAX_ORBIT_PITCH: Number = 1.55;
 impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
+    // `field_of_view` spans the canvas's larger dimension.
     pub fn new(field_of_view: Number) -> Self {
+        let axis = if HSIZE >= VSIZE {
+            FovAxis::Horizontal
+        } else {
+            FovAxis::Vertical
+        };
+        Self::with_fov_axis(field_of_view, axis)
+    }
+    // `field_of_view` spans `axis`; the other extents follow from the aspect
+    // ratio.
+    pub fn with_fov_axis(field_of_view: Number, axis: FovAxis) -> Self {
         let half_view = field_of_view.div(2.0).tan();
-        let aspect = HSIZE as Number / VSIZE as Number;
+        let (w, h) = (HSIZE as Number, VSIZE as Number);
 
-        let (half_width, half_height) = if aspect >= 1.0 {
-            (half_view, half_view / aspect)
-        } else {
-            (half_view * aspect, half_view)
+        let (half_width, half_height) = match axis {
+            FovAxis::Horizontal => (half_view, half_view * h / w),
+            FovAxis::Vertical => (half_view * w / h, half_view),
+            FovAxis::Diagonal => {
+                let diagonal = (w * w + h * h).sqrt();
+                (half_view * w / diagonal, half_view * h / diagonal)
+            }
         };
 
         let pixel_size = (half_width * 2.0) / HSIZE as Number;
@@ -48,6 +217,13 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
             aperture: 0.0,
             focal_distance: 1.0,
             samples: 1,
+            focus_blur: 0.0,
+            near: 0.0,
+            far: Number::INFINITY,
+            color_space: OutputColorSpace::default(),
+            seed: 0,
+            sample_pattern: SamplePattern::default(),
+            report_progress: false,
         }
     }
     // Enable depth of field: `aperture` is the lens radius (world units), objects
@@ -57,6 +233,33 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
         self.focal_distance = focal_distance.max(EPSILON);
         self.samples = samples.max(1);
     }
+    // Enable the soft-focus preview: directions jitter within a cone of half-angle
+    // `focus_blur` radians, averaged over `samples` rays per pixel.
+    pub fn set_focus_blur(&mut self, focus_blur: Number, samples: usize) {
+        self.focus_blur = focus_blur.max(0.0);
+        self.samples = samples.max(1);
+    }
+    // Discard camera-ray hits nearer than `near` or farther than `far`.
+    pub fn set_clip(&mut self, near: Number, far: Number) {
+        self.near = near.max(0.0);
+        self.far = far;
+    }
+    // Pick the sample pattern for stochastic renders; see `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+    // Choose where `render_aa` samples each pixel; see `SamplePattern`.
+    pub fn set_sample_pattern(&mut self, pattern: SamplePattern) {
+        self.sample_pattern = pattern;
+    }
+    // Turn `render_reporting`'s progress output on or off.
+    pub fn set_report_progress(&mut self, report: bool) {
+        self.report_progress = report;
+    }
+    // Choose how renders are encoded; see `OutputColorSpace`.
+    pub fn set_color_space(&mut self, color_space: OutputColorSpace) {
+        self.color_space = color_space;
+    }
     pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
         let xoffset = (px as Number + 0.5) * self.pixel_size;
         let yoffset = (py as Number + 0.5) * self.pixel_size;
@@ -83,10 +286,13 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
     // so all lens samples for a pixel converge there. With aperture 0 the lens
     // offset has no effect and this reduces to `ray_for_pixel`.
     fn ray_for_pixel_lens(&self, px: usize, py: usize, lens_u: Number, lens_v: Number) -> Ray {
-        let xoffset = (px as Number + 0.5) * self.pixel_size;
-        let yoffset = (py as Number + 0.5) * self.pixel_size;
-        let world_x = self.half_width - xoffset;
-        let world_y = self.half_height - yoffset;
+        self.ray_through_lens(px as Number + 0.5, py as Number + 0.5, lens_u, lens_v)
+    }
+    // `ray_for_pixel_lens` through any point of the image plane, in continuous
+    // pixel coordinates (pixel (px, py) spans px..px + 1, py..py + 1).
+    fn ray_through_lens(&self, x: Number, y: Number, lens_u: Number, lens_v: Number) -> Ray {
+        let world_x = self.half_width - x * self.pixel_size;
+        let world_y = self.half_height - y * self.pixel_size;
         // The point on the focal plane along the central ray through this pixel.
         let mut focus = Point {
             x: world_x * self.focal_distance,
@@ -106,15 +312,58 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
         let direction = (focus - origin).normalize();
         Ray { origin, direction }
     }
+    // Sample `s` of pixel (px, py): jittered across the lens if focal blur is on,
+    // then tilted within the `focus_blur` cone. With neither, this is exactly the
+    // central ray through the pixel.
+    fn sample_ray(&self, px: usize, py: usize, s: usize) -> Ray {
+        self.sample_ray_through(px as Number + 0.5, py as Number + 0.5, px, py, s)
+    }
+    // `sample_ray` through the point (x, y) of pixel (px, py) instead of its
+    // center.
+    fn sample_ray_through(&self, x: Number, y: Number, px: usize, py: usize, s: usize) -> Ray {
+        let (lens_u, lens_v) = lens_jitter(self.seed, px, py, s);
+        let ray = self.ray_through_lens(x, y, lens_u, lens_v);
+        if self.focus_blur == 0.0 {
+            return ray;
+        }
+        // An even spread over the cone's cross-section: the square root keeps
+        // samples from bunching at the axis.
+        let (a, b) = lens_jitter(self.seed, px, py, s + 2 * self.samples);
+        let r = (a + 0.5).sqrt() * self.focus_blur.tan();
+        let theta = 2.0 * PI * (b + 0.5);
+        let d = ray.direction;
+        let axis = if d.x.abs() < 0.9 {
+            Vector::from([1.0, 0.0, 0.0])
+        } else {
+            Vector::from([0.0, 1.0, 0.0])
+        };
+        let u = d.cross(axis).normalize();
+        let v = d.cross(u);
+        let direction = (d + u * (r * theta.cos()) + v * (r * theta.sin())).normalize();
+        Ray {
+            origin: ray.origin,
+            direction,
+        }
+    }
+    // Whether every pixel is a single central ray: no focal blur, soft focus or
+    // extra samples.
+    fn is_pinhole(&self) -> bool {
+        self.samples <= 1 && self.aperture == 0.0 && self.focus_blur == 0.0
+    }
     // The averaged color for one pixel. A pinhole camera (aperture 0, 1 sample)
-    // casts the single central ray; with focal blur enabled it averages `samples`
-    // lens-jittered rays. The jitter is a deterministic hash of (px, py, sample)
-    // so it needs no shared RNG state and stays reproducible under the parallel
-    // renderer.
+    // casts the single central ray; with focal blur or soft focus enabled it
+    // averages `samples` jittered rays (see `sample_ray`). The jitter is a
+    // deterministic hash of (px, py, sample) so it needs no shared RNG state and
+    // stays reproducible under the parallel renderer.
     fn color_for_pixel(&self, world: &World, px: usize, py: usize, depth: usize) -> Pixel {
-        if self.samples <= 1 && self.aperture == 0.0 {
+        Pixel::quantize(self.radiance_for_pixel(world, px, py, depth), self.color_space)
+    }
+    // The linear, unclamped color of pixel (px, py) before quantizing: the central
+    // ray for a pinhole camera, else the average over the lens samples.
+    fn radiance_for_pixel(&self, world: &World, px: usize, py: usize, depth: usize) -> Color {
+        if self.is_pinhole() {
             let ray = self.ray_for_pixel(px, py);
-            return Pixel::clamp(0, 255, world.color_at(&ray, depth));
+            return world.color_at_clipped(&ray, depth, self.near, self.far);
         }
         let mut sum = Color {
             r: 0.0,
@@ -122,18 +371,153 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
             b: 0.0,
         };
         for s in 0..self.samples {
-            let (lens_u, lens_v) = lens_jitter(px, py, s);
-            let ray = self.ray_for_pixel_lens(px, py, lens_u, lens_v);
-            sum = sum + world.color_at(&ray, depth);
+            let ray = self.sample_ray(px, py, s);
+            sum = sum + world.color_at_clipped(&ray, depth, self.near, self.far);
+        }
+        sum * (1.0 / self.samples as Number)
+    }
+    // How much of pixel (px, py) shows object `target_id` in front, from 0 to 1.
+    // A pinhole camera with one sample answers 0 or 1 for the central ray; with
+    // `samples` > 1 each sample is also jittered across the pixel (and the lens,
+    // if focal blur is on) so silhouette edges get fractional coverage.
+    fn coverage_for_pixel(&self, world: &World, px: usize, py: usize, target_id: usize) -> Number {
+        let hits_target = |ray: &Ray| {
+            let xs = world.intersect_world(ray);
+            let i = xs.hit_index_within(self.near, self.far);
+            i != xs.len && xs.xs[i].object_id == target_id
+        };
+        if self.samples <= 1 && self.aperture == 0.0 {
+            return if hits_target(&self.ray_for_pixel(px, py)) { 1.0 } else { 0.0 };
+        }
+        let mut covered = 0;
+        let offsets = self
+            .sample_pattern
+            .offsets(self.samples, pixel_seed(self.seed, px, py));
+        for (s, (du, dv)) in offsets.into_iter().enumerate() {
+            let (lens_u, lens_v) = lens_jitter(self.seed, px, py, s);
+            let (x, y) = (px as Number + du, py as Number + dv);
+            if hits_target(&self.ray_through_lens(x, y, lens_u, lens_v)) {
+                covered += 1;
+            }
+        }
+        covered as Number / self.samples as Number
+    }
+    // The inverse of `ray_for_pixel`: the (continuous) pixel coordinates where
+    // `point` lands on the image, with pixel (px, py)'s center at (px + 0.5,
+    // py + 0.5). None if the point is on or behind the camera plane. Coordinates
+    // outside 0..HSIZE / 0..VSIZE mean the point is in front but off-screen.
+    pub fn project(&self, point: Point) -> Option<(Number, Number)> {
+        let camera_point = self.transform * point;
+        if camera_point.z > -EPSILON {
+            return None;
+        }
+        // Scale onto the canvas plane at z = -1, then undo the offsets.
+        let world_x = camera_point.x / -camera_point.z;
+        let world_y = camera_point.y / -camera_point.z;
+        let px = (self.half_width - world_x) / self.pixel_size;
+        let py = (self.half_height - world_y) / self.pixel_size;
+        Some((px, py))
+    }
+    // Mark every light in `world` on `image` (a render from this camera) with
+    // a cross in `color`, centered where the light projects. Each arm spans the
+    // light's projected extent, but at least GIZMO_ARM pixels so point lights
+    // show too. Lights behind the camera or off the canvas are skipped; a cross
+    // near an edge is cut off by it. Returns how many were drawn.
+    pub fn draw_light_gizmos(
+        &self,
+        world: &World,
+        image: &mut Canvas<VSIZE, HSIZE>,
+        color: Pixel,
+    ) -> usize {
+        let mut drawn = 0;
+        for (position, extent) in world.light_gizmos() {
+            let Some((px, py)) = self.project(position) else {
+                continue;
+            };
+            if px < 0.0 || py < 0.0 || px >= HSIZE as Number || py >= VSIZE as Number {
+                continue;
+            }
+            let depth = -(self.transform * position).z;
+            let arm = (extent / depth / self.pixel_size).max(GIZMO_ARM as Number) as isize;
+            let (x, y) = (px as isize, py as isize);
+            for d in -arm..=arm {
+                for (col, row) in [(x + d, y), (x, y + d)] {
+                    if (0..HSIZE as isize).contains(&col) && (0..VSIZE as isize).contains(&row) {
+                        image.set(color, row as usize, col as usize);
+                    }
+                }
+            }
+            drawn += 1;
         }
-        Pixel::clamp(0, 255, sum * (1.0 / self.samples as Number))
+        drawn
     }
     pub fn set_transform(&mut self, transform: Matrix<4, 4>) -> () {
         self.transform = transform;
         self.inverse_transform = inverse(&transform);
     }
-    // Flatten this camera into the GPU-uploadable `Cam` (pinhole; focal blur is
-    // host-only). `max_depth` is the reflection/refraction bounce budget.
+    // Orbit the eye around `center` for interactive navigation: turn it
+    // `yaw_delta` radians around +y and `pitch_delta` up (or down) from where it
+    // is now, `radius` away from `center`, looking back at it with +y up. The
+    // view is rigid, so rather than running the general `inverse` on every step
+    // its inverse is written down directly: the transposed rotation, then the
+    // eye as translation. Each angle's sine and cosine are taken once.
+    pub fn orbit(&mut self, center: Point, yaw_delta: Number, pitch_delta: Number, radius: Number) {
+        let eye = self.inverse_transform.unwrap_or(Matrix::identity()) * Point::default();
+        let offset = eye - center;
+        let (yaw, pitch) = if offset.magnitude() < EPSILON {
+            (0.0, 0.0)
+        } else {
+            let d = offset.normalize();
+            (d.x.atan2(d.z), d.y.clamp(-1.0, 1.0).asin())
+        };
+        let yaw = yaw + yaw_delta;
+        let pitch = (pitch + pitch_delta).clamp(-MAX_ORBIT_PITCH, MAX_ORBIT_PITCH);
+        let (sy, cy, sp, cp) = (yaw.sin(), yaw.cos(), pitch.sin(), pitch.cos());
+        // The camera's basis, as `view_transform` lays it out: left, up, and
+        // back (from `center` towards the eye).
+        let left = Vector {
+            x: cy,
+            y: 0.0,
+            z: -sy,
+        };
+        let up = Vector {
+            x: -sy * sp,
+            y: cp,
+            z: -cy * sp,
+        };
+        let back = Vector {
+            x: cp * sy,
+            y: sp,
+            z: cp * cy,
+        };
+        let eye = center + back * radius;
+        let from_origin = eye - Point::default();
+        let (tl, tu, tb) = (
+            -left.dot(from_origin),
+            -up.dot(from_origin),
+            -back.dot(from_origin),
+        );
+        self.transform = Matrix::new([
+            [left.x, left.y, left.z, tl],
+            [up.x, up.y, up.z, tu],
+            [back.x, back.y, back.z, tb],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        self.inverse_transform = Some(Matrix::new([
+            [left.x, up.x, back.x, eye.x],
+            [left.y, up.y, back.y, eye.y],
+            [left.z, up.z, back.z, eye.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ]));
+    }
+    // The angle (radians, small-angle) one pixel subtends: its width on the
+    // canvas plane one unit away. Assign it to `World::pixel_spread` to
+    // anti-alias pattern edges in renders from this camera.
+    pub fn pixel_spread(&self) -> Number {
+        self.pixel_size
+    }
+    // Flatten this camera into the GPU-uploadable `Cam` (pinhole; focal blur and
+    // clip planes are host-only). `max_depth` is the reflection/refraction bounce budget.
     #[cfg(feature = "gpu")]
     pub fn to_cam(&self, max_depth: u32) -> raycore::render::Cam {
         raycore::render::Cam {
@@ -146,6 +530,7 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
             max_depth,
             row_offset: 0,
             col_offset: 0,
+            srgb: (self.color_space == OutputColorSpace::Srgb) as u32,
         }
     }
     // Like `to_cam`, but for a `1/scale`-resolution render of the SAME view (fewer,
@@ -165,6 +550,7 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
             max_depth,
             row_offset: 0,
             col_offset: 0,
+            srgb: (self.color_space == OutputColorSpace::Srgb) as u32,
         }
     }
     pub fn render(&self, world: World) -> Canvas<VSIZE, HSIZE> {
@@ -189,16 +575,155 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
             });
         image
     }
+    // Antialiased render: each pixel averages `samples` rays spread over it by
+    // the camera's `SamplePattern`, on top of any focal blur or soft focus.
+    pub fn render_aa(&self, world: &World, samples: usize) -> Canvas<VSIZE, HSIZE> {
+        let samples = samples.max(1);
+        let mut image: Canvas<VSIZE, HSIZE> = Canvas::new(255);
+        image
+            .pixels
+            .par_rows_mut()
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let offsets = self
+                        .sample_pattern
+                        .offsets(samples, pixel_seed(self.seed, x, y));
+                    let mut sum = Color {
+                        r: 0.0,
+                        g: 0.0,
+                        b: 0.0,
+                    };
+                    for (s, (du, dv)) in offsets.into_iter().enumerate() {
+                        let (sx, sy) = (x as Number + du, y as Number + dv);
+                        let ray = self.sample_ray_through(sx, sy, x, y, s);
+                        sum = sum
+                            + world.color_at_clipped(
+                                &ray,
+                                MAX_REFLECTION_DEPTH,
+                                self.near,
+                                self.far,
+                            );
+                    }
+                    *pixel = Pixel::quantize(sum * (1.0 / samples as Number), self.color_space);
+                }
+            });
+        image
+    }
+    // The top-level objects whose bounding boxes reach into the view frustum
+    // (the pyramid through the image's edges, cut at the clip planes), by arena
+    // id. The rest are certainly off-screen, but a reflection, refraction or
+    // shadow may still see them, so this is for what camera rays alone decide:
+    // masks, picking, or trimming a scene for a preview. Objects with unbounded
+    // boxes, like planes, are always kept.
+    pub fn cull(&self, world: &World) -> Vec<usize> {
+        (0..world.objects.len())
+            .filter(|&id| world.objects[id].parent().is_none())
+            .filter(|&id| {
+                let bounds = world.parent_space_bounds(id);
+                let finite = |p: Point| p.x.is_finite() && p.y.is_finite() && p.z.is_finite();
+                if !(finite(bounds.min) && finite(bounds.max)) {
+                    return true;
+                }
+                // In camera space the eye looks down -z and the image plane at
+                // z = -1 spans [-half_width, half_width] x [-half_height,
+                // half_height]. The box is out if all its corners are beyond
+                // any one of the six planes. The near clip is on the distance
+                // along the ray, not depth, so it is a sphere around the eye:
+                // an off-axis corner can be nearer than `near` in depth and
+                // still past it along its ray.
+                let view: Vec<Point> = bounds.corners().map(|c| self.transform * c).to_vec();
+                let all = |outside: &dyn Fn(&Point) -> bool| view.iter().all(outside);
+                !(all(&|p| (*p - Point::default()).magnitude() < self.near)
+                    || all(&|p| -p.z > self.far)
+                    || all(&|p| p.x > -p.z * self.half_width)
+                    || all(&|p| p.x < p.z * self.half_width)
+                    || all(&|p| p.y > -p.z * self.half_height)
+                    || all(&|p| p.y < p.z * self.half_height))
+            })
+            .collect()
+    }
+    // A matte for compositing: white where the nearest visible surface belongs to
+    // object `target_id` (its index in the world's object arena, as reported in
+    // `Intersection::object_id`), black elsewhere, and grey along its edges when
+    // supersampling is on (see `coverage_for_pixel`). Nothing is shaded.
+    pub fn render_id_mask(&self, world: &World, target_id: usize) -> Canvas<VSIZE, HSIZE> {
+        let mut image: Canvas<VSIZE, HSIZE> = Canvas::new(255);
+        image
+            .pixels
+            .par_rows_mut()
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let coverage = self.coverage_for_pixel(world, x, y, target_id);
+                    let grey = Color {
+                        r: coverage,
+                        g: coverage,
+                        b: coverage,
+                    };
+                    *pixel = Pixel::clamp(0, 255, grey);
+                }
+            });
+        image
+    }
+    // Debug view of the shadows alone: each pixel is grey at the level
+    // `World::shadow_at` gives for its central ray, white where the surface
+    // sees every light and black where it's in shadow (or shows the
+    // background). No color, shading or bounces.
+    pub fn render_shadows(&self, world: &World) -> Canvas<VSIZE, HSIZE> {
+        let mut image: Canvas<VSIZE, HSIZE> = Canvas::new(255);
+        image
+            .pixels
+            .par_rows_mut()
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let lit = world.shadow_at(&self.ray_for_pixel(x, y));
+                    let grey = Color {
+                        r: lit,
+                        g: lit,
+                        b: lit,
+                    };
+                    *pixel = Pixel::clamp(0, 255, grey);
+                }
+            });
+        image
+    }
     // Render a still, choosing the backend by build feature: the GPU compute
     // shader with `--features gpu`, otherwise the parallel CPU renderer. This is
     // the entry the chapters use, so one binary renders the whole book on whichever
     // backend it was built for. The GPU is a pinhole path tracer, so focal blur
-    // (a positive aperture or multiple samples) and a missing GPU adapter both
-    // transparently fall back to the CPU renderer.
+    // (a positive aperture, soft focus or multiple samples), a background other
+    // than black, fog, a plane with a horizon fade, a non-white ambient light,
+    // material image maps (the shader is handed no images), glossy reflection,
+    // tinted glass (the shader's jobs carry no absorption) and a missing GPU
+    // adapter all transparently fall back to the CPU renderer.
     pub fn render_auto(&self, world: World) -> Canvas<VSIZE, HSIZE> {
         #[cfg(feature = "gpu")]
         {
-            if self.aperture == 0.0 && self.samples <= 1 {
+            let pinhole = self.is_pinhole();
+            let faded = world.objects.iter().any(|o| o.horizon_fade(Number::MAX) < 1.0);
+            let glossy = world.objects.iter().any(|o| o.material.glossiness > 0.0);
+            let tinted = world.objects.iter().any(|o| {
+                let absorbs = |m: &crate::materials::Material| {
+                    let a = m.absorption;
+                    a.r > 0.0 || a.g > 0.0 || a.b > 0.0
+                };
+                absorbs(&o.material) || o.cap_material().is_some_and(absorbs)
+            });
+            let white = Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            };
+            let plain = world.environment == Environment::Black
+                && world.fog.is_none()
+                && !faded
+                && world.ambient_light == white
+                && world.images.is_empty()
+                && !glossy
+                && !tinted;
+            if pinhole && plain {
                 let mut world = world;
                 // The GPU trace reads the flat child_indices and cached bounds, so
                 // make sure they reflect the final scene before uploading.
@@ -259,6 +784,162 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
                 *px = (p.r as u32) << 16 | (p.g as u32) << 8 | p.b as u32;
             });
     }
+    // `render_live` at full depth, returned as a runtime-sized `DynCanvas` for
+    // callers that pick output sizes at runtime.
+    pub fn render_dyn(&self, world: &World) -> DynCanvas {
+        self.render_live(world, MAX_REFLECTION_DEPTH).into()
+    }
+    // Render straight to a binary (P6) PPM on `out` without holding the canvas:
+    // the header goes first, then each row's bytes as soon as the row is done
+    // (its pixels are still rendered in parallel), so memory stays at one row
+    // however large the image. The bytes match `render_par`'s canvas written
+    // with `write_ppm_to(.., PpmFormat::P6)`.
+    pub fn render_streaming<W: Write>(&self, world: &World, mut out: W) -> std::io::Result<()> {
+        write_ppm_header(&mut out, &PpmFormat::P6, HSIZE, VSIZE, 255)?;
+        for y in 0..VSIZE {
+            let row: Vec<u8> = (0..HSIZE)
+                .into_par_iter()
+                .flat_map_iter(|x| {
+                    let p = self.color_for_pixel(world, x, y, MAX_REFLECTION_DEPTH);
+                    [p.r, p.g, p.b]
+                })
+                .collect();
+            out.write_all(&row)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+    // `render_par`, printing the percentage of rows done and a rough time left
+    // to stderr as it goes, if `set_report_progress` turned that on.
+    pub fn render_reporting(&self, world: &World) -> Canvas<VSIZE, HSIZE> {
+        self.render_reporting_to(world, std::io::stderr())
+    }
+    // `render_reporting` with the progress written to `out`. Lines are at least
+    // REPORT_INTERVAL apart, each overwriting the last (they start with '\r'),
+    // and a final one at 100% ends with a newline. The ETA assumes the rows
+    // left take as long as the rows done so far. Write errors only lose
+    // progress lines; the render carries on.
+    pub fn render_reporting_to<W: Write + Send>(
+        &self,
+        world: &World,
+        out: W,
+    ) -> Canvas<VSIZE, HSIZE> {
+        let start = Instant::now();
+        let rows_done = AtomicUsize::new(0);
+        let progress = Mutex::new((out, start));
+        let report = |out: &mut W, done: usize| {
+            let elapsed = start.elapsed().as_secs_f32();
+            let eta = elapsed * (VSIZE - done) as Number / done as Number;
+            let percent = 100.0 * done as Number / VSIZE as Number;
+            let _ = write!(out, "\r{percent:3.0}% ETA {eta:.0}s");
+            let _ = out.flush();
+        };
+        let mut image: Canvas<VSIZE, HSIZE> = Canvas::new(255);
+        image
+            .pixels
+            .par_rows_mut()
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    *pixel = self.color_for_pixel(world, x, y, MAX_REFLECTION_DEPTH);
+                }
+                let done = rows_done.fetch_add(1, Ordering::Relaxed) + 1;
+                if !self.report_progress || done == VSIZE {
+                    return;
+                }
+                // Another row is already reporting; this one can skip its turn.
+                if let Ok(mut guard) = progress.try_lock() {
+                    let (out, last) = &mut *guard;
+                    if last.elapsed() >= REPORT_INTERVAL {
+                        report(out, done);
+                        *last = Instant::now();
+                    }
+                }
+            });
+        if self.report_progress {
+            let (mut out, _) = progress.into_inner().unwrap_or_else(|e| e.into_inner());
+            report(&mut out, VSIZE);
+            let _ = writeln!(out);
+        }
+        image
+    }
+    // Render for at most `budget`, then return whatever is done. The frame is cut
+    // into TIMED_TILE-square tiles, ordered center-out so the middle of the image
+    // fills in first; tiles are rendered in parallel and each checks the deadline
+    // before it starts, so a tile that began in time is always finished whole.
+    // Tiles that never started are left black. A tiny budget returns promptly.
+    pub fn render_timed(&self, world: &World, budget: Duration) -> Canvas<VSIZE, HSIZE> {
+        let mut image: Canvas<VSIZE, HSIZE> = Canvas::new(255);
+        self.render_timed_into(world, budget, &mut image);
+        image
+    }
+    // Like `render_timed`, but renders over an existing canvas: tiles that run out
+    // of budget keep whatever `image` already held (e.g. the previous frame)
+    // instead of going black. Returns how many tiles were rendered.
+    pub fn render_timed_into(
+        &self,
+        world: &World,
+        budget: Duration,
+        image: &mut Canvas<VSIZE, HSIZE>,
+    ) -> usize {
+        let deadline = Instant::now() + budget;
+        // Each finished tile is returned as (x, y, w, pixels) and copied into the
+        // canvas afterwards, so the parallel workers never share the canvas.
+        let done: Vec<(usize, usize, usize, Vec<Pixel>)> = timed_tiles(HSIZE, VSIZE)
+            .into_par_iter()
+            .filter_map(|(x0, y0, w, h)| {
+                if Instant::now() >= deadline {
+                    return None;
+                }
+                let mut pixels = Vec::with_capacity(w * h);
+                for y in y0..y0 + h {
+                    for x in x0..x0 + w {
+                        pixels.push(self.color_for_pixel(world, x, y, MAX_REFLECTION_DEPTH));
+                    }
+                }
+                Some((x0, y0, w, pixels))
+            })
+            .collect();
+        for (x0, y0, w, pixels) in &done {
+            for (i, p) in pixels.iter().enumerate() {
+                image.set(*p, y0 + i / w, x0 + i % w);
+            }
+        }
+        done.len()
+    }
+    // Trace only the pixels whose `done_mask` entry is false, storing each one's
+    // linear color in `buffer` and marking it done. Both slices are row-major,
+    // HSIZE * VSIZE long. Saving the pair between calls lets a long render that
+    // was interrupted pick up where it left off; once every pixel is done the
+    // buffer quantizes to the same image as `render_par`. Returns how many pixels
+    // were traced.
+    pub fn render_resumable(
+        &self,
+        world: &World,
+        done_mask: &mut [bool],
+        buffer: &mut [Color],
+    ) -> usize {
+        assert_eq!(done_mask.len(), HSIZE * VSIZE, "done_mask must cover the frame");
+        assert_eq!(buffer.len(), HSIZE * VSIZE, "buffer must cover the frame");
+        done_mask
+            .par_iter_mut()
+            .zip(buffer.par_iter_mut())
+            .enumerate()
+            .filter(|(_, (done, _))| !**done)
+            .map(|(i, (done, color))| {
+                *color = self.radiance_for_pixel(world, i % HSIZE, i / HSIZE, MAX_REFLECTION_DEPTH);
+                *done = true;
+            })
+            .count()
+    }
+    // Quantize a finished `render_resumable` buffer into a canvas.
+    pub fn resolve_buffer(&self, buffer: &[Color]) -> Canvas<VSIZE, HSIZE> {
+        let mut image: Canvas<VSIZE, HSIZE> = Canvas::new(255);
+        for (i, color) in buffer.iter().enumerate() {
+            image.set(Pixel::quantize(*color, self.color_space), i / HSIZE, i % HSIZE);
+        }
+        image
+    }
     // The ARGB color of a single pixel. The viewport's interlaced refinement traces
     // a sparse, growing set of pixels and uses this to color each one through the
     // camera's normal pipeline.
@@ -268,22 +949,59 @@ impl<const HSIZE: usize, const VSIZE: usize> Camera<HSIZE, VSIZE> {
     }
 }
 
-// A deterministic jitter for lens sampling: hash (px, py, sample) into two values
-// in [-0.5, 0.5]. Being a pure function of its inputs, it gives every pixel a
-// different but reproducible spread of lens offsets with no shared RNG, which the
-// parallel renderer needs.
-fn lens_jitter(px: usize, py: usize, sample: usize) -> (Number, Number) {
-    fn hash(mut h: u64) -> u64 {
-        h ^= h >> 33;
-        h = h.wrapping_mul(0xff51afd7ed558ccd);
-        h ^= h >> 33;
-        h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
-        h ^= h >> 33;
-        h
+// Tile edge (pixels) for `render_timed`: small enough that the deadline is
+// checked often, large enough that per-tile bookkeeping stays negligible.
+const TIMED_TILE: usize = 16;
+
+// The TIMED_TILE tiles covering a w x h frame as (x, y, width, height), nearest
+// the center first, so a budget cut short still leaves the middle of the frame.
+// The chapters in main.rs render without a budget.
+#[allow(dead_code)]
+fn timed_tiles(w: usize, h: usize) -> Vec<(usize, usize, usize, usize)> {
+    let mut tiles = Vec::new();
+    for y in (0..h).step_by(TIMED_TILE) {
+        for x in (0..w).step_by(TIMED_TILE) {
+            tiles.push((x, y, TIMED_TILE.min(w - x), TIMED_TILE.min(h - y)));
+        }
     }
+    let (cx, cy) = (w as isize / 2, h as isize / 2);
+    tiles.sort_by_key(|&(x, y, tw, th)| {
+        let dx = (x + tw / 2) as isize - cx;
+        let dy = (y + th / 2) as isize - cy;
+        dx * dx + dy * dy
+    });
+    tiles
+}
+
+// A 64-bit mix (the MurmurHash3 finalizer): nearby inputs give unrelated
+// outputs.
+fn hash(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+    h
+}
+// The top 24 bits of `h` as a Number in [0, 1); any more and f32 rounding
+// could reach 1.
+fn unit(h: u64) -> Number {
+    (h >> 40) as Number / (1u64 << 24) as Number
+}
+// The `SamplePattern::offsets` seed for pixel (px, py).
+#[allow(dead_code)]
+fn pixel_seed(seed: u64, px: usize, py: usize) -> u64 {
+    hash(seed) ^ hash((px as u64) << 32 | py as u64)
+}
+// A deterministic jitter for lens sampling: hash (seed, px, py, sample) into two
+// values in [-0.5, 0.5]. Being a pure function of its inputs, it gives every
+// pixel a different but reproducible spread of lens offsets with no shared RNG,
+// which the parallel renderer needs.
+fn lens_jitter(seed: u64, px: usize, py: usize, sample: usize) -> (Number, Number) {
     let base = (px as u64).wrapping_mul(73856093)
         ^ (py as u64).wrapping_mul(19349663)
         ^ (sample as u64).wrapping_mul(83492791);
+    let base = base ^ hash(seed);
     let a = hash(base);
     let b = hash(base ^ 0x9e3779b97f4a7c15);
     // Top 53 bits -> [0, 1), then shift to [-0.5, 0.5).
@@ -294,8 +1012,204 @@ fn lens_jitter(px: usize, py: usize, sample: usize) -> (Number, Number) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shapes::*;
+    use crate::transformations::scaling;
     use crate::worlds::World;
 
+    #[test]
+    fn a_near_clip_plane_past_the_front_reveals_the_interior() {
+        let mut c: Camera<11, 11> = Camera::new(PI / 2.0);
+        let from = Point {
+            x: 0.0,
+            y: 0.0,
+            z: -5.0,
+        };
+        let up = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        c.set_transform(view_transform(from, Point::default(), up));
+        let world = World::default();
+        let front = c.color_for_pixel(&world, 5, 5, 1);
+        // The outer sphere's front is at t = 4 and the inner one spans 4.5..5.5,
+        // so a near plane at 5.6 leaves only the outer sphere's far wall (t = 6),
+        // seen from inside.
+        c.set_clip(5.6, Number::INFINITY);
+        let cut = c.color_for_pixel(&world, 5, 5, 1);
+        assert_ne!(cut, front);
+        let ray = c.ray_for_pixel(5, 5);
+        let xs = world.intersect_world(&ray);
+        let back = xs.xs[xs.len - 1];
+        assert_almost_eq!(back.t, 6.0);
+        let comps = back.prepare_computations(&ray, &world.scene(), &xs);
+        assert!(comps.inside);
+        assert_eq!(
+            cut,
+            Pixel::quantize(world.shade_hit(comps, 1), OutputColorSpace::Srgb)
+        );
+        // A far plane short of the sphere leaves nothing to shade.
+        c.set_clip(0.0, 3.0);
+        assert_eq!(c.color_for_pixel(&world, 5, 5, 1), Pixel::black());
+    }
+    #[test]
+    fn an_id_mask_covers_the_front_sphere_and_nothing_else() {
+        let mut c: Camera<11, 11> = Camera::new(PI / 2.0);
+        let from = Point {
+            x: 0.0,
+            y: 0.0,
+            z: -5.0,
+        };
+        let up = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        c.set_transform(view_transform(from, Point::default(), up));
+        let world = World::default();
+        // Object 0 is the outer unit sphere, which hides the inner one entirely.
+        let mask = c.render_id_mask(&world, 0);
+        assert_eq!(mask.pixel_at(5, 5), Pixel::white());
+        assert_eq!(mask.pixel_at(0, 0), Pixel::black());
+        assert_eq!(mask.pixel_at(5, 0), Pixel::black());
+        assert_eq!(c.render_id_mask(&world, 1).pixel_at(5, 5), Pixel::black());
+        // Every pixel is either fully on or off the silhouette without supersampling,
+        // and supersampling leaves the interior and background alone but softens
+        // the rim.
+        c.set_focal_blur(0.0, 5.0, 16);
+        let smooth = c.render_id_mask(&world, 0);
+        assert_eq!(smooth.pixel_at(5, 5), Pixel::white());
+        assert_eq!(smooth.pixel_at(0, 0), Pixel::black());
+        let mut partial = 0;
+        for y in 0..11 {
+            for x in 0..11 {
+                let p = mask.pixel_at(y, x);
+                assert!(p == Pixel::white() || p == Pixel::black());
+                let q = smooth.pixel_at(y, x);
+                if q != Pixel::white() && q != Pixel::black() {
+                    partial += 1;
+                }
+            }
+        }
+        assert!(partial > 0);
+    }
+
+    #[test]
+    fn grid_samples_are_evenly_spaced() {
+        let offsets = SamplePattern::Grid.offsets(9, 0);
+        assert_eq!(offsets.len(), 9);
+        for (i, &(x, y)) in offsets.iter().enumerate() {
+            assert_almost_eq!(x, (i % 3) as Number / 3.0 + 1.0 / 6.0);
+            assert_almost_eq!(y, (i / 3) as Number / 3.0 + 1.0 / 6.0);
+        }
+        // No randomness: the seed changes nothing.
+        assert_eq!(SamplePattern::Grid.offsets(9, 7), offsets);
+    }
+    #[test]
+    fn jittered_samples_stay_within_their_strata() {
+        for seed in 0..20 {
+            let offsets = SamplePattern::Jittered.offsets(16, seed);
+            assert_eq!(offsets.len(), 16);
+            for (i, &(x, y)) in offsets.iter().enumerate() {
+                let (cx, cy) = ((i % 4) as Number / 4.0, (i / 4) as Number / 4.0);
+                assert!(x >= cx && x < cx + 0.25, "sample {i} x = {x}");
+                assert!(y >= cy && y < cy + 0.25, "sample {i} y = {y}");
+            }
+        }
+        assert_ne!(
+            SamplePattern::Jittered.offsets(16, 1),
+            SamplePattern::Jittered.offsets(16, 2)
+        );
+    }
+    #[test]
+    fn samples_balance_on_the_pixel_center_when_n_is_not_a_square() {
+        let centroid = |offsets: &[(Number, Number)]| {
+            let n = offsets.len() as Number;
+            let x: Number = offsets.iter().map(|o| o.0).sum();
+            let y: Number = offsets.iter().map(|o| o.1).sum();
+            (x / n, y / n)
+        };
+        for n in [2, 3, 5, 7] {
+            let (x, y) = centroid(&SamplePattern::Grid.offsets(n, 0));
+            assert_almost_eq!(x, 0.5, 1e-5);
+            assert_almost_eq!(y, 0.5, 1e-5);
+            // Jittered samples scatter, but over many pixels they balance too.
+            let (mut sx, mut sy) = (0.0, 0.0);
+            for seed in 0..500 {
+                let (x, y) = centroid(&SamplePattern::Jittered.offsets(n, seed));
+                sx += x / 500.0;
+                sy += y / 500.0;
+            }
+            assert!((sx - 0.5).abs() < 0.02 && (sy - 0.5).abs() < 0.02, "n = {n}: ({sx}, {sy})");
+        }
+    }
+    #[test]
+    fn every_sample_pattern_stays_inside_the_pixel() {
+        for pattern in [
+            SamplePattern::Grid,
+            SamplePattern::Jittered,
+            SamplePattern::Rotated,
+            SamplePattern::Poisson,
+        ] {
+            for n in [1, 2, 5, 16] {
+                let offsets = pattern.offsets(n, 3);
+                assert_eq!(offsets.len(), n);
+                for (x, y) in offsets {
+                    assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+                }
+            }
+        }
+        // Rotating the grid gives every sample its own column.
+        let mut xs: Vec<Number> = SamplePattern::Rotated
+            .offsets(4, 0)
+            .iter()
+            .map(|o| o.0)
+            .collect();
+        xs.sort_by(Number::total_cmp);
+        assert!(xs.windows(2).all(|w| w[1] - w[0] > 0.1), "{xs:?}");
+    }
+    #[test]
+    fn orbiting_a_full_turn_returns_the_camera_to_its_start() {
+        let mut c: Camera<11, 11> = Camera::new(PI / 2.0);
+        let from = Point {
+            x: 0.0,
+            y: 0.0,
+            z: -5.0,
+        };
+        let up = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let start = view_transform(from, Point::default(), up);
+        c.set_transform(start);
+        // A quarter turn puts the eye on the -x side, still looking at the center.
+        c.orbit(Point::default(), PI / 2.0, 0.0, 5.0);
+        let eye = c.inverse_transform.unwrap() * Point::default();
+        assert_approx_eq!(
+            eye,
+            Point {
+                x: -5.0,
+                y: 0.0,
+                z: 0.0
+            }
+        );
+        assert_eq!(
+            c.transform * c.inverse_transform.unwrap(),
+            Matrix::identity()
+        );
+        for _ in 0..6 {
+            c.orbit(Point::default(), PI / 4.0, 0.0, 5.0);
+        }
+        assert_eq!(c.transform, start);
+        // Climbing and coming back down is undone too.
+        c.orbit(Point::default(), 0.0, 0.4, 5.0);
+        assert_ne!(c.transform, start);
+        c.orbit(Point::default(), 0.0, -0.4, 5.0);
+        assert_eq!(c.transform, start);
+        assert_eq!(c.inverse_transform.unwrap(), inverse(&start).unwrap());
+    }
+
     #[test]
     fn render_live_rows_matches_a_full_render() {
         // Striped rendering must produce exactly the same pixels as one full pass,
@@ -314,6 +1228,272 @@ mod tests {
         assert_eq!(full, banded);
     }
 
+    #[test]
+    fn a_streamed_render_writes_the_same_ppm_as_a_full_render() {
+        let mut c: Camera<20, 12> = Camera::new(PI / 2.0);
+        c.set_transform(translation(0.0, 0.0, -5.0));
+        let world = World::default();
+        let mut streamed = vec![];
+        c.render_streaming(&world, &mut streamed).unwrap();
+        let header = b"P6\n20 12\n255\n";
+        assert_eq!(&streamed[..header.len()], header);
+        assert_eq!(streamed.len(), header.len() + 20 * 12 * 3);
+        let mut full = vec![];
+        c.render_par(world).write_ppm_to(&mut full, PpmFormat::P6).unwrap();
+        assert_eq!(streamed, full);
+    }
+
+    #[test]
+    fn an_object_behind_the_camera_is_culled() {
+        let mut c: Camera<11, 11> = Camera::new(PI / 2.0);
+        let from = Point::from([0.0, 0.0, -5.0]);
+        let up = Vector::from([0.0, 1.0, 0.0]);
+        c.set_transform(view_transform(from, Point::default(), up));
+        let mut world = World::default();
+        let mut behind = Primitive::sphere();
+        behind.set_transform(translation(0.0, 0.0, -20.0));
+        let behind = world.add_object(behind);
+        let mut beside = Primitive::sphere();
+        beside.set_transform(translation(30.0, 0.0, 0.0));
+        let beside = world.add_object(beside);
+        let floor = world.add_object(Primitive::plane());
+        let kept = c.cull(&world);
+        assert_eq!(kept, vec![0, 1, floor]);
+        assert!(!kept.contains(&behind) && !kept.contains(&beside));
+        // A far clip plane short of the spheres culls them too.
+        c.set_clip(0.0, 2.0);
+        assert_eq!(c.cull(&world), vec![floor]);
+    }
+    #[test]
+    fn the_near_clip_culls_by_distance_from_the_eye_not_depth() {
+        let mut c: Camera<11, 11> = Camera::new(PI / 2.0);
+        c.set_clip(1.0, Number::INFINITY);
+        let mut world = World::new();
+        // Less than 1 deep but about 1.27 away along its rays: visible.
+        let mut off_axis = Primitive::sphere();
+        off_axis.set_transform(translation(0.9, 0.0, -0.9) * scaling(0.05, 0.05, 0.05));
+        let off_axis = world.add_object(off_axis);
+        let mut close = Primitive::sphere();
+        close.set_transform(translation(0.0, 0.0, -0.5) * scaling(0.1, 0.1, 0.1));
+        world.add_object(close);
+        assert_eq!(c.cull(&world), vec![off_axis]);
+    }
+    #[test]
+    fn a_quiet_reporting_render_prints_nothing_and_matches_a_full_render() {
+        let mut c: Camera<20, 12> = Camera::new(PI / 2.0);
+        c.set_transform(translation(0.0, 0.0, -5.0));
+        let world = World::default();
+        let mut progress = vec![];
+        let mut reported = vec![];
+        c.render_reporting_to(&world, &mut progress)
+            .write_ppm_to(&mut reported, PpmFormat::P6)
+            .unwrap();
+        assert!(progress.is_empty());
+        let mut full = vec![];
+        c.render_par(world.clone())
+            .write_ppm_to(&mut full, PpmFormat::P6)
+            .unwrap();
+        assert_eq!(reported, full);
+        // Turned on, it at least reports finishing.
+        c.set_report_progress(true);
+        c.render_reporting_to(&world, &mut progress);
+        let text = String::from_utf8(progress).unwrap();
+        assert!(text.ends_with("100% ETA 0s\n"), "{text:?}");
+    }
+    #[test]
+    fn a_render_of_the_default_world_matches_its_golden_image() {
+        let mut c: Camera<32, 24> = Camera::new(PI / 2.0);
+        c.set_transform(view_transform(
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+        ));
+        let image = c.render_dyn(&World::default());
+        let golden = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/default_world.ppm");
+        assert_canvas_matches(&image, golden, 1);
+    }
+
+    #[test]
+    fn render_timed_with_a_tiny_budget_returns_a_mostly_unfinished_canvas() {
+        let mut c: Camera<64, 48> = Camera::new(PI / 2.0);
+        c.set_transform(translation(0.0, 0.0, -5.0));
+        let world = World::default();
+        let start = Instant::now();
+        let image = c.render_timed(&world, Duration::ZERO);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let black = image.to_argb().iter().filter(|&&p| p == 0).count();
+        assert!(black * 10 >= 64 * 48 * 9, "only {black} black pixels");
+    }
+
+    #[test]
+    fn render_timed_with_ample_budget_matches_a_full_render() {
+        let mut c: Camera<40, 24> = Camera::new(PI / 2.0);
+        c.set_transform(translation(0.0, 0.0, -5.0));
+        let world = World::default();
+        let timed = c.render_timed(&world, Duration::from_secs(60)).to_argb();
+        let full = c.render_live(&world, MAX_REFLECTION_DEPTH).to_argb();
+        assert_eq!(timed, full);
+    }
+
+    #[test]
+    fn a_render_resumed_after_the_first_half_matches_a_full_render() {
+        let mut c: Camera<20, 12> = Camera::new(PI / 2.0);
+        c.set_transform(translation(0.0, 0.0, -5.0));
+        let world = World::default();
+        let black = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        let mut buffer = vec![black; 20 * 12];
+        // The first run is "interrupted" after the top half: pretend the bottom
+        // half is already done so only the top is traced.
+        let mut mask: Vec<bool> = (0..20 * 12).map(|i| i >= 20 * 6).collect();
+        assert_eq!(c.render_resumable(&world, &mut mask, &mut buffer), 20 * 6);
+        assert!(mask.iter().all(|&done| done));
+        for done in &mut mask[20 * 6..] {
+            *done = false;
+        }
+        // Resuming traces only what is left.
+        assert_eq!(c.render_resumable(&world, &mut mask, &mut buffer), 20 * 6);
+        assert_eq!(c.render_resumable(&world, &mut mask, &mut buffer), 0);
+        let resumed = c.resolve_buffer(&buffer).to_argb();
+        let full = c.render_par(world).to_argb();
+        assert_eq!(resumed, full);
+    }
+
+    #[test]
+    fn a_point_straight_ahead_projects_to_the_image_center() {
+        let c: Camera<201, 101> = Camera::new(PI / 2.0);
+        let (px, py) = c
+            .project(Point {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            })
+            .unwrap();
+        assert_almost_eq!(px, 100.5);
+        assert_almost_eq!(py, 50.5);
+    }
+    #[test]
+    fn a_stochastic_render_is_the_same_on_any_number_of_threads() {
+        let mut world = World::default();
+        world.roulette_seed = 7;
+        world.objects[0].material.set_reflective(0.6);
+        world.objects[0].material.set_glossiness(0.2);
+        let mut c: Camera<24, 16> = Camera::new(PI / 2.0);
+        c.set_transform(view_transform(
+            Point::from([0.0, 0.5, -4.0]),
+            Point::default(),
+            Vector::from([0.0, 1.0, 0.0]),
+        ));
+        c.set_focal_blur(0.2, 4.0, 4);
+        c.set_seed(99);
+        let render_on = |c: &Camera<24, 16>, threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| c.render_par(world.clone()).to_argb())
+        };
+        let single = render_on(&c, 1);
+        assert_eq!(single, render_on(&c, 8));
+        assert_eq!(single, render_on(&c, 3));
+        // Another seed is another sample pattern.
+        c.set_seed(100);
+        assert_ne!(single, render_on(&c, 1));
+    }
+    #[test]
+    fn a_shadow_render_is_white_where_lit_and_black_elsewhere() {
+        let mut c: Camera<11, 11> = Camera::new(PI / 2.0);
+        c.set_transform(view_transform(
+            Point::from([0.0, 0.0, -5.0]),
+            Point::default(),
+            Vector::from([0.0, 1.0, 0.0]),
+        ));
+        let image = c.render_shadows(&World::default());
+        // The sphere's front faces the light; the corners see only background.
+        assert_eq!(image.pixel_at(5, 5), Pixel::white());
+        assert_eq!(image.pixel_at(0, 0), Pixel::black());
+    }
+    #[test]
+    fn the_default_worlds_light_is_marked_on_the_canvas() {
+        let world = World::default();
+        assert_eq!(
+            world.light_gizmos(),
+            vec![(Point::from([-10.0, 10.0, -10.0]), 0.0)]
+        );
+        let mut c: Camera<40, 30> = Camera::new(PI / 2.0);
+        c.set_transform(view_transform(
+            Point::from([0.0, 0.0, -30.0]),
+            Point::default(),
+            Vector::from([0.0, 1.0, 0.0]),
+        ));
+        let (px, py) = c.project(Point::from([-10.0, 10.0, -10.0])).unwrap();
+        assert!((0.0..40.0).contains(&px) && (0.0..30.0).contains(&py));
+        let mut image = c.render_live(&world, 1);
+        assert_eq!(c.draw_light_gizmos(&world, &mut image, Pixel::green()), 1);
+        let (x, y) = (px as usize, py as usize);
+        assert_eq!(image.pixel_at(y, x), Pixel::green());
+        assert_eq!(image.pixel_at(y, x + GIZMO_ARM), Pixel::green());
+        assert_eq!(image.pixel_at(y - GIZMO_ARM, x), Pixel::green());
+        // Looking the other way, the light is behind the camera.
+        c.set_transform(view_transform(
+            Point::from([0.0, 0.0, -30.0]),
+            Point::from([0.0, 0.0, -60.0]),
+            Vector::from([0.0, 1.0, 0.0]),
+        ));
+        assert_eq!(c.draw_light_gizmos(&world, &mut image, Pixel::green()), 0);
+    }
+    #[test]
+    fn projecting_undoes_ray_for_pixel() {
+        let mut c: Camera<201, 101> = Camera::new(PI / 2.0);
+        c.set_transform(rotation_y(PI / 4.0) * translation(0.0, -2.0, 5.0));
+        let r = c.ray_for_pixel(30, 70);
+        let (px, py) = c.project(r.position(3.0)).unwrap();
+        assert_almost_eq!(px, 30.5, 1e-3);
+        assert_almost_eq!(py, 70.5, 1e-3);
+    }
+    #[test]
+    fn soft_focus_jitters_directions_only_when_enabled() {
+        let mut c: Camera<201, 101> = Camera::new(PI / 2.0);
+        c.set_transform(rotation_y(PI / 4.0) * translation(0.0, -2.0, 5.0));
+        c.set_focus_blur(0.0, 8);
+        let sharp = c.ray_for_pixel(30, 70);
+        for s in 0..8 {
+            assert_eq!(c.sample_ray(30, 70, s), sharp);
+        }
+        c.set_focus_blur(0.05, 8);
+        let blurred: Vec<Ray> = (0..8).map(|s| c.sample_ray(30, 70, s)).collect();
+        for r in &blurred {
+            assert_eq!(r.origin, sharp.origin);
+            let angle = r.direction.dot(sharp.direction).min(1.0).acos();
+            assert!(angle <= 0.05 + 1e-3);
+        }
+        assert!(blurred.iter().any(|r| r.direction != blurred[0].direction));
+    }
+    #[test]
+    fn a_point_behind_the_camera_does_not_project() {
+        let c: Camera<201, 101> = Camera::new(PI / 2.0);
+        let behind = Point {
+            x: 0.0,
+            y: 0.0,
+            z: 5.0,
+        };
+        assert_eq!(c.project(behind), None);
+    }
     #[test]
     fn constructing_a_camera() {
         const HSIZE: usize = 160;
@@ -340,6 +1520,24 @@ mod tests {
         assert_almost_eq!(c.pixel_size, 0.01);
     }
     #[test]
+    fn a_vertical_field_of_view_holds_as_the_image_widens() {
+        let narrow: Camera<200, 100> = Camera::with_fov_axis(PI / 2.0, FovAxis::Vertical);
+        let wide: Camera<400, 100> = Camera::with_fov_axis(PI / 2.0, FovAxis::Vertical);
+        assert_almost_eq!(narrow.half_height, 1.0);
+        assert_almost_eq!(wide.half_height, 1.0);
+        assert_almost_eq!(narrow.half_width, 2.0);
+        assert_almost_eq!(wide.half_width, 4.0);
+        // Pinned horizontally instead, widening shrinks the vertical view.
+        let narrow: Camera<200, 100> = Camera::with_fov_axis(PI / 2.0, FovAxis::Horizontal);
+        let wide: Camera<400, 100> = Camera::with_fov_axis(PI / 2.0, FovAxis::Horizontal);
+        assert_almost_eq!(narrow.half_width, wide.half_width);
+        assert_almost_eq!(narrow.half_height, 0.5);
+        assert_almost_eq!(wide.half_height, 0.25);
+        let diagonal: Camera<300, 400> = Camera::with_fov_axis(PI / 2.0, FovAxis::Diagonal);
+        assert_almost_eq!(diagonal.half_width, 0.6);
+        assert_almost_eq!(diagonal.half_height, 0.8);
+    }
+    #[test]
     fn constructing_a_ray_through_the_center_of_the_canvas() {
         let c: Camera<201, 101> = Camera::new(PI / 2.0);
         let r = c.ray_for_pixel(100, 50);
@@ -351,7 +1549,7 @@ mod tests {
                 z: 0.0
             }
         );
-        assert_eq!(
+        assert_approx_eq!(
             r.direction,
             Vector {
                 x: 0.0,
@@ -372,7 +1570,7 @@ mod tests {
                 z: 0.0
             }
         );
-        assert_eq!(
+        assert_approx_eq!(
             r.direction,
             Vector {
                 x: 0.66519,
@@ -388,7 +1586,7 @@ mod tests {
 
         let r = c.ray_for_pixel(100, 50);
 
-        assert_eq!(
+        assert_approx_eq!(
             r.origin,
             Point {
                 x: 0.0,
@@ -396,7 +1594,7 @@ mod tests {
                 z: -5.0
             }
         );
-        assert_eq!(
+        assert_approx_eq!(
             r.direction,
             Vector {
                 x: sqrt(2.0) / 2.0,
@@ -1,5 +1,7 @@
+use crate::camera::Camera;
 use crate::colors::*;
 use crate::tuples::*;
+use crate::worlds::{Environment, World};
 use rayon::prelude::*;
 use std::fmt::Write as StringWrite;
 use std::fs::File;
@@ -67,20 +69,92 @@ impl<T: std::marker::Copy + PrettyPrint, const ROWS: usize, const COLS: usize>
             data: vec![value; ROWS * COLS].into_boxed_slice(),
         }
     }
+    // Unchecked in release: a column past the end would land on the next row,
+    // so debug builds catch it here rather than as a stray pixel.
     fn set(&mut self, value: T, row: usize, col: usize) -> () {
+        debug_assert!(
+            row < ROWS && col < COLS,
+            "({row}, {col}) outside {ROWS}x{COLS}"
+        );
         self.data[row * COLS + col] = value;
     }
     fn get(&self, row: usize, col: usize) -> &T {
+        debug_assert!(
+            row < ROWS && col < COLS,
+            "({row}, {col}) outside {ROWS}x{COLS}"
+        );
         &self.data[row * COLS + col]
     }
+    // Checked access for indices that may be out of range. The chapters in
+    // main.rs clamp theirs and call `set`, so only tests use these so far.
+    #[allow(dead_code)]
+    pub fn try_set(&mut self, value: T, row: usize, col: usize) -> Result<(), OutOfBounds> {
+        Self::check(row, col)?;
+        self.data[row * COLS + col] = value;
+        Ok(())
+    }
+    #[allow(dead_code)]
+    pub fn try_get(&self, row: usize, col: usize) -> Result<&T, OutOfBounds> {
+        Self::check(row, col)?;
+        Ok(&self.data[row * COLS + col])
+    }
+    fn check(row: usize, col: usize) -> Result<(), OutOfBounds> {
+        if row < ROWS && col < COLS {
+            Ok(())
+        } else {
+            Err(OutOfBounds {
+                row,
+                col,
+                rows: ROWS,
+                cols: COLS,
+            })
+        }
+    }
+}
+
+// Why a checked `HeapMatrix` access was refused: the index asked for and the
+// matrix's size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutOfBounds {
+    pub row: usize,
+    pub col: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl std::fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({}, {}) outside {}x{}",
+            self.row, self.col, self.rows, self.cols
+        )
+    }
 }
 
 pub trait Serialize {
     fn to_bytes(&self) -> Vec<u8>;
 }
+// Which corner row 0 of `Canvas::set`/`write_pixel`/`pixel_at` refers to.
+// Images are stored and written top row first either way; `BottomLeft` lets
+// code that thinks in world y (pointing up) address pixels without flipping
+// the row itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Origin {
+    #[default]
+    TopLeft,
+    BottomLeft,
+}
+
+// Pixels are stored at full 8-bit range whatever `max_color` is; the PPM
+// writers rescale each channel to 0..=max_color so the samples agree with the
+// header (`ppm_pixel`). PNG output is always 8-bit and uses them unscaled.
 pub struct Canvas<const ROWS: usize, const COLS: usize> {
     pub pixels: HeapMatrix<Pixel, ROWS, COLS>,
     max_color: u8,
+    origin: Origin,
+    // How `write_pixel` encodes linear colors; sRGB unless set otherwise.
+    color_space: OutputColorSpace,
 }
 
 impl<const ROWS: usize, const COLS: usize> Canvas<ROWS, COLS> {
@@ -88,11 +162,45 @@ impl<const ROWS: usize, const COLS: usize> Canvas<ROWS, COLS> {
         Self {
             pixels: HeapMatrix::new(Pixel::black()),
             max_color,
+            origin: Origin::TopLeft,
+            color_space: OutputColorSpace::default(),
+        }
+    }
+    // Choose the corner row 0 refers to; see `Origin`.
+    pub fn origin(&mut self, origin: Origin) {
+        self.origin = origin;
+    }
+    // Choose how `write_pixel` encodes colors; see `OutputColorSpace`.
+    pub fn set_color_space(&mut self, color_space: OutputColorSpace) {
+        self.color_space = color_space;
+    }
+    // The stored (top-down) row for a row addressed under the current origin.
+    fn storage_row(&self, row: usize) -> usize {
+        match self.origin {
+            Origin::TopLeft => row,
+            Origin::BottomLeft => ROWS - 1 - row,
         }
     }
     pub fn set(&mut self, value: Pixel, row: usize, col: usize) -> () {
+        let row = self.storage_row(row);
         self.pixels.set(value, row, col);
     }
+    pub fn pixel_at(&self, row: usize, col: usize) -> Pixel {
+        *self.pixels.get(self.storage_row(row), col)
+    }
+    // A difference image against `other`: black where the two agree to within
+    // `tolerance` per channel, red where they don't, brighter the further apart
+    // they are (see `diff_pixel`).
+    pub fn diff(&self, other: &Self, tolerance: u8) -> Self {
+        let mut out = Self::new(self.max_color);
+        for row in 0..ROWS {
+            for col in 0..COLS {
+                let (a, b) = (self.pixels.get(row, col), other.pixels.get(row, col));
+                out.pixels.set(diff_pixel(*a, *b, tolerance), row, col);
+            }
+        }
+        out
+    }
     // Build a canvas from a row-major 0x00RRGGBB framebuffer (the format the GPU
     // backend returns). `argb` must hold exactly ROWS*COLS pixels, row by row from
     // the top-left, matching the canvas layout.
@@ -111,9 +219,90 @@ impl<const ROWS: usize, const COLS: usize> Canvas<ROWS, COLS> {
         canvas
     }
     pub fn write_pixel(&mut self, color: Color, row: usize, col: usize) -> () {
-        let value = Pixel::clamp(0, self.max_color, color);
+        let value = Pixel::quantize(color, self.color_space);
         self.set(value, row, col)
     }
+    // A one-pixel line from `from` to `to`, given as (x, y) in the continuous
+    // pixel coordinates `Camera::project` returns. The segment is clipped to
+    // the canvas first, so an end far off-screen costs nothing extra.
+    pub fn draw_line(&mut self, from: (Number, Number), to: (Number, Number), color: Pixel) {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        // Liang-Barsky: narrow [t0, t1] to the part inside each edge.
+        let (mut t0, mut t1): (Number, Number) = (0.0, 1.0);
+        for (p, q) in [
+            (-dx, from.0),
+            (dx, COLS as Number - from.0),
+            (-dy, from.1),
+            (dy, ROWS as Number - from.1),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        if t0 > t1 {
+            return;
+        }
+        let steps = ((t1 - t0) * dx.abs().max(dy.abs())).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let t = t0 + (t1 - t0) * i as Number / steps as Number;
+            let (x, y) = (from.0 + dx * t, from.1 + dy * t);
+            // The far clip edge is inclusive, so the last step can land on it.
+            if x >= 0.0 && y >= 0.0 && (x as usize) < COLS && (y as usize) < ROWS {
+                self.set(color, y as usize, x as usize);
+            }
+        }
+    }
+    // The world axes as `camera` sees them: red from the origin to x = 1, green
+    // to y = 1 and blue to z = 1, for checking a camera setup. An axis with an
+    // end on or behind the camera is skipped. Returns how many were drawn.
+    pub fn draw_axes(&mut self, camera: &Camera<COLS, ROWS>) -> usize {
+        let Some(origin) = camera.project(Point::from([0.0, 0.0, 0.0])) else {
+            return 0;
+        };
+        let mut drawn = 0;
+        for (end, color) in [
+            ([1.0, 0.0, 0.0], Pixel::red()),
+            ([0.0, 1.0, 0.0], Pixel::green()),
+            ([0.0, 0.0, 1.0], Pixel::blue()),
+        ] {
+            if let Some(end) = camera.project(Point::from(end)) {
+                self.draw_line(origin, end, color);
+                drawn += 1;
+            }
+        }
+        drawn
+    }
+    // Synthetic test images, for calibrating output and exercising exporters.
+    // A checkerboard of `cell`-pixel squares starting with `a` in the top-left.
+    pub fn checkerboard(cell: usize, a: Color, b: Color) -> Self {
+        let cell = cell.max(1);
+        let mut canvas = Self::new(255);
+        for row in 0..ROWS {
+            for col in 0..COLS {
+                let color = if (row / cell + col / cell).is_multiple_of(2) { a } else { b };
+                canvas.write_pixel(color, row, col);
+            }
+        }
+        canvas
+    }
+    // Blend from `left` in the first column to `right` in the last.
+    pub fn horizontal_gradient(left: Color, right: Color) -> Self {
+        let mut canvas = Self::new(255);
+        let span = COLS.saturating_sub(1).max(1) as Number;
+        for col in 0..COLS {
+            let color = left + (right - left) * (col as Number / span);
+            for row in 0..ROWS {
+                canvas.write_pixel(color, row, col);
+            }
+        }
+        canvas
+    }
     pub fn write_ppm(&self, filename: &str, format: PpmFormat) -> Result<(), std::io::Error> {
         let mut file = File::create(filename)?;
         self.write_ppm_to(&mut file, format)
@@ -137,11 +326,849 @@ impl<const ROWS: usize, const COLS: usize> Canvas<ROWS, COLS> {
         out: &mut W,
         format: PpmFormat,
     ) -> Result<(), std::io::Error> {
-        let header = format!("{}\n{} {}\n{}", format.pp(), COLS, ROWS, self.max_color);
-        writeln!(out, "{}", header)?;
-        match format {
-            PpmFormat::P3 => writeln!(out, "{}", self.pixels.pp()),
-            PpmFormat::P6 => out.write_all(&self.to_bytes()),
+        write_ppm_header(out, &format, COLS, ROWS, self.max_color)?;
+        let pixels = (0..ROWS * COLS).map(|i| *self.pixels.get(i / COLS, i % COLS));
+        write_ppm_pixels(out, format, COLS, self.max_color, pixels)
+    }
+    pub fn write_png(&self, filename: &str) -> Result<(), std::io::Error> {
+        let mut file = File::create(filename)?;
+        write_png_to(&mut file, COLS, ROWS, &self.to_bytes())
+    }
+    // Write a Radiance .hdr of the canvas. The pixels are 8-bit, so this adds no
+    // range; it decodes them back to linear light (undoing sRGB if the canvas
+    // encodes it) for tools that expect linear HDR input.
+    pub fn write_hdr(&self, filename: &str) -> Result<(), std::io::Error> {
+        let mut file = File::create(filename)?;
+        let colors = (0..ROWS * COLS).map(|i| {
+            let p = self.pixels.get(i / COLS, i % COLS);
+            self.color_space.decode(Color {
+                r: p.r as Number / 255.0,
+                g: p.g as Number / 255.0,
+                b: p.b as Number / 255.0,
+            })
+        });
+        write_hdr_to(&mut file, COLS, ROWS, colors)
+    }
+    // Write the canvas in the format `path`'s extension names (any case): `.ppm`
+    // as binary P6, `.png` or `.hdr`. Anything else is an `InvalidInput` error
+    // and no file is created.
+    pub fn save(&self, path: &str) -> Result<(), std::io::Error> {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ppm") => self.write_ppm(path, PpmFormat::P6),
+            Some("png") => self.write_png(path),
+            Some("hdr") => self.write_hdr(path),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("can't tell the image format of {path}: use .ppm, .png or .hdr"),
+            )),
+        }
+    }
+}
+
+// A canvas whose size is chosen at runtime, for sizes that come from a scene
+// file or the command line rather than the source. Same pixel layout and output
+// formats as `Canvas`; convert a rendered `Canvas` with `DynCanvas::from`.
+// The chapters in main.rs all have fixed sizes, so the binary itself doesn't
+// build one.
+#[allow(dead_code)]
+pub struct DynCanvas {
+    pub rows: usize,
+    pub cols: usize,
+    pub pixels: Vec<Pixel>,
+    max_color: u8,
+    color_space: OutputColorSpace,
+}
+
+#[allow(dead_code)]
+impl DynCanvas {
+    pub fn new(rows: usize, cols: usize, max_color: u8) -> Self {
+        Self {
+            rows,
+            cols,
+            pixels: vec![Pixel::black(); rows * cols],
+            max_color,
+            color_space: OutputColorSpace::default(),
+        }
+    }
+    // `Canvas::set_color_space`.
+    pub fn set_color_space(&mut self, color_space: OutputColorSpace) {
+        self.color_space = color_space;
+    }
+    pub fn set(&mut self, value: Pixel, row: usize, col: usize) {
+        self.pixels[row * self.cols + col] = value;
+    }
+    pub fn get(&self, row: usize, col: usize) -> Pixel {
+        self.pixels[row * self.cols + col]
+    }
+    // `Canvas::diff` for runtime-sized canvases; both must be the same size.
+    pub fn diff(&self, other: &DynCanvas, tolerance: u8) -> DynCanvas {
+        assert_eq!((self.rows, self.cols), (other.rows, other.cols), "canvas sizes differ");
+        DynCanvas {
+            rows: self.rows,
+            cols: self.cols,
+            pixels: self
+                .pixels
+                .iter()
+                .zip(&other.pixels)
+                .map(|(a, b)| diff_pixel(*a, *b, tolerance))
+                .collect(),
+            max_color: self.max_color,
+            color_space: self.color_space,
+        }
+    }
+    pub fn write_pixel(&mut self, color: Color, row: usize, col: usize) {
+        let value = Pixel::quantize(color, self.color_space);
+        self.set(value, row, col)
+    }
+    pub fn write_ppm(&self, filename: &str, format: PpmFormat) -> Result<(), std::io::Error> {
+        let mut file = File::create(filename)?;
+        self.write_ppm_to(&mut file, format)
+    }
+    pub fn write_ppm_to<W: Write>(
+        &self,
+        out: &mut W,
+        format: PpmFormat,
+    ) -> Result<(), std::io::Error> {
+        write_ppm_header(out, &format, self.cols, self.rows, self.max_color)?;
+        let pixels = self.pixels.iter().copied();
+        write_ppm_pixels(out, format, self.cols, self.max_color, pixels)
+    }
+    pub fn write_png(&self, filename: &str) -> Result<(), std::io::Error> {
+        let mut file = File::create(filename)?;
+        write_png_to(&mut file, self.cols, self.rows, &self.to_bytes())
+    }
+    pub fn read_ppm(filename: &str) -> Result<DynCanvas, std::io::Error> {
+        Self::from_ppm(&std::fs::read(filename)?)
+    }
+    // Parse a P3 (ASCII) or P6 (binary) PPM, the two formats `write_ppm_to`
+    // produces. `#` comments are allowed in the header. Samples are scaled from
+    // 0..=max color up to the canvas's full 8-bit range, and the max color is
+    // kept for writing back. Malformed input is an `InvalidData` error.
+    pub fn from_ppm(bytes: &[u8]) -> Result<DynCanvas, std::io::Error> {
+        let mut pos = 0;
+        let magic = match ppm_token(bytes, &mut pos) {
+            Some(b"P3") => PpmFormat::P3,
+            Some(b"P6") => PpmFormat::P6,
+            _ => return Err(invalid_ppm("not a P3 or P6 PPM")),
+        };
+        let cols = ppm_number(bytes, &mut pos, "width")?;
+        let rows = ppm_number(bytes, &mut pos, "height")?;
+        let max_color = ppm_number(bytes, &mut pos, "max color")?;
+        if max_color == 0 || max_color > 255 {
+            return Err(invalid_ppm("max color must be 1..=255"));
+        }
+        let mut canvas = DynCanvas::new(rows, cols, max_color as u8);
+        match magic {
+            PpmFormat::P3 => {
+                for i in 0..rows * cols {
+                    let mut rgb = [0u8; 3];
+                    for channel in rgb.iter_mut() {
+                        let sample = ppm_number(bytes, &mut pos, "sample")?.min(max_color);
+                        *channel = rescale(sample as u8, max_color as u8, 255);
+                    }
+                    canvas.pixels[i] = Pixel::new(rgb[0], rgb[1], rgb[2]);
+                }
+            }
+            PpmFormat::P6 => {
+                // Exactly one whitespace byte separates the header from the data.
+                let data = bytes
+                    .get(pos + 1..pos + 1 + rows * cols * 3)
+                    .ok_or_else(|| invalid_ppm("truncated pixel data"))?;
+                let max = max_color as u8;
+                for (i, rgb) in data.chunks_exact(3).enumerate() {
+                    let [r, g, b] = [rgb[0], rgb[1], rgb[2]].map(|c| rescale(c.min(max), max, 255));
+                    canvas.pixels[i] = Pixel::new(r, g, b);
+                }
+            }
+        }
+        Ok(canvas)
+    }
+    // This image's pixels row by row from the top as linear light, decoded
+    // the way `write_hdr` decodes (undoing sRGB if the canvas encodes it).
+    pub fn to_texels(&self) -> Vec<Color> {
+        self.to_data_texels()
+            .into_iter()
+            .map(|c| self.color_space.decode(c))
+            .collect()
+    }
+    // This image's pixels row by row from the top, with each channel's
+    // 0..=255 mapped straight to 0.0..=1.0: for maps that hold numbers, not
+    // colors.
+    pub fn to_data_texels(&self) -> Vec<Color> {
+        self.pixels
+            .iter()
+            .map(|p| Color {
+                r: p.r as Number / 255.0,
+                g: p.g as Number / 255.0,
+                b: p.b as Number / 255.0,
+            })
+            .collect()
+    }
+    // This image as an environment map to set as `World::environment`, laid out
+    // as `Environment::ImageSphere` expects (a latitude/longitude image, top row
+    // straight up), decoded to linear light like `to_texels`.
+    pub fn to_environment(&self) -> Environment {
+        Environment::ImageSphere {
+            width: self.cols,
+            texels: self.to_texels(),
+        }
+    }
+    // Store this image in `world` for an albedo map (`Material::from_maps`),
+    // returning its id.
+    pub fn add_to(&self, world: &mut World) -> u32 {
+        world.add_image(self.cols, &self.to_texels())
+    }
+    // Store this image in `world` undecoded, for a roughness or metal map,
+    // returning its id.
+    pub fn add_data_to(&self, world: &mut World) -> u32 {
+        world.add_image(self.cols, &self.to_data_texels())
+    }
+    pub fn to_argb(&self) -> Vec<u32> {
+        self.pixels
+            .iter()
+            .map(|p| (p.r as u32) << 16 | (p.g as u32) << 8 | p.b as u32)
+            .collect()
+    }
+}
+
+// The largest per-channel difference between two pixels.
+fn pixel_distance(a: Pixel, b: Pixel) -> u8 {
+    a.r.abs_diff(b.r).max(a.g.abs_diff(b.g)).max(a.b.abs_diff(b.b))
+}
+
+// One pixel of a diff image: black within `tolerance`, otherwise red from 64 up
+// to 255 with the distance, so even a one-step mismatch stands out on black.
+fn diff_pixel(a: Pixel, b: Pixel, tolerance: u8) -> Pixel {
+    let distance = pixel_distance(a, b);
+    if distance <= tolerance {
+        return Pixel::black();
+    }
+    Pixel::new((64 + distance as u32 * 191 / 255) as u8, 0, 0)
+}
+
+// A channel value out of 0..=`from` mapped onto 0..=`to`, rounded.
+fn rescale(value: u8, from: u8, to: u8) -> u8 {
+    ((value as u32 * to as u32 + from as u32 / 2) / from as u32) as u8
+}
+
+// A stored (full-range) pixel with each channel rescaled to 0..=`max_color`,
+// as the PPM header declares.
+fn ppm_pixel(pixel: Pixel, max_color: u8) -> Pixel {
+    Pixel::new(
+        rescale(pixel.r, 255, max_color),
+        rescale(pixel.g, 255, max_color),
+        rescale(pixel.b, 255, max_color),
+    )
+}
+
+// The PPM header for a `cols` x `rows` image, ending in the newline after
+// `max_color`; the samples follow directly.
+pub fn write_ppm_header<W: Write>(
+    out: &mut W,
+    format: &PpmFormat,
+    cols: usize,
+    rows: usize,
+    max_color: u8,
+) -> Result<(), std::io::Error> {
+    writeln!(out, "{}\n{} {}\n{}", format.pp(), cols, rows, max_color)
+}
+
+// The PPM body for row-major `pixels`, `cols` to a row: ASCII triples a row
+// per line for P3, raw bytes for P6, rescaled to `max_color` either way.
+fn write_ppm_pixels<W: Write>(
+    out: &mut W,
+    format: PpmFormat,
+    cols: usize,
+    max_color: u8,
+    pixels: impl Iterator<Item = Pixel>,
+) -> Result<(), std::io::Error> {
+    let pixels = pixels.map(|p| ppm_pixel(p, max_color));
+    match format {
+        PpmFormat::P3 => {
+            let mut sb = String::new();
+            for (i, pixel) in pixels.enumerate() {
+                let _ = write!(sb, "{} ", pixel.pp());
+                if (i + 1) % cols == 0 {
+                    sb.push('\n');
+                }
+            }
+            writeln!(out, "{}", sb)
+        }
+        PpmFormat::P6 => {
+            let bytes: Vec<u8> = pixels.flat_map(|p| [p.r, p.g, p.b]).collect();
+            out.write_all(&bytes)
+        }
+    }
+}
+
+fn invalid_ppm(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// The next whitespace-delimited PPM token from `pos`, skipping `#` comments.
+fn ppm_token<'a>(bytes: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < bytes.len() && bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+            continue;
+        }
+        break;
+    }
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    (*pos > start).then(|| &bytes[start..*pos])
+}
+
+fn ppm_number(bytes: &[u8], pos: &mut usize, what: &str) -> Result<usize, std::io::Error> {
+    ppm_token(bytes, pos)
+        .and_then(|t| std::str::from_utf8(t).ok()?.parse().ok())
+        .ok_or_else(|| invalid_ppm(&format!("bad or missing {what}")))
+}
+
+impl Serialize for DynCanvas {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|p| [p.r, p.g, p.b]).collect()
+    }
+}
+
+impl<const ROWS: usize, const COLS: usize> From<Canvas<ROWS, COLS>> for DynCanvas {
+    fn from(canvas: Canvas<ROWS, COLS>) -> Self {
+        Self {
+            rows: ROWS,
+            cols: COLS,
+            pixels: canvas.pixels.data.into_vec(),
+            max_color: canvas.max_color,
+            color_space: canvas.color_space,
+        }
+    }
+}
+
+// Write 8-bit RGB `rgb` (row-major, `width * height * 3` bytes) as a PNG. Keeps
+// the crate dependency-free: the zlib stream uses uncompressed "stored" deflate
+// blocks, so files are about the size of a P6 PPM but open in any image viewer.
+// PNG has no empty images, so a zero width or height is an `InvalidInput` error
+// and nothing is written.
+pub fn write_png_to<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    rgb: &[u8],
+) -> Result<(), std::io::Error> {
+    if width == 0 || height == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("a PNG can't be {width}x{height}"),
+        ));
+    }
+    fn chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<(), std::io::Error> {
+        out.write_all(&(data.len() as u32).to_be_bytes())?;
+        out.write_all(kind)?;
+        out.write_all(data)?;
+        let crc = crc32(kind.iter().chain(data));
+        out.write_all(&crc.to_be_bytes())
+    }
+    // Every scanline starts with its filter type; 0 is "none".
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in rgb.chunks(width * 3).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push((i + 1 == blocks.len()) as u8);
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    if blocks.is_empty() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, color type 2 (RGB), default compression/filter, no interlace.
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    out.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])?;
+    chunk(out, b"IHDR", &ihdr)?;
+    chunk(out, b"IDAT", &zlib)?;
+    chunk(out, b"IEND", &[])
+}
+
+// Write `width * height` linear colors (row-major from the top-left) as a
+// Radiance RGBE .hdr. Scanlines are stored flat rather than run-length encoded,
+// which every reader accepts.
+pub fn write_hdr_to<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    colors: impl Iterator<Item = Color>,
+) -> Result<(), std::io::Error> {
+    write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n")?;
+    let mut body = Vec::with_capacity(width * height * 4);
+    for color in colors.take(width * height) {
+        body.extend_from_slice(&rgbe(color));
+    }
+    out.write_all(&body)
+}
+
+// One color as RGBE: three 8-bit mantissas sharing the exponent of the
+// brightest channel. Negative channels clamp to 0.
+fn rgbe(color: Color) -> [u8; 4] {
+    let v = color.r.max(color.g).max(color.b);
+    if v < 1e-32 {
+        return [0; 4];
+    }
+    let exponent = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / (2.0 as Number).powi(exponent);
+    let mantissa = |c: Number| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        mantissa(color.r),
+        mantissa(color.g),
+        mantissa(color.b),
+        (exponent + 128) as u8,
+    ]
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+// Golden-image check for render regression tests: compare `canvas` against the
+// reference PPM at `golden_path`, allowing each channel to differ by up to
+// `tolerance`, and panic listing how many pixels differ and where. Run with
+// `UPDATE_GOLDEN=1` to (re)write the reference from `canvas` instead, after
+// checking the new render is the one you want. On a mismatch the diff image is
+// written next to the golden as `<golden_path>.diff.ppm`.
+#[cfg(test)]
+pub fn assert_canvas_matches(canvas: &DynCanvas, golden_path: &str, tolerance: u8) {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        canvas
+            .write_ppm(golden_path, PpmFormat::P3)
+            .unwrap_or_else(|e| panic!("writing golden {golden_path}: {e}"));
+        return;
+    }
+    let golden = DynCanvas::read_ppm(golden_path)
+        .unwrap_or_else(|e| panic!("reading golden {golden_path}: {e}"));
+    assert_eq!(
+        (canvas.rows, canvas.cols),
+        (golden.rows, golden.cols),
+        "canvas size differs from golden {golden_path}"
+    );
+    let mut mismatches = vec![];
+    for row in 0..canvas.rows {
+        for col in 0..canvas.cols {
+            let (a, b) = (canvas.get(row, col), golden.get(row, col));
+            if pixel_distance(a, b) > tolerance {
+                mismatches.push((row, col, a, b));
+            }
+        }
+    }
+    if !mismatches.is_empty() {
+        let shown: Vec<String> = mismatches
+            .iter()
+            .take(10)
+            .map(|(row, col, a, b)| format!("  ({row}, {col}): got {}, want {}", a.pp(), b.pp()))
+            .collect();
+        let diff_path = format!("{golden_path}.diff.ppm");
+        let _ = canvas.diff(&golden, tolerance).write_ppm(&diff_path, PpmFormat::P6);
+        panic!(
+            "{} of {} pixels differ from {golden_path} by more than {tolerance} \
+             (diff in {diff_path}):\n{}",
+            mismatches.len(),
+            canvas.rows * canvas.cols,
+            shown.join("\n")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transformations::{view_transform, PI};
+
+    #[test]
+    fn axes_start_at_the_projected_origin_in_three_colors() {
+        let mut camera: Camera<41, 31> = Camera::new(PI / 3.0);
+        camera.set_transform(view_transform(
+            Point::from([3.0, 2.0, -5.0]),
+            Point::from([0.0, 0.0, 0.0]),
+            Vector::from([0.0, 1.0, 0.0]),
+        ));
+        let (ox, oy) = camera.project(Point::from([0.0, 0.0, 0.0])).unwrap();
+        assert!((0.0..41.0).contains(&ox) && (0.0..31.0).contains(&oy));
+        let mut canvas: Canvas<31, 41> = Canvas::new(255);
+        assert_eq!(canvas.draw_axes(&camera), 3);
+        assert_ne!(canvas.pixel_at(oy as usize, ox as usize), Pixel::black());
+        for color in [Pixel::red(), Pixel::green(), Pixel::blue()] {
+            let count = (0..31)
+                .flat_map(|row| (0..41).map(move |col| (row, col)))
+                .filter(|&(row, col)| canvas.pixel_at(row, col) == color)
+                .count();
+            assert!(count > 0, "no {color:?} pixels");
         }
     }
+    #[test]
+    fn try_set_out_of_range_is_an_error_and_leaves_neighbors_alone() {
+        let mut m: HeapMatrix<Pixel, 3, 4> = HeapMatrix::new(Pixel::black());
+        // Unchecked, (0, 4) would be (1, 0).
+        assert_eq!(
+            m.try_set(Pixel::white(), 0, 4),
+            Err(OutOfBounds {
+                row: 0,
+                col: 4,
+                rows: 3,
+                cols: 4
+            })
+        );
+        assert!(m.try_set(Pixel::white(), 3, 0).is_err());
+        assert_eq!(*m.try_get(1, 0).unwrap(), Pixel::black());
+        assert!(m.try_get(0, 4).is_err());
+        m.try_set(Pixel::white(), 2, 3).unwrap();
+        assert_eq!(*m.try_get(2, 3).unwrap(), Pixel::white());
+    }
+
+    #[test]
+    fn a_dyn_canvas_exports_a_ppm_with_its_runtime_size() {
+        let mut c = DynCanvas::new(7, 13, 255);
+        c.write_pixel(
+            Color {
+                r: 1.0,
+                g: 0.0,
+                b: 0.0,
+            },
+            2,
+            3,
+        );
+        assert_eq!(c.get(2, 3), Pixel::red());
+        let mut out = Vec::new();
+        c.write_ppm_to(&mut out, PpmFormat::P3).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(&lines[..3], &["P3", "13 7", "255"]);
+        assert_eq!(lines[3 + 2].split_whitespace().nth(3 * 3), Some("255"));
+    }
+
+    #[test]
+    fn a_bottom_left_origin_writes_row_zero_last() {
+        let mut c: Canvas<3, 2> = Canvas::new(255);
+        c.origin(Origin::BottomLeft);
+        c.write_pixel(
+            Color {
+                r: 1.0,
+                g: 0.0,
+                b: 0.0,
+            },
+            0,
+            0,
+        );
+        assert_eq!(c.pixel_at(0, 0), Pixel::red());
+        let mut out = Vec::new();
+        c.write_ppm_to(&mut out, PpmFormat::P3).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = text.lines().skip(3).collect();
+        assert_eq!(rows[0].split_whitespace().next(), Some("0"));
+        assert_eq!(rows[2].split_whitespace().next(), Some("255"));
+    }
+
+    #[test]
+    fn pixels_are_srgb_encoded_unless_the_canvas_is_linear() {
+        let grey = Color {
+            r: 0.5,
+            g: 0.5,
+            b: 0.5,
+        };
+        let mut c = DynCanvas::new(1, 2, 255);
+        c.write_pixel(grey, 0, 0);
+        assert_eq!(c.get(0, 0), Pixel::new(188, 188, 188));
+        c.set_color_space(OutputColorSpace::Linear);
+        c.write_pixel(grey, 0, 1);
+        assert_eq!(c.get(0, 1), Pixel::new(128, 128, 128));
+    }
+
+    #[test]
+    fn a_ppm_reads_back_what_was_written() {
+        let mut c = DynCanvas::new(2, 3, 255);
+        c.set(Pixel::new(10, 200, 30), 0, 2);
+        c.set(Pixel::blue(), 1, 0);
+        for format in [PpmFormat::P3, PpmFormat::P6] {
+            let mut out = Vec::new();
+            c.write_ppm_to(&mut out, format).unwrap();
+            let back = DynCanvas::from_ppm(&out).unwrap();
+            assert_eq!((back.rows, back.cols), (2, 3));
+            assert_eq!(back.pixels, c.pixels);
+        }
+    }
+
+    #[test]
+    fn ppm_samples_are_scaled_to_the_max_color_in_the_header() {
+        let mut c: Canvas<2, 2> = Canvas::new(128);
+        let white = Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        c.write_pixel(white, 0, 0);
+        c.set(Pixel::white(), 1, 1);
+        for format in [PpmFormat::P3, PpmFormat::P6] {
+            let mut out = Vec::new();
+            c.write_ppm_to(&mut out, format).unwrap();
+            let text = String::from_utf8_lossy(&out).into_owned();
+            assert_eq!(text.lines().nth(2), Some("128"));
+            let header_len: usize = text.lines().take(3).map(|l| l.len() + 1).sum();
+            let samples: Vec<u8> = match text.starts_with("P3") {
+                true => text.split_whitespace().skip(4).map(|s| s.parse().unwrap()).collect(),
+                false => out[header_len..].to_vec(),
+            };
+            assert_eq!(samples.len(), 12);
+            assert!(samples.iter().all(|&s| s <= 128));
+            assert_eq!(&samples[..3], &[128, 128, 128]);
+            // Reading it back restores the full-range pixels.
+            let back = DynCanvas::from_ppm(&out).unwrap();
+            assert_eq!(back.get(1, 1), Pixel::white());
+        }
+    }
+
+    #[test]
+    fn a_ppm_map_is_stored_in_the_world_top_row_first() {
+        let ppm = b"P3\n2 2\n255\n255 0 0  0 255 0\n0 0 255  255 255 255\n";
+        let map = DynCanvas::from_ppm(ppm).unwrap();
+        let mut w = World::new();
+        let id = map.add_to(&mut w);
+        assert_eq!(id, 0);
+        assert_eq!(w.texels, map.to_texels());
+        let image = w.images[id as usize];
+        assert_eq!((image.width, image.height), (2, 2));
+        // v = 1 is the top row of the file.
+        let red = Color {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        assert_eq!(image.image_at(&w.texels, 0.0, 1.0), red);
+    }
+
+    #[test]
+    fn albedo_maps_are_decoded_and_data_maps_are_not() {
+        let mut map = DynCanvas::from_ppm(b"P3\n1 1\n255\n128 128 128\n").unwrap();
+        let mut w = World::new();
+        let albedo = map.add_to(&mut w);
+        let roughness = map.add_data_to(&mut w);
+        let at = |id: u32| w.images[id as usize].image_at(&w.texels, 0.5, 0.5).r;
+        // sRGB 128 is about 0.216 linear; as a number it stays 128/255.
+        assert_almost_eq!(at(albedo), 0.2158605, 1e-4);
+        assert_almost_eq!(at(roughness), 128.0 / 255.0, 1e-6);
+        map.set_color_space(OutputColorSpace::Linear);
+        assert_eq!(map.to_texels(), map.to_data_texels());
+    }
+
+    #[test]
+    fn an_environment_map_is_decoded_to_linear_light() {
+        let map = DynCanvas::from_ppm(b"P3\n1 1\n255\n128 64 255\n").unwrap();
+        match map.to_environment() {
+            Environment::ImageSphere { width, texels } => {
+                assert_eq!(width, 1);
+                assert_eq!(texels, map.to_texels());
+                assert_almost_eq!(texels[0].r, 0.2158605, 1e-4);
+            }
+            other => panic!("expected an image sphere, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reading_a_ppm_skips_header_comments_and_rejects_garbage() {
+        let c = DynCanvas::from_ppm(b"P3\n# a comment\n1 1\n255\n1 2 3\n").unwrap();
+        assert_eq!(c.get(0, 0), Pixel::new(1, 2, 3));
+        assert!(DynCanvas::from_ppm(b"P5\n1 1\n255\n").is_err());
+        assert!(DynCanvas::from_ppm(b"P6\n2 2\n255\nabc").is_err());
+    }
+
+    #[test]
+    fn a_checkerboard_alternates_at_the_cell_boundary() {
+        let white = Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        let black = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        let c: Canvas<8, 12> = Canvas::checkerboard(4, white, black);
+        assert_eq!(c.pixel_at(0, 0), Pixel::white());
+        assert_eq!(c.pixel_at(3, 3), Pixel::white());
+        assert_eq!(c.pixel_at(0, 4), Pixel::black());
+        assert_eq!(c.pixel_at(4, 3), Pixel::black());
+        assert_eq!(c.pixel_at(4, 4), Pixel::white());
+    }
+
+    #[test]
+    fn a_gradient_runs_from_the_left_to_the_right_color() {
+        let left = Color {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        let right = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 1.0,
+        };
+        let c: Canvas<3, 10> = Canvas::horizontal_gradient(left, right);
+        for row in 0..3 {
+            assert_eq!(c.pixel_at(row, 0), Pixel::red());
+            assert_eq!(c.pixel_at(row, 9), Pixel::blue());
+        }
+        let mid = c.pixel_at(1, 5);
+        assert!(mid.r > 0 && mid.b > 0 && mid.g == 0);
+    }
+
+    #[test]
+    fn converting_a_canvas_keeps_its_pixels() {
+        let mut c: Canvas<3, 5> = Canvas::new(255);
+        c.set(Pixel::green(), 1, 4);
+        let argb = c.to_argb();
+        let d = DynCanvas::from(c);
+        assert_eq!((d.rows, d.cols), (3, 5));
+        assert_eq!(d.get(1, 4), Pixel::green());
+        assert_eq!(d.to_argb(), argb);
+    }
+
+    #[test]
+    fn identical_canvases_diff_to_black() {
+        let mut a: Canvas<4, 6> = Canvas::new(255);
+        let mut b: Canvas<4, 6> = Canvas::new(255);
+        a.set(Pixel::green(), 1, 3);
+        b.set(Pixel::green(), 1, 3);
+        let d = a.diff(&b, 0);
+        for row in 0..4 {
+            for col in 0..6 {
+                assert_eq!(d.pixel_at(row, col), Pixel::black());
+            }
+        }
+    }
+
+    #[test]
+    fn a_diff_highlights_only_the_changed_pixel() {
+        let a = DynCanvas::new(3, 4, 255);
+        let mut b = DynCanvas::new(3, 4, 255);
+        b.set(Pixel::new(0, 2, 0), 0, 0);
+        b.set(Pixel::new(0, 200, 0), 2, 1);
+        let d = a.diff(&b, 2);
+        for row in 0..3 {
+            for col in 0..4 {
+                let p = d.get(row, col);
+                if (row, col) == (2, 1) {
+                    assert!(p.r > 64 && p.g == 0 && p.b == 0);
+                } else {
+                    assert_eq!(p, Pixel::black());
+                }
+            }
+        }
+        assert!(a.diff(&b, 0).get(0, 0).r < d.get(2, 1).r);
+    }
+
+    #[test]
+    fn png_output_has_the_signature_and_header() {
+        let c = DynCanvas::new(2, 3, 255);
+        let mut out = Vec::new();
+        write_png_to(&mut out, c.cols, c.rows, &c.to_bytes()).unwrap();
+        assert_eq!(&out[..8], &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+        assert_eq!(&out[12..16], b"IHDR");
+        assert_eq!(&out[16..20], &3u32.to_be_bytes());
+        assert_eq!(&out[20..24], &2u32.to_be_bytes());
+        assert_eq!(&out[out.len() - 8..out.len() - 4], b"IEND");
+    }
+
+    #[test]
+    fn an_empty_png_is_an_error_not_a_panic() {
+        for (rows, cols) in [(2, 0), (0, 3), (0, 0)] {
+            let c = DynCanvas::new(rows, cols, 255);
+            let mut out = Vec::new();
+            let err = write_png_to(&mut out, c.cols, c.rows, &c.to_bytes()).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            assert!(out.is_empty());
+        }
+    }
+
+    #[test]
+    fn save_picks_the_format_from_the_extension() {
+        let c: Canvas<2, 3> = Canvas::horizontal_gradient(
+            Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            },
+            Color {
+                r: 1.0,
+                g: 0.5,
+                b: 0.25,
+            },
+        );
+        let dir = std::env::temp_dir();
+        let path = |name: &str| {
+            let p = dir.join(format!("rusttracer_save_{}_{name}", std::process::id()));
+            p.to_str().unwrap().to_string()
+        };
+        let (png, ppm, hdr) = (path("out.png"), path("out.PPM"), path("out.hdr"));
+        c.save(&png).unwrap();
+        c.save(&ppm).unwrap();
+        c.save(&hdr).unwrap();
+        let png_bytes = std::fs::read(&png).unwrap();
+        assert_eq!(&png_bytes[..8], &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+        let ppm_bytes = std::fs::read(&ppm).unwrap();
+        assert!(ppm_bytes.starts_with(b"P6\n3 2\n255\n"));
+        assert_eq!(ppm_bytes.len(), b"P6\n3 2\n255\n".len() + 2 * 3 * 3);
+        let hdr_bytes = std::fs::read(&hdr).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 3\n";
+        assert!(hdr_bytes.starts_with(header));
+        assert_eq!(hdr_bytes.len(), header.len() + 2 * 3 * 4);
+        // The last column is linear (1.0, 0.5, 0.25) again: exponent 1.
+        assert_eq!(&hdr_bytes[header.len() + 8..header.len() + 12], &[128, 64, 32, 129]);
+        for p in [&png, &ppm, &hdr] {
+            std::fs::remove_file(p).unwrap();
+        }
+        let unknown = path("out.bmp");
+        let err = c.save(&unknown).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!std::path::Path::new(&unknown).exists());
+    }
+
+    #[test]
+    fn crc32_matches_the_reference_check_value() {
+        assert_eq!(crc32(b"123456789".iter()), 0xcbf4_3926);
+    }
 }
@@ -1,3 +1,5 @@
+use crate::tuples::linear_to_srgb;
+use crate::tuples::srgb_to_linear;
 use crate::tuples::Color;
 use crate::tuples::Number;
 
@@ -6,6 +8,43 @@ pub trait PrettyPrint {
     fn pp(&self) -> String;
 }
 
+// How a linear render color is encoded into 8-bit pixels. `Srgb` (the default)
+// applies the sRGB transfer function so images look right on an ordinary
+// display; `Linear` writes the values unchanged, for pipelines that do their
+// own encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputColorSpace {
+    // The chapters in main.rs all write sRGB.
+    #[allow(dead_code)]
+    Linear,
+    #[default]
+    Srgb,
+}
+
+impl OutputColorSpace {
+    pub fn encode(self, color: Color) -> Color {
+        match self {
+            OutputColorSpace::Linear => color,
+            OutputColorSpace::Srgb => Color {
+                r: linear_to_srgb(color.r),
+                g: linear_to_srgb(color.g),
+                b: linear_to_srgb(color.b),
+            },
+        }
+    }
+    // Undo `encode`: a color as stored in this space back to linear light.
+    pub fn decode(self, color: Color) -> Color {
+        match self {
+            OutputColorSpace::Linear => color,
+            OutputColorSpace::Srgb => Color {
+                r: srgb_to_linear(color.r),
+                g: srgb_to_linear(color.g),
+                b: srgb_to_linear(color.b),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Pixel {
     pub r: u8,
@@ -39,6 +78,10 @@ impl Pixel {
             b: (color.b.mul(max as Number).round() as u8).max(min).min(max),
         }
     }
+    // An 8-bit pixel for linear `color`, encoded for `space`.
+    pub fn quantize(color: Color, space: OutputColorSpace) -> Pixel {
+        Pixel::clamp(0, 255, space.encode(color))
+    }
 }
 
 impl PrettyPrint for Pixel {
@@ -33,15 +33,17 @@
 
 use raycore::lights::Light;
 use raycore::render::Cam;
-use raycore::shapes::{HasMaterial, Primitive};
-use raycore::tuples::Color;
-use raycore::worlds::Scene;
+use raycore::shapes::Primitive;
+use raycore::tuples::{linear_to_srgb, Color};
+use raycore::worlds::{Fog, Scene, Sky};
 use spirv_std::glam::UVec3;
 use spirv_std::spirv;
 
-// Pack a linear Color (channels in roughly 0..1) into 0x00RRGGBB, clamping.
-fn pack_color(c: Color) -> u32 {
+// Pack a linear Color (channels in roughly 0..1) into 0x00RRGGBB, clamping,
+// sRGB-encoding each channel first when `srgb` is 1.
+fn pack_color(c: Color, srgb: u32) -> u32 {
     let to8 = |v: f32| -> u32 {
+        let v = if srgb == 1 { linear_to_srgb(v) } else { v };
         let v = if v < 0.0 {
             0.0
         } else if v > 1.0 {
@@ -54,11 +56,12 @@ fn pack_color(c: Color) -> u32 {
     (to8(c.r) << 16) | (to8(c.g) << 8) | to8(c.b)
 }
 
-use raycore::materials::lightning;
+use raycore::materials::{lightning_surface, material_color_at};
 use raycore::render::{Job, WfNode, WF_MAX_LIGHTS, WF_STACK};
 use raycore::rays::Ray;
 
 const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0 };
+const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0 };
 
 // Seed each pixel's path: one job (the primary ray, full weight, full depth) and a
 // zeroed accumulator.
@@ -114,7 +117,24 @@ pub fn wf_trace_cs(
     let s2 = s - 1;
     sp[idx] = s2;
     let job = jobs[idx * WF_STACK + s2 as usize];
-    let scene = Scene { objects, lights, child_indices, use_bounds: true };
+    let scene = Scene {
+        objects,
+        lights,
+        child_indices,
+        shadow_casters: &[],
+        use_shadow_casters: false,
+        use_bounds: true,
+        light_threshold: 0.0,
+        roulette_seed: 0,
+        pixel_spread: 0.0,
+        environment: &[],
+        environment_width: 0,
+        images: &[],
+        texels: &[],
+        sky: Sky::BLACK,
+        fog: Fog::NONE,
+        ambient_light: WHITE,
+    };
     let xs = scene.intersect_world(&job.ray);
     let hi = xs.hit_index();
     if hi == xs.len {
@@ -146,7 +166,24 @@ pub fn wf_shadow_cs(
     if nodes[idx].active == 0 {
         return;
     }
-    let scene = Scene { objects, lights, child_indices, use_bounds: true };
+    let scene = Scene {
+        objects,
+        lights,
+        child_indices,
+        shadow_casters: &[],
+        use_shadow_casters: false,
+        use_bounds: true,
+        light_threshold: 0.0,
+        roulette_seed: 0,
+        pixel_spread: 0.0,
+        environment: &[],
+        environment_width: 0,
+        images: &[],
+        texels: &[],
+        sky: Sky::BLACK,
+        fog: Fog::NONE,
+        ambient_light: WHITE,
+    };
     let over = nodes[idx].over_point;
     let mut li = 0usize;
     while li < lights.len() && li < WF_MAX_LIGHTS {
@@ -181,12 +218,17 @@ pub fn wf_shade_cs(
         return;
     }
     let object = &objects[node.object_id as usize];
-    let mut surface = BLACK;
+    let material = object.material_for(node.on_cap != 0);
+    let color = material_color_at(object, material, node.point, 0.0);
+    // An emissive surface glows whether or not any light reaches it, as in
+    // the CPU `surface_at`.
+    let mut surface = material.emissive;
     let mut li = 0usize;
     while li < lights.len() && li < WF_MAX_LIGHTS {
         surface = surface
-            + lightning(
-                object,
+            + lightning_surface(
+                material,
+                color,
                 lights[li],
                 node.point,
                 node.eyev,
@@ -200,7 +242,6 @@ pub fn wf_shade_cs(
     if node.remaining == 0 {
         return;
     }
-    let material = object.get_material();
     let reflective = material.reflective;
     let transparency = material.transparency;
     if reflective == 0.0 && transparency == 0.0 {
@@ -213,7 +254,15 @@ pub fn wf_shade_cs(
     if reflective > 0.0 {
         let s = sp[idx];
         if (s as usize) < WF_STACK {
-            let w = if both { reflective * reflectance } else { reflective };
+            // An opaque Fresnel surface reflects by Schlick too (as
+            // `fresnel_weight` on the CPU).
+            let w = if both {
+                reflective * reflectance
+            } else if material.fresnel_reflective != 0 && transparency == 0.0 {
+                reflective * node.schlick()
+            } else {
+                reflective
+            };
             jobs[idx * WF_STACK + s as usize] = Job {
                 ray: Ray { origin: node.over_point, direction: node.reflectv },
                 weight: node.weight * w,
@@ -251,5 +300,5 @@ pub fn wf_present_cs(
         return;
     }
     let idx = (y * cam.hsize + x) as usize;
-    out[idx] = pack_color(accum[idx]);
+    out[idx] = pack_color(accum[idx], cam.srgb);
 }
@@ -6,8 +6,8 @@
 // binary's existing `crate::<module>` / unqualified paths keep resolving exactly
 // as they did when these were local modules.
 pub use raycore::{
-    bounds, cones, csg, cubes, cylinders, groups, intersections, lights,
-    materials, matrices, patterns, planes, rays, shapes, spheres, texture_maps,
+    bounds, cones, csg, cubes, cylinders, disks, groups, intersections, lights,
+    materials, matrices, patterns, planes, rays, shapes, slabs, spheres, texture_maps,
     transformations, triangles, tuples, worlds,
 };
 
@@ -43,6 +43,16 @@ macro_rules! assert_almost_eq {
         );
     };
 }
+// Likewise `assert_approx_eq!`, for points and vectors (whose `==` is exact).
+#[cfg(test)]
+macro_rules! assert_approx_eq {
+    ($a: expr, $b: expr) => {
+        assert_approx_eq!($a, $b, "assert_approx_eq failed: {:?} != {:?}", $a, $b)
+    };
+    ($a: expr, $b: expr, $($arg: tt)+) => {
+        assert!($a.approx_eq(&$b, 1e-5), $($arg)+)
+    };
+}
 
 // Host-only modules: framebuffer, render driver, interactive viewport, OBJ
 // loading and PPM/pixel output.
@@ -568,12 +578,15 @@ fn chapter17() {
     let focal_distance = (to - from).magnitude();
     camera.set_focal_blur(0.12, focal_distance, 24);
 
+    // Focal blur keeps this render on the CPU, so `render_reporting` loses
+    // nothing over `render_auto` and shows how far along it is.
+    camera.set_report_progress(true);
     println!("chapter17: rendering {W}x{H} (this samples the lens + area light, so it is slow)...");
     let start = Instant::now();
-    let canvas = camera.render_auto(world);
+    let canvas = camera.render_reporting(&world);
     println!("chapter17: rendered in {:.2?}", start.elapsed());
-    let filename = "chapter17.ppm";
-    match canvas.write_ppm(filename, PpmFormat::P6) {
+    let filename = "chapter17.png";
+    match canvas.save(filename) {
         Err(_) => println!("Something went wrong!"),
         Ok(()) => println!("Succesfully written {filename}!"),
     }
@@ -855,6 +868,9 @@ fn load_teapot(smooth: bool) -> World {
     let text = std::fs::read_to_string("teapot.obj")
         .expect("teapot.obj not found in the working directory");
     let parser = obj_parser::parse_obj(&text);
+    if parser.degenerate > 0 {
+        eprintln!("teapot.obj: {} degenerate triangles", parser.degenerate);
+    }
     if smooth {
         parser.to_world_bvh_smooth(&mut world, 16, teapot_material);
     } else {
@@ -2160,11 +2176,12 @@ fn chapter1() -> std::io::Result<()> {
     const HEIGHT: usize = 550;
 
     let mut canvas: Canvas<HEIGHT, WIDTH> = Canvas::new(255);
+    canvas.origin(Origin::BottomLeft);
 
     for position in positions {
         let y = position.y().round().clamp(0.0, (HEIGHT - 1) as Number) as usize;
         let x = position.x().round().clamp(0.0, (WIDTH - 1) as Number) as usize;
-        canvas.set(Pixel::white(), HEIGHT - y, x);
+        canvas.set(Pixel::white(), y, x);
     }
 
     let filename = "chapter1.ppm";
@@ -8,7 +8,9 @@
 //                 becomes a smooth triangle, otherwise a flat one.
 //   g  name       starts a new named group; faces after it go into that group.
 // Anything else (including the gibberish the book throws at it) is counted in
-// `ignored` and skipped, so a malformed line never aborts the parse.
+// `ignored` and skipped, so a malformed line never aborts the parse. Zero-area
+// triangles are kept (they are never hit, and get a safe stand-in normal) but
+// counted in `degenerate`.
 
 use crate::materials::Material;
 use crate::shapes::{HasMaterial, Primitive, ShapeKind};
@@ -28,6 +30,8 @@ pub struct ObjGroup {
 #[derive(Debug)]
 pub struct ObjParser {
     pub ignored: usize,
+    // How many triangles had no area (see `Primitive::is_degenerate`).
+    pub degenerate: usize,
     // 1-indexed to match OBJ's numbering: index 0 is an unused placeholder so
     // that `vertices[1]` is the first vertex.
     pub vertices: Vec<Point>,
@@ -127,18 +131,14 @@ fn build_from_triangles(
 // pass through unchanged.
 fn smoothed(triangles: Vec<Primitive>) -> Vec<Primitive> {
     use std::collections::HashMap;
-    // Quantize a position into an integer key. 1e4 keeps ~4 decimals, far finer
-    // than the spacing between distinct vertices but coarse enough to ignore
-    // float noise.
-    fn key(p: Point) -> (i64, i64, i64) {
-        let q = |v: Number| (v * 10_000.0).round() as i64;
-        (q(p.x), q(p.y), q(p.z))
-    }
+    // A 1e-4 grid keeps ~4 decimals, far finer than the spacing between
+    // distinct vertices but coarse enough to ignore float noise.
+    let key = |p: Point| QuantizedPoint::with_grid(p, 1e-4);
 
     // Accumulate area-weighted face normals at each vertex. `e2.cross(e1)` matches
     // the orientation the flat triangle uses for its own normal, and its length is
     // proportional to the face area, so larger faces pull the average more.
-    let mut accum: HashMap<(i64, i64, i64), Vector> = HashMap::new();
+    let mut accum: HashMap<QuantizedPoint, Vector> = HashMap::new();
     for t in &triangles {
         if t.kind == ShapeKind::Triangle {
             let face = t.e2.cross(t.e1);
@@ -165,7 +165,7 @@ fn smoothed(triangles: Vec<Primitive>) -> Vec<Primitive> {
                         summed.normalize()
                     }
                 };
-                let flat = t.e2.cross(t.e1);
+                let flat = t.normal;
                 Primitive::smooth_triangle(
                     t.p1,
                     t.p2,
@@ -372,8 +372,14 @@ pub fn parse_obj(input: &str) -> ObjParser {
         }
     }
 
+    let degenerate = groups
+        .iter()
+        .flat_map(|g| &g.triangles)
+        .filter(|t| t.is_degenerate())
+        .count();
     ObjParser {
         ignored,
+        degenerate,
         vertices,
         normals,
         groups,
@@ -405,6 +411,25 @@ and came back the previous night.";
         assert_eq!(parser.ignored, 5);
     }
 
+    #[test]
+    fn degenerate_faces_are_kept_and_counted() {
+        let input = "\
+v 0 0 0
+v 1 0 0
+v 2 0 0
+v 0 1 0
+f 1 2 3
+f 1 2 4
+f 1 1 1";
+        let parser = parse_obj(input);
+        let triangles = &parser.default_group().triangles;
+        assert_eq!(triangles.len(), 3);
+        assert_eq!(parser.degenerate, 2);
+        for t in triangles {
+            assert!(!t.normal.x.is_nan() && !t.normal.y.is_nan() && !t.normal.z.is_nan());
+        }
+    }
+
     #[test]
     fn vertex_records() {
         let input = "\
@@ -175,11 +175,165 @@ impl BoundingBox {
     }
 }
 
+// A bounding sphere: the alternative broad-phase test a group can opt into
+// with `World::use_bounding_sphere`. For a roughly round mesh it hugs the
+// geometry better than a box (a ray through a box corner misses the sphere),
+// and the test itself is one quadratic discriminant instead of three slabs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point,
+    pub radius: Number,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Point, radius: Number) -> Self {
+        Self { center, radius }
+    }
+
+    // The sphere around `points`, centered on the middle of their box. Not the
+    // minimal enclosing sphere, but tight for the symmetric shapes it is for.
+    // Any infinite point (from an unbounded shape such as a plane) makes the
+    // radius infinite, around the origin, rather than dropping the point.
+    pub fn around(points: &[Point]) -> Self {
+        let finite = |p: &Point| p.x.is_finite() && p.y.is_finite() && p.z.is_finite();
+        if !points.iter().all(finite) {
+            return Self {
+                center: Point::default(),
+                radius: Number::INFINITY,
+            };
+        }
+        let mut b = BoundingBox::empty();
+        for p in points {
+            b.add_point(*p);
+        }
+        let center = Point {
+            x: (b.min.x + b.max.x) / 2.0,
+            y: (b.min.y + b.max.y) / 2.0,
+            z: (b.min.z + b.max.z) / 2.0,
+        };
+        let mut radius: Number = 0.0;
+        for p in points {
+            radius = radius.max((*p - center).magnitude());
+        }
+        Self { center, radius }
+    }
+
+    // Does the ray's line pass through (or touch) the sphere? Like
+    // `BoundingBox::intersects` this only answers yes/no for culling; a tangent
+    // ray counts as a hit so grazing rays still reach the children.
+    pub fn intersects(&self, ray: &Ray) -> bool {
+        let to_origin = ray.origin - self.center;
+        let a = ray.direction.dot(ray.direction);
+        let b = ray.direction.dot(to_origin);
+        let c = to_origin.dot(to_origin) - self.radius * self.radius;
+        b * b - a * c >= 0.0
+    }
+}
+
+impl BoundingBox {
+    // The eight corners, e.g. to fit a `BoundingSphere` around the box.
+    pub fn corners(&self) -> [Point; 8] {
+        let (lo, hi) = (self.min, self.max);
+        [
+            Point { x: lo.x, y: lo.y, z: lo.z },
+            Point { x: lo.x, y: lo.y, z: hi.z },
+            Point { x: lo.x, y: hi.y, z: lo.z },
+            Point { x: lo.x, y: hi.y, z: hi.z },
+            Point { x: hi.x, y: lo.y, z: lo.z },
+            Point { x: hi.x, y: lo.y, z: hi.z },
+            Point { x: hi.x, y: hi.y, z: lo.z },
+            Point { x: hi.x, y: hi.y, z: hi.z },
+        ]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::transformations::*;
 
+    #[test]
+    fn a_bounding_sphere_culls_a_ray_through_the_box_corner() {
+        let s = BoundingSphere::new(
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            1.0,
+        );
+        let b = BoundingBox::new(
+            Point {
+                x: -1.0,
+                y: -1.0,
+                z: -1.0,
+            },
+            Point {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        );
+        let corner = Ray {
+            origin: Point {
+                x: 0.9,
+                y: 0.9,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        assert!(b.intersects(&corner));
+        assert!(!s.intersects(&corner));
+        let tangent = Ray {
+            origin: Point {
+                x: 1.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        assert!(s.intersects(&tangent));
+    }
+
+    #[test]
+    fn a_bounding_sphere_around_points_reaches_the_farthest() {
+        let s = BoundingSphere::around(&[
+            Point {
+                x: -2.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point {
+                x: 2.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+        ]);
+        assert_eq!(
+            s.center,
+            Point {
+                x: 0.0,
+                y: 0.5,
+                z: 0.0
+            }
+        );
+        assert_almost_eq!(s.radius, (4.0 as Number + 0.25).sqrt());
+    }
+
     #[test]
     fn an_empty_box_has_inverted_bounds() {
         let b = BoundingBox::empty();
@@ -25,7 +25,7 @@ fn intersect_caps(prim: &Primitive, ray: &Ray, object_id: usize, xs: &mut Inters
         let bound = bounds[bi];
         let t = (bound - ray.origin.y()) / ray.direction.y();
         if check_caps(ray, t, bound.abs()) {
-            xs.push(Intersection::new(t, object_id));
+            xs.push(Intersection::cap(t, object_id));
         }
         bi += 1;
     }
@@ -82,20 +82,31 @@ pub fn cone_intersect(prim: &Primitive, ray: &Ray, object_id: usize, xs: &mut In
 pub fn cone_normal_at(prim: &Primitive, point: &Point) -> Vector {
     let dist = point.x().powi(2) + point.z().powi(2);
 
-    if dist < 1.0 + EPSILON && point.y() >= prim.maximum - EPSILON {
+    // A cap is as wide as the cone at its height, |y|, so the cap test
+    // compares against that rather than the cylinder's unit radius.
+    if dist < prim.maximum.powi(2) + EPSILON && point.y() >= prim.maximum - EPSILON {
         return Vector {
             x: 0.0,
             y: 1.0,
             z: 0.0,
         };
     }
-    if dist < 1.0 + EPSILON && point.y() <= prim.minimum + EPSILON {
+    if dist < prim.minimum.powi(2) + EPSILON && point.y() <= prim.minimum + EPSILON {
         return Vector {
             x: 0.0,
             y: -1.0,
             z: 0.0,
         };
     }
+    // At the apex the side's normal shrinks to zero, which normalizes to NaN;
+    // point along the axis instead, the way the lower half's normals lean.
+    if dist + point.y().powi(2) < EPSILON {
+        return Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+    }
     let mut y = (point.x().powi(2) + point.z().powi(2)).sqrt();
     if point.y() > 0.0 {
         y = -y;
@@ -218,7 +229,7 @@ fn computing_the_normal_vector_on_a_cone() {
             },
             normal: Vector {
                 x: 0.0,
-                y: 0.0,
+                y: 1.0,
                 z: 0.0,
             },
         },
@@ -255,6 +266,18 @@ fn computing_the_normal_vector_on_a_cone() {
     }
 }
 
+#[test]
+fn the_normal_at_a_cones_apex_is_a_unit_vector() {
+    let shape = Primitive::cone();
+    let n = shape.normal_at(&Point {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    });
+    assert!(n.x.is_finite() && n.y.is_finite() && n.z.is_finite());
+    assert_almost_eq!(n.magnitude(), 1.0);
+}
+
 #[test]
 fn intersecting_a_cones_end_caps() {
     let mut shape = Primitive::cone();
@@ -323,3 +346,38 @@ fn intersecting_a_cones_end_caps() {
         assert_eq!(xs.count(), count);
     }
 }
+#[test]
+fn a_ray_up_the_axis_of_a_closed_double_cone_crosses_the_apex() {
+    let mut shape = Primitive::cone();
+    shape.set_minimum(-1.0);
+    shape.set_maximum(1.0);
+    shape.set_closed(true);
+    let r = Ray {
+        origin: Point::from([0.0, -2.0, 0.0]),
+        direction: Vector::from([0.0, 1.0, 0.0]),
+    };
+    let mut xs = Intersections::empty();
+    cone_intersect(&shape, &r, 0, &mut xs);
+    // The bottom cap, both nappes touching at the apex, then the top cap.
+    let mut ts: Vec<Number> = (0..xs.count()).map(|i| xs[i].t).collect();
+    ts.sort_by(|a, b| a.total_cmp(b));
+    assert_eq!(ts.len(), 4);
+    assert_almost_eq!(ts[0], 1.0);
+    assert_almost_eq!(ts[1], 2.0);
+    assert_almost_eq!(ts[2], 2.0);
+    assert_almost_eq!(ts[3], 3.0);
+}
+#[test]
+fn the_normal_on_a_wide_cap_of_a_cone_points_along_the_axis() {
+    let mut shape = Primitive::cone();
+    shape.set_minimum(-3.0);
+    shape.set_maximum(2.0);
+    shape.set_closed(true);
+    let top = cone_normal_at(&shape, &Point::from([1.5, 2.0, 0.0]));
+    assert_eq!(top, Vector::from([0.0, 1.0, 0.0]));
+    let bottom = cone_normal_at(&shape, &Point::from([0.0, -3.0, -2.5]));
+    assert_eq!(bottom, Vector::from([0.0, -1.0, 0.0]));
+    // The wall just below the rim still leans outward.
+    let wall = cone_normal_at(&shape, &Point::from([1.9, 1.9, 0.0]));
+    assert_eq!(wall, Vector::from([1.9, -1.9, 0.0]));
+}
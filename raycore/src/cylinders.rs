@@ -25,7 +25,7 @@ fn intersect_caps(prim: &Primitive, ray: &Ray, object_id: usize, xs: &mut Inters
         let bound = bounds[bi];
         let t = (bound - ray.origin.y()) / ray.direction.y();
         if check_caps(ray, t) {
-            xs.push(Intersection::new(t, object_id));
+            xs.push(Intersection::cap(t, object_id));
         }
         bi += 1;
     }
@@ -516,6 +516,31 @@ fn intersecting_the_caps_of_a_closed_cylinder() {
     }
 }
 #[test]
+fn a_capped_tube_built_with_the_setters_is_hit_on_both_caps() {
+    let mut tube = Primitive::cylinder();
+    tube.set_minimum(1.0);
+    tube.set_maximum(2.0);
+    tube.set_closed(true);
+    assert_eq!((tube.minimum, tube.maximum, tube.closed), (1.0, 2.0, 1));
+    let r = Ray {
+        origin: Point::from([0.0, 3.0, 0.0]),
+        direction: Vector::from([0.0, -1.0, 0.0]),
+    };
+    let mut xs = Intersections::empty();
+    cylinder_intersect(&tube, &r, 0, &mut xs);
+    assert_eq!(xs.count(), 2);
+    let mut ts = [xs[0].t, xs[1].t];
+    ts.sort_by(|a, b| a.total_cmp(b));
+    assert_eq!(ts, [1.0, 2.0]);
+    let top = cylinder_normal_at(&tube, &Point::from([0.5, 2.0, 0.0]));
+    assert_eq!(top, Vector::from([0.0, 1.0, 0.0]));
+    // Uncapped it is an open tube the ray passes straight down.
+    tube.set_closed(false);
+    let mut xs = Intersections::empty();
+    cylinder_intersect(&tube, &r, 0, &mut xs);
+    assert_eq!(xs.count(), 0);
+}
+#[test]
 fn the_normal_vector_on_a_cylinders_end_caps() {
     let mut cyl = Primitive::cylinder();
     cyl.minimum = 1.0;
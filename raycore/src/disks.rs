@@ -0,0 +1,117 @@
+use crate::intersections::*;
+use crate::planes::ray_plane_intersection;
+use crate::rays::*;
+use crate::shapes::Primitive;
+use crate::tuples::*;
+
+// A flat disk in the xz plane (y = 0), centered on the origin. It reuses the
+// cylinder's `minimum`/`maximum` fields as its inner and outer radius: the ray
+// must cross y = 0 at a radial distance within [inner, outer], so an inner
+// radius above 0 punches a hole through the middle and makes an annulus.
+pub fn disk_intersect(prim: &Primitive, ray: &Ray, object_id: usize, xs: &mut Intersections) {
+    let Some(t) = ray_plane_intersection(ray, 0.0) else {
+        return;
+    };
+    let x = ray.origin.x() + t * ray.direction.x();
+    let z = ray.origin.z() + t * ray.direction.z();
+    let dist2 = x * x + z * z;
+    if dist2 >= prim.minimum * prim.minimum && dist2 <= prim.maximum * prim.maximum {
+        xs.push(Intersection::new(t, object_id));
+    }
+}
+
+// Like a plane, a disk faces straight up everywhere.
+pub fn disk_normal_at(_: &Point) -> Vector {
+    Vector {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn down_ray(x: Number, z: Number) -> Ray {
+        Ray {
+            origin: Point { x, y: 1.0, z },
+            direction: Vector {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn a_ray_hits_the_face_of_a_disk() {
+        let disk = Primitive::disk(0.5, 2.0);
+        let mut xs = Intersections::empty();
+        disk_intersect(&disk, &down_ray(1.0, 0.5), 0, &mut xs);
+        assert_eq!(xs.count(), 1);
+        assert_eq!(xs[0].t, 1.0);
+    }
+
+    #[test]
+    fn a_ray_misses_through_the_central_hole() {
+        let disk = Primitive::disk(0.5, 2.0);
+        let mut xs = Intersections::empty();
+        disk_intersect(&disk, &down_ray(0.2, -0.2), 0, &mut xs);
+        assert_eq!(xs.count(), 0);
+    }
+
+    #[test]
+    fn a_ray_misses_outside_the_outer_radius() {
+        let disk = Primitive::disk(0.5, 2.0);
+        let mut xs = Intersections::empty();
+        disk_intersect(&disk, &down_ray(1.5, 1.5), 0, &mut xs);
+        assert_eq!(xs.count(), 0);
+    }
+
+    #[test]
+    fn a_solid_disk_is_hit_at_its_center() {
+        let disk = Primitive::disk(0.0, 1.0);
+        let mut xs = Intersections::empty();
+        disk_intersect(&disk, &down_ray(0.0, 0.0), 0, &mut xs);
+        assert_eq!(xs.count(), 1);
+    }
+
+    #[test]
+    fn a_ray_parallel_to_a_disk_misses() {
+        let disk = Primitive::disk(0.0, 1.0);
+        let r = Ray {
+            origin: Point {
+                x: -2.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        };
+        let mut xs = Intersections::empty();
+        disk_intersect(&disk, &r, 0, &mut xs);
+        assert_eq!(xs.count(), 0);
+    }
+
+    #[test]
+    fn the_normal_of_a_disk_points_up() {
+        let disk = Primitive::disk(0.5, 2.0);
+        let n = disk.local_normal_at(&Point {
+            x: 1.0,
+            y: 0.0,
+            z: -1.0,
+        });
+        assert_eq!(
+            n,
+            Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0
+            }
+        );
+    }
+}
@@ -9,6 +9,7 @@
 
 #[cfg(test)]
 mod tests {
+    use crate::csg::CsgOperation;
     use crate::matrices::*;
     use crate::rays::*;
     use crate::shapes::*;
@@ -214,7 +215,7 @@ mod tests {
                 z: -10.0,
             },
         );
-        assert_eq!(
+        assert_approx_eq!(
             p,
             Point {
                 x: 0.0,
@@ -224,6 +225,79 @@ mod tests {
         );
     }
 
+    #[test]
+    fn flattening_a_group_composes_each_leafs_transform() {
+        let mut w = World::new();
+        let mut g = Primitive::group();
+        g.set_transform(scaling(2.0, 2.0, 2.0));
+        let g = w.add_object(g);
+        let offsets = [-3.0, 0.0, 3.0];
+        let mut ids = vec![];
+        for x in offsets {
+            let mut sphere = Primitive::sphere();
+            sphere.set_transform(translation(x, 0.0, 0.0));
+            ids.push(w.add_child(g, sphere));
+        }
+        let leaves = w.flatten(g, Matrix::identity());
+        assert_eq!(leaves.len(), 3);
+        for (i, (transform, leaf)) in leaves.iter().enumerate() {
+            assert_eq!(leaf.kind, ShapeKind::Sphere);
+            assert_eq!(
+                *transform,
+                scaling(2.0, 2.0, 2.0) * translation(offsets[i], 0.0, 0.0)
+            );
+            assert_eq!(*transform, w.world_transform(ids[i]));
+        }
+        // The enclosing transform goes on the outside.
+        let moved = w.flatten(g, translation(0.0, 1.0, 0.0));
+        assert_eq!(
+            moved[2].0,
+            translation(0.0, 1.0, 0.0) * scaling(2.0, 2.0, 2.0) * translation(3.0, 0.0, 0.0)
+        );
+        // A leaf flattens to itself.
+        assert_eq!(w.flatten(ids[0], Matrix::identity()).len(), 1);
+    }
+    #[test]
+    fn flattening_a_csg_node_reaches_both_sides() {
+        let mut w = World::new();
+        let csg = w.add_object(Primitive::csg(CsgOperation::Difference));
+        let left = w.add_object(Primitive::cube());
+        let mut right = Primitive::sphere();
+        right.set_transform(translation(0.5, 0.0, 0.0));
+        let right = w.add_object(right);
+        w.set_csg_children(csg, left, right);
+        let leaves = w.flatten(csg, Matrix::identity());
+        let kinds: Vec<ShapeKind> = leaves.iter().map(|(_, p)| p.kind).collect();
+        assert_eq!(kinds, vec![ShapeKind::Cube, ShapeKind::Sphere]);
+        assert_eq!(leaves[1].0, translation(0.5, 0.0, 0.0));
+    }
+    #[test]
+    fn a_child_reports_its_composed_world_transform() {
+        let mut w = World::new();
+        let mut g1 = Primitive::group();
+        g1.set_transform(translation(1.0, 2.0, 3.0));
+        let g1 = w.add_object(g1);
+        let mut g2 = Primitive::group();
+        g2.set_transform(rotation_y(PI / 2.0));
+        let g2 = w.add_child(g1, g2);
+        let mut sphere = Primitive::sphere();
+        sphere.set_transform(translation(5.0, 0.0, 0.0));
+        let s = w.add_child(g2, sphere);
+        assert_eq!(w.world_transform(g1), translation(1.0, 2.0, 3.0));
+        assert_eq!(
+            w.world_transform(s),
+            translation(1.0, 2.0, 3.0) * rotation_y(PI / 2.0) * translation(5.0, 0.0, 0.0)
+        );
+        // It agrees with the link-by-link inverse walk.
+        let origin = Point {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let placed = w.world_transform(s) * origin;
+        assert_approx_eq!(w.world_to_object(s, placed), origin);
+    }
+
     #[test]
     fn finding_the_normal_on_a_child_object() {
         let mut w = World::new();
@@ -21,6 +21,10 @@ pub struct Intersection {
     // `Intersection::with_uv` so its normal can be interpolated across the face.
     pub u: Number,
     pub v: Number,
+    // 1 when the hit is on an end cap of a closed cylinder or cone (see
+    // `Intersection::cap`), 0 for everything else; a cap can be shaded with its
+    // own material (`Primitive::set_cap_material`).
+    pub on_cap: u32,
 }
 pub struct Computations {
     pub t: Number,
@@ -34,6 +38,7 @@ pub struct Computations {
     pub n1: Number,
     pub n2: Number,
     pub under_point: Point,
+    pub on_cap: bool,
 }
 
 impl Computations {
@@ -55,14 +60,40 @@ impl Computations {
         let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
         r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
+    // The direction of the refracted ray at this hit, or None under total
+    // internal reflection. Between two media with the same index (two touching
+    // glass objects, say) there's no real interface, so the ray carries on
+    // exactly as it came rather than picking up rounding from Snell's law.
+    pub fn refracted_direction(&self) -> Option<Vector> {
+        if (self.n1 - self.n2).abs() < EPSILON {
+            return Some(-self.eyev);
+        }
+        let n_ratio = self.n1 / self.n2;
+        let cos_i = self.eyev.dot(self.normalv);
+        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(self.normalv * (n_ratio * cos_i - cos_t) - self.eyev * n_ratio)
+    }
 }
 impl Intersection {
+    // `xs` must be every intersection along `ray`, sorted, with `self` among
+    // them: n1 and n2 come from replaying which objects the ray has entered
+    // and left up to `self`, so a list cut short (just the hit, say) gets
+    // them wrong wherever transparent objects nest or overlap.
     pub fn prepare_computations(
         &self,
         ray: &Ray,
         world: &Scene,
         xs: &Intersections,
     ) -> Computations {
+        #[cfg(feature = "std")]
+        debug_assert!(
+            (0..xs.len).any(|k| xs.xs[k].t == self.t && xs.xs[k].object_id == self.object_id),
+            "prepare_computations: the hit is missing from xs"
+        );
         let mut n1 = 1.0;
         let mut n2 = 1.0;
         // Fixed-capacity stand-in for the book's `containers` Vec: the set of
@@ -71,11 +102,13 @@ impl Intersection {
         let mut clen = 0usize;
         for idx in 0..xs.len {
             let i = xs.xs[idx];
-            let is_hit = i.t == self.t;
+            // Matched on the object too: another surface at the same t (two
+            // touching spheres) is a different boundary.
+            let is_hit = i.t == self.t && i.object_id == self.object_id;
             if is_hit {
                 if clen > 0 {
                     let object_id = containers[clen - 1];
-                    n1 = world.objects[object_id].get_material().refractive_index;
+                    n1 = world.objects[object_id].material_ref().refractive_index;
                 }
             }
             // Find `i.object_id` in containers[0..clen]; if present remove it
@@ -104,8 +137,9 @@ impl Intersection {
             if is_hit {
                 if clen > 0 {
                     let object_id = containers[clen - 1];
-                    n2 = world.objects[object_id].get_material().refractive_index;
+                    n2 = world.objects[object_id].material_ref().refractive_index;
                 }
+                break;
             }
         }
         let point = ray.position(self.t);
@@ -134,6 +168,7 @@ impl Intersection {
             n1: n1,
             n2: n2,
             under_point: under_point,
+            on_cap: self.on_cap != 0,
         }
     }
 }
@@ -194,16 +229,31 @@ impl Intersections {
     pub fn count(&self) -> usize {
         self.len
     }
+    // The intersection at `index`, or None past `len`. `xs[index]` only panics
+    // beyond MAX_XS and quietly reads padding between `len` and there, so
+    // loops over a list of unknown length should use this instead.
+    pub fn get(&self, index: usize) -> Option<&Intersection> {
+        if index < self.len {
+            Some(&self.xs[index])
+        } else {
+            None
+        }
+    }
     // Index of the nearest positive-t hit, or `self.len` if there is none.
     // rust-gpu 0.9 can't lower `Option<Intersection>` (an Option with a struct
     // payload), so the GPU trace path uses this sentinel-index form; the caller
     // reads `xs.xs[idx]` when `idx != xs.len`.
     pub fn hit_index(&self) -> usize {
+        self.hit_index_within(0.0, Number::INFINITY)
+    }
+    // `hit_index` restricted to near < t <= far: the nearest hit inside a clip
+    // range, skipping anything in front of `near` or beyond `far`.
+    pub fn hit_index_within(&self, near: Number, far: Number) -> usize {
         let mut best = self.len; // sentinel: none found yet
         let mut idx = 0;
         while idx < self.len {
             let t = self.xs[idx].t;
-            if t > 0.0 && (best == self.len || t < self.xs[best].t) {
+            if t > near && t <= far && (best == self.len || t < self.xs[best].t) {
                 best = idx;
             }
             idx += 1;
@@ -214,17 +264,63 @@ impl Intersections {
     // GPU path calls `hit_index` directly (so this Option<struct> is never lowered
     // to SPIR-V).
     pub fn hit(&self) -> Option<Intersection> {
-        let i = self.hit_index();
+        self.hit_after(0.0)
+    }
+    // The nearest hit with t > `t_min`, skipping anything closer: `hit` with a
+    // near limit, for looking past the front of an object. `hit` is
+    // `hit_after(0.0)`.
+    pub fn hit_after(&self, t_min: Number) -> Option<Intersection> {
+        let i = self.hit_index_within(t_min, Number::INFINITY);
+        if i == self.len {
+            None
+        } else {
+            Some(self.xs[i])
+        }
+    }
+    // Like `hit_index`, but without the t > 0 filter: the intersection closest
+    // to the ray origin on either side (smallest |t|). For inside/containment
+    // queries, where a surface behind the origin matters as much as one ahead.
+    pub fn hit_index_allowing_negative(&self) -> usize {
+        let mut best = self.len;
+        let mut idx = 0;
+        while idx < self.len {
+            let t = self.xs[idx].t.abs();
+            if best == self.len || t < self.xs[best].t.abs() {
+                best = idx;
+            }
+            idx += 1;
+        }
+        best
+    }
+    // Option-returning convenience over `hit_index_allowing_negative`. `hit`
+    // keeps ignoring negative t.
+    pub fn hit_allowing_negative(&self) -> Option<Intersection> {
+        let i = self.hit_index_allowing_negative();
         if i == self.len {
             None
         } else {
             Some(self.xs[i])
         }
     }
+    // Drop every intersection with `object_id`, keeping the rest in order.
+    pub fn remove_object(&mut self, object_id: usize) {
+        let mut kept = 0;
+        let mut idx = 0;
+        while idx < self.len {
+            if self.xs[idx].object_id != object_id {
+                self.xs[kept] = self.xs[idx];
+                kept += 1;
+            }
+            idx += 1;
+        }
+        self.len = kept;
+    }
     // Append without sorting. Sorting on every append made a scene-wide intersect
     // do O(objects) sorts of a growing list. Callers that need t-order sort once
     // at the point of use: `intersect_world` before returning, and
     // `filter_intersections` for CSG. `hit()` scans linearly and needs no order.
+    // Intersections are copied whole, so a smooth triangle's u/v and a cap flag
+    // reach `prepare_computations` however deep in groups the hit was.
     pub fn extend(&mut self, other: &Intersections) -> () {
         for idx in 0..other.len {
             self.push(other.xs[idx]);
@@ -245,6 +341,19 @@ impl Intersections {
             i += 1;
         }
     }
+    // The live intersections as a JSON array of `{"t", "object_id"}` objects in
+    // ascending t, for dumping exactly what a ray hit while debugging a render.
+    // Non-finite t values are written as `null` to keep the output valid JSON.
+    #[cfg(feature = "std")]
+    pub fn to_debug_json(&self) -> String {
+        let mut sorted = self.clone();
+        sorted.sort();
+        let entries: Vec<String> = sorted.xs[..sorted.len]
+            .iter()
+            .map(|i| format!("{{\"t\":{},\"object_id\":{}}}", json_number(i.t), i.object_id))
+            .collect();
+        format!("[{}]", entries.join(","))
+    }
     // Build from a Vec, copying items in and sorting. Test-only: it keeps every
     // existing `Intersections::new(vec![...])` test working verbatim.
     #[cfg(test)]
@@ -257,6 +366,15 @@ impl Intersections {
         result
     }
 }
+// A JSON number, or `null` for NaN/infinity (which JSON cannot represent).
+#[cfg(feature = "std")]
+pub fn json_number(n: Number) -> String {
+    if n.is_finite() {
+        format!("{n}")
+    } else {
+        "null".to_string()
+    }
+}
 impl Index<usize> for Intersections {
     type Output = Intersection;
     fn index(&self, index: usize) -> &Self::Output {
@@ -270,12 +388,26 @@ impl Intersection {
             object_id,
             u: 0.0,
             v: 0.0,
+            on_cap: 0,
+        }
+    }
+    // A hit on an end cap of a closed cylinder or cone.
+    pub const fn cap(t: Number, object_id: usize) -> Self {
+        Self {
+            on_cap: 1,
+            ..Self::new(t, object_id)
         }
     }
     // Used by smooth triangles, which record where on the face the ray landed so
     // the surface normal can be interpolated from the three vertex normals.
     pub const fn with_uv(t: Number, object_id: usize, u: Number, v: Number) -> Self {
-        Self { t, object_id, u, v }
+        Self {
+            t,
+            object_id,
+            u,
+            v,
+            on_cap: 0,
+        }
     }
 }
 #[cfg(test)]
@@ -310,6 +442,38 @@ mod tests {
         assert_eq!(xs[1].t, 2.0);
     }
     #[test]
+    fn getting_an_intersection_past_the_end_is_none() {
+        let i1 = Intersection::new(1.0, 0);
+        let i2 = Intersection::new(2.0, 1);
+        let xs = Intersections::new(vec![i1, i2]);
+        assert_eq!(xs.get(0), Some(&i1));
+        assert_eq!(xs.get(2), None);
+        assert_eq!(Intersections::empty().get(0), None);
+    }
+    #[test]
+    fn the_hit_allowing_negative_t_when_all_intersections_are_behind() {
+        let i1 = Intersection::new(-2.0, 0);
+        let i2 = Intersection::new(-1.0, 0);
+        let i3 = Intersection::new(-5.0, 0);
+        let xs = Intersections::new(vec![i1, i2, i3]);
+        assert_eq!(xs.hit(), None);
+        assert_eq!(xs.hit_allowing_negative(), Some(i2));
+        assert_eq!(Intersections::empty().hit_allowing_negative(), None);
+    }
+    #[test]
+    fn intersections_export_as_sorted_debug_json() {
+        let xs = Intersections::new(vec![
+            Intersection::new(2.5, 1),
+            Intersection::new(-1.0, 0),
+            Intersection::new(Number::INFINITY, 2),
+        ]);
+        assert_eq!(
+            xs.to_debug_json(),
+            r#"[{"t":-1,"object_id":0},{"t":2.5,"object_id":1},{"t":null,"object_id":2}]"#
+        );
+        assert_eq!(Intersections::empty().to_debug_json(), "[]");
+    }
+    #[test]
     fn the_hit_when_all_intersections_have_positive_t() {
         let i1 = Intersection::new(1.0, 0);
         let i2 = Intersection::new(2.0, 1);
@@ -344,6 +508,18 @@ mod tests {
         assert_eq!(i.unwrap(), i4);
     }
     #[test]
+    fn the_hit_after_a_t_min_skips_closer_intersections() {
+        let i1 = Intersection::new(5.0, 0);
+        let i2 = Intersection::new(7.0, 1);
+        let i3 = Intersection::new(-3.0, 2);
+        let i4 = Intersection::new(2.0, 3);
+        let xs = Intersections::new(vec![i1, i2, i3, i4]);
+        assert_eq!(xs.hit_after(0.0), xs.hit());
+        assert_eq!(xs.hit_after(2.0).unwrap(), i1);
+        assert_eq!(xs.hit_after(6.0).unwrap(), i2);
+        assert_eq!(xs.hit_after(7.0), None);
+    }
+    #[test]
     fn precomputing_the_state_of_an_intersection() {
         let r = Ray {
             origin: Point {
@@ -361,7 +537,7 @@ mod tests {
         let shape = Primitive::sphere();
         w.objects.append(&mut vec![shape]);
         let i = Intersection::new(4.0, 0);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         assert_eq!(comps.t, i.t);
         assert_eq!(comps.object_id, i.object_id);
         assert_eq!(
@@ -407,7 +583,7 @@ mod tests {
         let i = Intersection::new(4.0, 0);
         let mut w = World::new();
         w.objects.append(&mut vec![shape]);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         assert_eq!(comps.inside, false);
     }
     #[test]
@@ -428,7 +604,7 @@ mod tests {
         let i = Intersection::new(1.0, 0);
         let mut w = World::new();
         w.objects.append(&mut vec![shape]);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         assert_eq!(
             comps.point,
             Point {
@@ -509,6 +685,48 @@ mod tests {
         }
     }
     #[test]
+    fn nested_glass_gets_the_book_n1_and_n2_from_a_traced_ray() {
+        // The same three spheres, but the list comes from the world the way
+        // the renderer gets it, rather than written out by hand.
+        let mut w = World::new();
+        for (index, transform) in [
+            (1.5, scaling(2.0, 2.0, 2.0)),
+            (2.0, translation(0.0, 0.0, -0.25)),
+            (2.5, translation(0.0, 0.0, 0.25)),
+        ] {
+            let mut material = Material::glass();
+            material.set_refractive_index(index);
+            w.add_object(Primitive::with(Primitive::sphere, transform, material));
+        }
+        let r = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -4.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        let xs = w.intersect_world(&r);
+        let expected = [
+            (0, 1.0, 1.5),
+            (1, 1.5, 2.0),
+            (2, 2.0, 2.5),
+            (1, 2.5, 2.5),
+            (2, 2.5, 1.5),
+            (0, 1.5, 1.0),
+        ];
+        assert_eq!(xs.count(), expected.len());
+        for (index, (object_id, n1, n2)) in expected.into_iter().enumerate() {
+            let comps = xs[index].prepare_computations(&r, &w.scene(), &xs);
+            assert_eq!(comps.object_id, object_id);
+            assert_eq!((comps.n1, comps.n2), (n1, n2));
+        }
+    }
+    #[test]
     fn the_under_point_is_the_offset_below_the_surface() {
         let r = Ray {
             origin: Point {
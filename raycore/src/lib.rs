@@ -20,12 +20,14 @@
 #![cfg_attr(feature = "gpu", feature(const_fn_floating_point_arithmetic))]
 
 pub mod tuples;
+pub mod spectrum;
 pub mod matrices;
 pub mod transformations;
 pub mod rays;
 pub mod materials;
 pub mod patterns;
 pub mod texture_maps;
+pub mod font;
 pub mod lights;
 pub mod bounds;
 pub mod intersections;
@@ -35,6 +37,8 @@ pub mod planes;
 pub mod cubes;
 pub mod cylinders;
 pub mod cones;
+pub mod disks;
+pub mod slabs;
 pub mod triangles;
 pub mod groups;
 pub mod csg;
@@ -1,22 +1,44 @@
+use crate::transformations::PI;
 use crate::tuples::*;
+#[cfg(feature = "std")]
+use crate::worlds::Scene;
+#[cfg(feature = "std")]
+use std::sync::Arc;
 
 // A single flat, tagged light struct so the same layout works on the CPU and on
 // rust-gpu/SPIR-V (no data-carrying enums). `kind` selects the behavior:
-//   0 = point light: a single emitter at `position`.
+//   0 = point light: a single emitter at `position`. A positive `softness`
+//       fakes a penumbra: shadow rays aim at SOFT_SHADOW_SAMPLES points on a
+//       disk of that radius around `position`, facing the shaded point, and
+//       the lit fraction is returned. 0 (the default) is a hard shadow.
 //   1 = area light: a `usteps` x `vsteps` grid of cells spanning the rectangle
 //       `corner + full_uvec + full_vvec`. Shadow rays are cast to a point in each
 //       cell and averaged, so an occluder casts a soft penumbra rather than a hard
 //       edge. `uvec`/`vvec` are the per-cell step vectors and `position` is the
 //       rectangle's center (used where a single point is needed).
+//   2 = sphere light: a ball of `radius` centered on `position`, sampled on a
+//       `usteps` (around) x `vsteps` (pole to pole) grid of equal-area cells
+//       over its surface.
+//   3 = disk light: a disk of `radius` centered on `position`, spanned by the
+//       unit in-plane axes `uvec`/`vvec`, sampled on `usteps` (around) x
+//       `vsteps` (center to rim) equal-area cells.
+//   4 = spot light: a point emitter at `position` shining a cone along
+//       `direction`. Full strength inside the inner cone, dark outside the
+//       outer one, and in between `t^falloff`, where t runs linearly from 0 at
+//       the outer edge to 1 at the inner one (see `cone_at`).
+// Sphere and disk lights give round highlights and penumbras where a
+// rectangular area light gives boxy ones.
+// All kinds fall off with distance as 1 / (constant + linear*d + quadratic*d^2).
+// The default (1, 0, 0) is the book's unattenuated light.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Light {
-    pub kind: u32, // 0 = point, 1 = area
+    pub kind: u32, // 0 = point, 1 = area, 2 = sphere, 3 = disk, 4 = spot
     pub position: Point,
     pub intensity: Color,
     pub corner: Point, // area only
-    pub uvec: Vector,  // area only, per-cell step (full_uvec / usteps)
-    pub vvec: Vector,  // area only, per-cell step (full_vvec / vsteps)
+    pub uvec: Vector,  // area: per-cell step (full_uvec / usteps); disk: unit axis
+    pub vvec: Vector,  // area: per-cell step (full_vvec / vsteps); disk: unit axis
     // These are sample-grid counts. They are `u32`, not `usize`, on purpose: the
     // whole `Light` is uploaded byte-for-byte to the GPU, where rust-gpu lowers
     // `usize` to 32 bits. A `usize` here would make the host struct 12 bytes larger
@@ -25,8 +47,29 @@ pub struct Light {
     pub usteps: u32,
     pub vsteps: u32,
     pub samples: u32,
+    pub constant: Number,
+    pub linear: Number,
+    pub quadratic: Number,
+    pub radius: Number, // sphere and disk only
+    // Spot only: the unit axis of the cone and the cosines of its inner and
+    // outer half-angles (cosines so the per-hit test is a single dot product).
+    pub direction: Vector,
+    pub cos_inner: Number,
+    pub cos_outer: Number,
+    // Spot only: the exponent shaping the penumbra between the two cones. 1.0
+    // is a linear ramp; larger values darken the band and tighten the glow
+    // toward the inner cone.
+    pub falloff: Number,
+    // Point only: the radius of the fake penumbra disk (see `kind`).
+    pub softness: Number,
 }
 
+// Shadow rays per shaded point for a soft point light (`Light::softness`).
+pub const SOFT_SHADOW_SAMPLES: usize = 16;
+// The turn between successive `soft_shadow_point`s: the golden angle, which
+// spreads any number of them evenly over the disk.
+const GOLDEN_ANGLE: Number = 2.399_963;
+
 impl Light {
     pub const fn point_light(position: Point, intensity: Color) -> Light {
         let zero = Vector {
@@ -48,6 +91,15 @@ impl Light {
             usteps: 1,
             vsteps: 1,
             samples: 1,
+            constant: 1.0,
+            linear: 0.0,
+            quadratic: 0.0,
+            radius: 0.0,
+            direction: zero,
+            cos_inner: 1.0,
+            cos_outer: 1.0,
+            falloff: 1.0,
+            softness: 0.0,
         }
     }
     pub fn area_light(
@@ -68,8 +120,141 @@ impl Light {
             usteps: usteps as u32,
             vsteps: vsteps as u32,
             samples: (usteps * vsteps) as u32,
+            constant: 1.0,
+            linear: 0.0,
+            quadratic: 0.0,
+            radius: 0.0,
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            cos_inner: 1.0,
+            cos_outer: 1.0,
+            falloff: 1.0,
+            softness: 0.0,
         }
     }
+    pub fn sphere_light(
+        center: Point,
+        radius: Number,
+        usteps: usize,
+        vsteps: usize,
+        intensity: Color,
+    ) -> Light {
+        let mut light = Self::point_light(center, intensity);
+        light.kind = 2;
+        light.radius = radius;
+        light.usteps = usteps as u32;
+        light.vsteps = vsteps as u32;
+        light.samples = (usteps * vsteps) as u32;
+        light
+    }
+    // A disk light facing along `normal` (either side emits; only the shadow
+    // rays care where the samples are).
+    pub fn disk_light(
+        center: Point,
+        normal: Vector,
+        radius: Number,
+        usteps: usize,
+        vsteps: usize,
+        intensity: Color,
+    ) -> Light {
+        let normal = normal.normalize();
+        // Any vector not parallel to the normal seeds the in-plane axes.
+        let seed = if normal.x.abs() < 0.9 {
+            Vector {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            }
+        } else {
+            Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            }
+        };
+        let mut light = Self::sphere_light(center, radius, usteps, vsteps, intensity);
+        light.kind = 3;
+        light.uvec = normal.cross(seed).normalize();
+        light.vvec = normal.cross(light.uvec);
+        light
+    }
+    // A spot light at `position` aimed along `direction`, lit fully within
+    // `inner` radians of its axis and not at all beyond `outer`, with a linear
+    // penumbra between (see `with_falloff` and `smooth`).
+    pub fn spot_light(
+        position: Point,
+        direction: Vector,
+        inner: Number,
+        outer: Number,
+        intensity: Color,
+    ) -> Light {
+        let mut light = Self::point_light(position, intensity);
+        light.kind = 4;
+        light.direction = direction.normalize();
+        light.cos_inner = inner.cos();
+        light.cos_outer = outer.cos();
+        light
+    }
+    // This point light with shadows softened over a disk of radius
+    // `softness`.
+    pub const fn with_softness(mut self, softness: Number) -> Light {
+        self.softness = softness;
+        self
+    }
+    // Sample `i` of SOFT_SHADOW_SAMPLES on the softness disk, as seen from
+    // `point`: the disk is centered on `position` and faces `point`, and the
+    // samples spiral out from its center at equal-area steps.
+    pub fn soft_shadow_point(&self, i: usize, point: Point) -> Point {
+        let w = (self.position - point).normalize();
+        let helper = if w.x.abs() < 0.9 {
+            Vector {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            }
+        } else {
+            Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            }
+        };
+        let u = w.cross(helper).normalize();
+        let v = w.cross(u);
+        let r = self.softness * ((i as Number + 0.5) / SOFT_SHADOW_SAMPLES as Number).sqrt();
+        let theta = GOLDEN_ANGLE * i as Number;
+        self.position + u * (r * theta.cos()) + v * (r * theta.sin())
+    }
+    // This spot light with its penumbra shaped by `t^falloff`.
+    pub const fn with_falloff(mut self, falloff: Number) -> Light {
+        self.falloff = falloff;
+        self
+    }
+    // A soft-edged preset: a quadratic falloff, so the light fades in gently
+    // from the outer edge instead of ramping up at a constant rate.
+    pub const fn smooth(self) -> Light {
+        self.with_falloff(2.0)
+    }
+    // How much of a spot light's cone reaches `point`: 1.0 inside the inner
+    // cone, 0.0 outside the outer one, `t^falloff` in between. Always 1.0 for
+    // the other kinds.
+    pub fn cone_at(&self, point: Point) -> Number {
+        if self.kind != 4 {
+            return 1.0;
+        }
+        let cos_angle = self.direction.dot((point - self.position).normalize());
+        if cos_angle >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos_angle - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t.powf(self.falloff)
+    }
     // A point light is a 1x1 grid whose only sample is its position; an area
     // light reports its real grid. `lighting` and `intensity_at` iterate these
     // uniformly, so both light kinds flow through the same code.
@@ -88,20 +273,137 @@ impl Light {
     pub fn intensity(&self) -> Color {
         self.intensity
     }
+    // Roughly how far the emitting surface reaches from `position`: a sphere or
+    // disk light's radius, half the longer side of an area light's rectangle,
+    // and 0 for the point-like kinds (point and spot).
+    pub fn extent(&self) -> Number {
+        match self.kind {
+            1 => {
+                let u = self.uvec.magnitude() * self.usteps as Number;
+                let v = self.vvec.magnitude() * self.vsteps as Number;
+                u.max(v) / 2.0
+            }
+            2 | 3 => self.radius,
+            _ => 0.0,
+        }
+    }
+    // This light with its intensity multiplied by `factor`, for tuning
+    // exposure without retyping colors.
+    pub fn scaled(&self, factor: Number) -> Light {
+        Light {
+            intensity: self.intensity * factor,
+            ..*self
+        }
+    }
+    // This light with distance falloff 1 / (constant + linear*d + quadratic*d^2).
+    pub const fn with_attenuation(
+        mut self,
+        constant: Number,
+        linear: Number,
+        quadratic: Number,
+    ) -> Light {
+        self.constant = constant;
+        self.linear = linear;
+        self.quadratic = quadratic;
+        self
+    }
+    // The falloff factor at `point`, measured from the light's `position` (an area
+    // light's center), times a spot light's cone. 1.0 for the default
+    // unattenuated light.
+    pub fn attenuation_at(&self, point: Point) -> Number {
+        let d = (self.position - point).magnitude();
+        self.cone_at(point) / (self.constant + self.linear * d + self.quadratic * d * d)
+    }
+    // The most this light can add to any channel at `point`: its brightest channel
+    // after falloff. `Scene::is_significant` compares this against the scene's
+    // `light_threshold` to skip lights too dim to matter.
+    pub fn contribution_at(&self, point: Point) -> Number {
+        let peak = self.intensity.r.max(self.intensity.g).max(self.intensity.b);
+        peak * self.attenuation_at(point)
+    }
     // The center of cell (u, v). For a point light this is just its position.
     // Sampling cell centers (the +0.5 offset) gives a fixed, deterministic
     // pattern; the book optionally jitters within each cell for smoother
     // penumbras, which is omitted here so renders stay reproducible across the
     // parallel renderer.
+    // Sphere and disk lights map the same cell centers onto their surface:
+    // u picks the angle around, v the height (sphere, uniform in z so every cell
+    // covers equal area) or the ring (disk, uniform in r^2 for the same reason).
     pub fn point_on_light(&self, u: usize, v: usize) -> Point {
-        if self.kind == 0 {
+        let fu = (u as Number + 0.5) / self.usteps as Number;
+        let fv = (v as Number + 0.5) / self.vsteps as Number;
+        if self.kind == 0 || self.kind == 4 {
+            self.position
+        } else if self.kind == 2 {
+            let phi = 2.0 * PI * fu;
+            let z = 1.0 - 2.0 * fv;
+            let ring = (1.0 - z * z).sqrt();
             self.position
+                + Vector {
+                    x: ring * phi.cos(),
+                    y: ring * phi.sin(),
+                    z,
+                } * self.radius
+        } else if self.kind == 3 {
+            let theta = 2.0 * PI * fu;
+            let r = self.radius * fv.sqrt();
+            self.position + self.uvec * (r * theta.cos()) + self.vvec * (r * theta.sin())
         } else {
             self.corner + self.uvec * (u as Number + 0.5) + self.vvec * (v as Number + 0.5)
         }
     }
 }
 
+// What shading needs from any light: where it is and what color it emits.
+// Diffuse and specular are computed as if from a point light there.
+#[cfg(feature = "std")]
+pub trait LightProperties {
+    fn position(&self) -> Point;
+    fn intensity(&self) -> Color;
+}
+
+// A light the renderer knows only through this interface, for kinds the flat
+// `Light` can't express (an IES profile, a projector). `intensity_at` is the
+// fraction of the light reaching `point`, 0.0..=1.0, covering shadowing and
+// any falloff or angular profile; `scene` is there to cast shadow rays with.
+// Host-only: trait objects can't be uploaded, so these live in
+// `World::custom_lights` next to the built-in `lights` and the GPU renders
+// without them.
+#[cfg(feature = "std")]
+pub trait LightSource: LightProperties + core::fmt::Debug + Send + Sync {
+    fn intensity_at(&self, point: Point, scene: &Scene) -> Number;
+}
+
+#[cfg(feature = "std")]
+impl LightProperties for Light {
+    fn position(&self) -> Point {
+        self.position
+    }
+    fn intensity(&self) -> Color {
+        self.intensity
+    }
+}
+
+#[cfg(feature = "std")]
+impl LightSource for Light {
+    fn intensity_at(&self, point: Point, scene: &Scene) -> Number {
+        scene.intensity_at(point, self) * self.attenuation_at(point)
+    }
+}
+
+// A shared handle to a `LightSource`, so a `World` holding them stays `Clone`.
+// Two handles are equal when they point at the same light.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct CustomLight(pub Arc<dyn LightSource>);
+
+#[cfg(feature = "std")]
+impl PartialEq for CustomLight {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -191,4 +493,111 @@ mod tests {
             assert_eq!(light.point_on_light(u, v), expected, "u={u} v={v}");
         }
     }
+
+    #[test]
+    fn a_lights_extent_is_its_emitting_radius() {
+        let origin = Point { x: 0.0, y: 0.0, z: 0.0 };
+        assert_eq!(Light::point_light(origin, white()).extent(), 0.0);
+        assert_eq!(Light::sphere_light(origin, 0.5, 4, 4, white()).extent(), 0.5);
+        let area = Light::area_light(
+            origin,
+            Vector { x: 4.0, y: 0.0, z: 0.0 },
+            4,
+            Vector { x: 0.0, y: 0.0, z: 1.0 },
+            2,
+            white(),
+        );
+        assert_almost_eq!(area.extent(), 2.0);
+    }
+    #[test]
+    fn a_default_light_is_not_attenuated() {
+        let light = Light::point_light(Point { x: 0.0, y: 0.0, z: 0.0 }, white());
+        assert_eq!(light.attenuation_at(Point { x: 100.0, y: 0.0, z: 0.0 }), 1.0);
+    }
+
+    #[test]
+    fn an_attenuated_light_falls_off_with_distance() {
+        let light = Light::point_light(Point { x: 0.0, y: 0.0, z: 0.0 }, white())
+            .with_attenuation(1.0, 0.0, 1.0);
+        assert_eq!(light.attenuation_at(Point { x: 0.0, y: 0.0, z: 0.0 }), 1.0);
+        assert_eq!(light.attenuation_at(Point { x: 0.0, y: 3.0, z: 0.0 }), 0.1);
+        assert_eq!(light.contribution_at(Point { x: 0.0, y: 3.0, z: 0.0 }), 0.1);
+    }
+
+    #[test]
+    fn a_sphere_lights_samples_lie_on_its_surface() {
+        let center = Point {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        let light = Light::sphere_light(center, 0.5, 6, 4, white());
+        assert_eq!(light.samples(), 24);
+        for v in 0..4 {
+            for u in 0..6 {
+                let d = (light.point_on_light(u, v) - center).magnitude();
+                assert!((d - 0.5).abs() < EPSILON, "sample ({u}, {v}) at {d}");
+            }
+        }
+    }
+
+    #[test]
+    fn a_disk_lights_samples_lie_within_its_radius_in_its_plane() {
+        let center = Point {
+            x: 0.0,
+            y: 5.0,
+            z: 0.0,
+        };
+        let normal = Vector {
+            x: 0.0,
+            y: -1.0,
+            z: 0.0,
+        };
+        let light = Light::disk_light(center, normal, 2.0, 8, 3, white());
+        for v in 0..3 {
+            for u in 0..8 {
+                let offset = light.point_on_light(u, v) - center;
+                assert!(offset.magnitude() <= 2.0 + EPSILON);
+                assert!(offset.dot(normal).abs() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn a_spot_lights_falloff_shapes_its_penumbra() {
+        let origin = Point {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let axis = Vector {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        let (inner, outer) = (PI / 18.0, PI / 6.0); // 10 and 30 degrees
+        let linear = Light::spot_light(origin, axis, inner, outer, white());
+        let at = |degrees: Number| {
+            let a = degrees * PI / 180.0;
+            Point {
+                x: a.sin(),
+                y: 0.0,
+                z: a.cos(),
+            }
+        };
+        assert_eq!(linear.cone_at(at(5.0)), 1.0);
+        assert_eq!(linear.cone_at(at(40.0)), 0.0);
+        // falloff = 1.0: linear in the cosine between the two cones.
+        let mid = at(20.0);
+        let t = ((PI / 9.0).cos() - outer.cos()) / (inner.cos() - outer.cos());
+        assert_almost_eq!(linear.cone_at(mid), t);
+        // A steeper exponent darkens the band but leaves the cones themselves.
+        let steep = linear.with_falloff(3.0);
+        assert!(steep.cone_at(mid) < linear.cone_at(mid));
+        assert_almost_eq!(steep.cone_at(mid), t.powf(3.0));
+        assert_eq!(steep.cone_at(at(5.0)), 1.0);
+        assert_eq!(linear.smooth().falloff, 2.0);
+        // The cone scales the light's attenuation, which shading multiplies in.
+        assert_almost_eq!(linear.attenuation_at(mid), t);
+    }
 }
@@ -2,9 +2,15 @@ use crate::{
     lights::*,
     patterns::Pattern,
     shapes::{HasMaterial, Primitive},
+    spectrum::Shade,
+    texture_maps::MAPPING_SPHERICAL,
     tuples::*,
 };
 
+// Absorption per unit distance of a channel `Material::translucent` lacks
+// entirely; short enough that a unit-sized object is strongly tinted.
+const TRANSLUCENT_DENSITY: Number = 2.0;
+
 #[repr(C)]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Material {
@@ -17,9 +23,132 @@ pub struct Material {
     pub reflective: Number,
     pub transparency: Number,
     pub refractive_index: Number,
+    // Beer's law absorption per unit distance, per channel, for light passing
+    // through a transparent object: a refracted ray that travels d inside it
+    // keeps exp(-absorption * d) of each channel. Zero (the default) is clear.
+    pub absorption: Color,
+    // How far a reflection strays from the mirror direction: 0 (the default) is
+    // a sharp mirror; larger values average jittered rays over a wider cone for
+    // a blurrier, brushed-metal look.
+    pub glossiness: Number,
+    // Light the surface gives off by itself, added to its shading whatever
+    // lights the scene has. Black (the default) glows not at all. On its own
+    // this only makes the surface look bright; `World::add_emissive_light`
+    // turns it into light that reaches other surfaces.
+    pub emissive: Color,
+    // Nonzero makes an opaque surface's reflection follow Fresnel: weak head-on,
+    // strong at grazing angles (a wet floor, polished wood), weighted by the
+    // Schlick term for `refractive_index`. A u32 rather than a bool so the
+    // material uploads to the GPU as is. Transparent materials already reflect
+    // by Schlick, so it only changes opaque ones. Zero (the default) is off.
+    pub fresnel_reflective: u32,
+    // Images sampled at each hit in place of a flat value, for materials
+    // painted from texture maps. Each is an id from `World::add_image` plus
+    // one, so 0 (the default) is no map: `albedo_map` stands in for `color`
+    // and `pattern`, `roughness_map` for `glossiness` and `metal_map` for
+    // `reflective`, the last two read as the texel's gray level (the mean of
+    // its channels). All three are laid over the object by the `uv_map` tag
+    // `map_mapping`.
+    pub albedo_map: u32,
+    pub roughness_map: u32,
+    pub metal_map: u32,
+    pub map_mapping: u32,
+}
+
+// Why `Material::validate` rejected a material: the offending field's name and
+// the value it held. `MissingMap` is from `Material::validate_maps`: a map
+// naming an image id the world doesn't have.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaterialError {
+    OutOfRange { field: &'static str, value: Number },
+    MissingMap { field: &'static str, image: u32 },
+}
+
+// Host-only: float formatting does not lower to SPIR-V.
+#[cfg(feature = "std")]
+impl std::fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaterialError::OutOfRange { field, value } => {
+                write!(f, "material {field} out of range: {value}")
+            }
+            MaterialError::MissingMap { field, image } => {
+                write!(f, "material {field} names missing image {image}")
+            }
+        }
+    }
 }
 
 impl Material {
+    // Check every field is in the range the shading math expects:
+    //   color channels, ambient, diffuse, specular  >= 0
+    //   shininess                                   > 0
+    //   reflective, transparency                    0..=1
+    //   refractive_index                            >= 1 (vacuum is 1.0)
+    //   absorption channels                         >= 0
+    //   glossiness                                  >= 0
+    //   emissive channels                           >= 0
+    // Values outside these still render, just subtly wrong (a refractive index
+    // below 1 bends light the wrong way), so this is opt-in: scene builders can
+    // call it (or `World::validate_materials`) to catch typos early. NaN fails
+    // every check.
+    pub fn validate(&self) -> Result<(), MaterialError> {
+        fn check(field: &'static str, value: Number, ok: bool) -> Result<(), MaterialError> {
+            if ok {
+                Ok(())
+            } else {
+                Err(MaterialError::OutOfRange { field, value })
+            }
+        }
+        check("color.r", self.color.r, self.color.r >= 0.0)?;
+        check("color.g", self.color.g, self.color.g >= 0.0)?;
+        check("color.b", self.color.b, self.color.b >= 0.0)?;
+        check("ambient", self.ambient, self.ambient >= 0.0)?;
+        check("diffuse", self.diffuse, self.diffuse >= 0.0)?;
+        check("specular", self.specular, self.specular >= 0.0)?;
+        check("shininess", self.shininess, self.shininess > 0.0)?;
+        check(
+            "reflective",
+            self.reflective,
+            (0.0..=1.0).contains(&self.reflective),
+        )?;
+        check(
+            "transparency",
+            self.transparency,
+            (0.0..=1.0).contains(&self.transparency),
+        )?;
+        check(
+            "refractive_index",
+            self.refractive_index,
+            self.refractive_index >= 1.0,
+        )?;
+        check("absorption.r", self.absorption.r, self.absorption.r >= 0.0)?;
+        check("absorption.g", self.absorption.g, self.absorption.g >= 0.0)?;
+        check("absorption.b", self.absorption.b, self.absorption.b >= 0.0)?;
+        check("glossiness", self.glossiness, self.glossiness >= 0.0)?;
+        check("emissive.r", self.emissive.r, self.emissive.r >= 0.0)?;
+        check("emissive.g", self.emissive.g, self.emissive.g >= 0.0)?;
+        check("emissive.b", self.emissive.b, self.emissive.b >= 0.0)
+    }
+    // Check every image map names one of the `images` images a world holds
+    // (`World::add_image`). A missing one shades black; this catches a
+    // material built before its image was added, or moved to another world.
+    pub fn validate_maps(&self, images: usize) -> Result<(), MaterialError> {
+        let maps = [
+            ("albedo_map", self.albedo_map),
+            ("roughness_map", self.roughness_map),
+            ("metal_map", self.metal_map),
+        ];
+        for (field, map) in maps {
+            if map as usize > images {
+                return Err(MaterialError::MissingMap {
+                    field,
+                    image: map - 1,
+                });
+            }
+        }
+        Ok(())
+    }
     pub const fn new(
         color: Color,
         ambient: Number,
@@ -40,6 +169,35 @@ impl Material {
             reflective,
             transparency,
             refractive_index,
+            absorption: Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            },
+            glossiness: 0.0,
+            emissive: Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            },
+            fresnel_reflective: 0,
+            albedo_map: 0,
+            roughness_map: 0,
+            metal_map: 0,
+            map_mapping: MAPPING_SPHERICAL,
+        }
+    }
+    // The default material with its color, glossiness and reflectivity read
+    // from images added with `World::add_image`: `albedo` everywhere, and
+    // `roughness` and `metal` where given (the flat defaults otherwise). The
+    // maps wrap the object spherically; set `map_mapping` for another shape.
+    pub fn from_maps(albedo: u32, roughness: Option<u32>, metal: Option<u32>) -> Self {
+        let map = |image: Option<u32>| image.map_or(0, |id| id + 1);
+        Self {
+            albedo_map: albedo + 1,
+            roughness_map: map(roughness),
+            metal_map: map(metal),
+            ..Self::default()
         }
     }
     pub const fn default() -> Self {
@@ -73,6 +231,70 @@ impl Material {
             reflective: 0.1,
             transparency: 1.0,
             refractive_index: 1.5,
+            absorption: Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            },
+            glossiness: 0.0,
+            emissive: Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            },
+            fresnel_reflective: 0,
+            albedo_map: 0,
+            roughness_map: 0,
+            metal_map: 0,
+            map_mapping: MAPPING_SPHERICAL,
+        }
+    }
+    // A cheap stand-in for wax, soap or skin: half transparent, mostly
+    // diffuse, with a short Beer's law absorption that soaks up the channels
+    // `color` lacks, so light passing through comes out tinted `color`. This
+    // is an approximation, not subsurface scattering: light only goes
+    // straight through along the refracted ray, it never spreads sideways
+    // under the surface.
+    pub fn translucent(color: Color) -> Self {
+        let soak = |c: Number| (TRANSLUCENT_DENSITY * (1.0 - c)).max(0.0);
+        Self {
+            absorption: Color {
+                r: soak(color.r),
+                g: soak(color.g),
+                b: soak(color.b),
+            },
+            ..Self::new(color, 0.1, 0.9, 0.3, 50.0, 0.0, 0.5, 1.44)
+        }
+    }
+    // Blend toward `other` by `t` (0 = self, 1 = other) for keyframed material
+    // animation. Every scalar and the base color interpolate linearly; a pattern
+    // (or the Fresnel flag, or an image map) can't be blended, so it switches
+    // from self's to other's at t = 0.5.
+    pub fn lerp(&self, other: &Material, t: Number) -> Material {
+        let mix = |a: Number, b: Number| a + (b - a) * t;
+        let maps = if t < 0.5 { self } else { other };
+        Material {
+            color: self.color + (other.color - self.color) * t,
+            ambient: mix(self.ambient, other.ambient),
+            diffuse: mix(self.diffuse, other.diffuse),
+            specular: mix(self.specular, other.specular),
+            shininess: mix(self.shininess, other.shininess),
+            pattern: if t < 0.5 { self.pattern } else { other.pattern },
+            reflective: mix(self.reflective, other.reflective),
+            transparency: mix(self.transparency, other.transparency),
+            refractive_index: mix(self.refractive_index, other.refractive_index),
+            absorption: self.absorption + (other.absorption - self.absorption) * t,
+            glossiness: mix(self.glossiness, other.glossiness),
+            emissive: self.emissive + (other.emissive - self.emissive) * t,
+            fresnel_reflective: if t < 0.5 {
+                self.fresnel_reflective
+            } else {
+                other.fresnel_reflective
+            },
+            albedo_map: maps.albedo_map,
+            roughness_map: maps.roughness_map,
+            metal_map: maps.metal_map,
+            map_mapping: maps.map_mapping,
         }
     }
     pub const fn set_color(&mut self, color: Color) -> () {
@@ -102,14 +324,58 @@ impl Material {
     pub const fn set_refractive_index(&mut self, refractive_index: Number) -> () {
         self.refractive_index = refractive_index
     }
+    pub const fn set_absorption(&mut self, absorption: Color) {
+        self.absorption = absorption
+    }
+    pub const fn set_glossiness(&mut self, glossiness: Number) {
+        self.glossiness = glossiness
+    }
+    pub const fn set_emissive(&mut self, emissive: Color) {
+        self.emissive = emissive
+    }
+    pub const fn set_fresnel_reflective(&mut self, fresnel_reflective: bool) {
+        self.fresnel_reflective = fresnel_reflective as u32
+    }
+}
+
+// The unlit color of `object` at `point`: its pattern sampled there, or the
+// flat material color. Every shading term (ambient, diffuse and, later,
+// emission) starts from this one value, so a hit samples its pattern once no
+// matter how many lights or terms use it.
+pub fn surface_color_at(object: &Primitive, point: Point) -> Color {
+    surface_color_at_filtered(object, point, 0.0)
+}
+
+// `surface_color_at` with the pattern anti-aliased over a pixel footprint of
+// `footprint` world units (`Pattern::pattern_at_shape_filtered`); 0.0 takes a
+// single sample.
+pub fn surface_color_at_filtered(object: &Primitive, point: Point, footprint: Number) -> Color {
+    material_color_at(object, object.material_ref(), point, footprint)
+}
+
+// `surface_color_at_filtered` for one of `object`'s materials, which need not be
+// its main one (a cylinder's cap material, say). The pattern is still placed by
+// `object`'s transform.
+pub fn material_color_at(
+    object: &Primitive,
+    material: &Material,
+    point: Point,
+    footprint: Number,
+) -> Color {
+    if material.pattern.kind != 0 {
+        material.pattern.pattern_at_shape_filtered(object, point, footprint)
+    } else {
+        material.color
+    }
 }
 
 // `intensity` is the fraction of the light visible from `point` (1.0 fully lit,
 // 0.0 fully shadowed, in between for an area light's penumbra), as returned by
 // `World::intensity_at`. Diffuse and specular are summed over every sample point
 // on the light's surface and averaged, so an area light also softens highlights,
-// then scaled by `intensity`. A point light is a single sample, recovering the
-// original Phong result with `intensity` standing in for the old shadow flag.
+// then scaled by `intensity` and the light's distance attenuation. A point light
+// is a single sample, recovering the original Phong result with `intensity`
+// standing in for the old shadow flag.
 pub fn lightning(
     object: &Primitive,
     light: Light,
@@ -118,20 +384,57 @@ pub fn lightning(
     normalv: Vector,
     intensity: Number,
 ) -> Color {
-    let material = object.get_material();
-    let color = if material.pattern.kind != 0 {
-        material.pattern.pattern_at_shape(object, point)
-    } else {
-        material.color
-    };
-    let effective_color = color * light.intensity();
+    lightning_surface(
+        object.material_ref(),
+        surface_color_at(object, point),
+        light,
+        point,
+        eyev,
+        normalv,
+        intensity,
+    )
+}
+
+// `lightning` for a surface color the caller already sampled with
+// `surface_color_at`, so shading one hit under several lights reuses it.
+// Generic over the color representation (see `spectrum::Shade`): pass a
+// `Color` for the usual RGB result or a `Spectrum` to shade per wavelength bin;
+// the light's RGB intensity is converted with `Shade::from_rgb`.
+pub fn lightning_surface<S: Shade>(
+    material: &Material,
+    color: S,
+    light: Light,
+    point: Point,
+    eyev: Vector,
+    normalv: Vector,
+    intensity: Number,
+) -> S {
+    let (ambient, direct) =
+        lightning_parts(material, color, light, point, eyev, normalv, intensity);
+    ambient + direct
+}
+
+// `lightning_surface` split into its ambient term and the direct (diffuse and
+// specular) light, for a caller that scales the ambient on its own, as
+// `World::ambient_light` does.
+pub fn lightning_parts<S: Shade>(
+    material: &Material,
+    color: S,
+    light: Light,
+    point: Point,
+    eyev: Vector,
+    normalv: Vector,
+    intensity: Number,
+) -> (S, S) {
+    let light_intensity = S::from_rgb(light.intensity());
+    let effective_color = color * light_intensity;
     let ambient = effective_color * material.ambient;
 
-    let black = Color {
+    let black = S::from_rgb(Color {
         r: 0.0,
         g: 0.0,
         b: 0.0,
-    };
+    });
     let mut diffuse_sum = black;
     let mut specular_sum = black;
     for v in 0..light.vsteps() {
@@ -144,7 +447,7 @@ pub fn lightning(
                 let reflect_dot_eye = reflectv.dot(eyev);
                 if reflect_dot_eye > 0.0 {
                     let factor = reflect_dot_eye.powf(material.shininess);
-                    specular_sum = specular_sum + light.intensity() * material.specular * factor;
+                    specular_sum = specular_sum + light_intensity * material.specular * factor;
                 }
             }
         }
@@ -152,7 +455,9 @@ pub fn lightning(
     let samples = light.samples() as Number;
     let diffuse = diffuse_sum * (1.0 / samples);
     let specular = specular_sum * (1.0 / samples);
-    ambient + (diffuse + specular) * intensity
+    // Distance falloff dims only the direct terms; ambient stays flat.
+    let direct = (diffuse + specular) * (intensity * light.attenuation_at(point));
+    (ambient, direct)
 }
 #[test]
 fn the_default_meterial() {
@@ -171,6 +476,59 @@ fn the_default_meterial() {
     assert_eq!(m.shininess, 200.0);
 }
 
+#[test]
+fn lerping_between_two_materials() {
+    let matte = Material::default();
+    let mut glossy = Material::glass();
+    glossy.set_color(Color {
+        r: 0.0,
+        g: 0.5,
+        b: 1.0,
+    });
+    glossy.set_pattern(Pattern::test_pattern());
+    assert_eq!(matte.lerp(&glossy, 0.0), matte);
+    assert_eq!(matte.lerp(&glossy, 1.0), glossy);
+    let half = matte.lerp(&glossy, 0.5);
+    assert_eq!(half.ambient, 0.05);
+    assert_eq!(half.diffuse, 0.45);
+    assert_eq!(half.specular, 0.95);
+    assert_eq!(half.shininess, 250.0);
+    assert_eq!(half.transparency, 0.5);
+    assert_eq!(half.refractive_index, 1.25);
+    assert_eq!(
+        half.color,
+        Color {
+            r: 0.5,
+            g: 0.75,
+            b: 1.0
+        }
+    );
+    assert_eq!(half.pattern, glossy.pattern);
+}
+#[test]
+fn the_default_and_glass_materials_validate() {
+    assert_eq!(Material::default().validate(), Ok(()));
+    assert_eq!(Material::glass().validate(), Ok(()));
+}
+#[test]
+fn a_refractive_index_below_one_fails_validation() {
+    let mut m = Material::glass();
+    m.set_refractive_index(0.5);
+    assert_eq!(
+        m.validate(),
+        Err(MaterialError::OutOfRange {
+            field: "refractive_index",
+            value: 0.5
+        })
+    );
+}
+#[test]
+fn reflective_above_one_fails_validation() {
+    let mut m = Material::default();
+    m.set_reflective(1.5);
+    assert!(m.validate().is_err());
+}
+
 #[cfg(test)]
 fn background() -> (Material, Point) {
     let m = Material::default();
@@ -186,12 +544,73 @@ fn background() -> (Material, Point) {
 mod tests {
     use crate::{
         intersections::{Intersection, Intersections},
+        matrices::Matrix,
         rays::Ray,
+        spectrum::Spectrum,
+        transformations::{rotation_x, translation, PI},
         worlds::World,
     };
 
     use super::*;
 
+    #[test]
+    fn a_translucent_sphere_glows_when_lit_from_behind() {
+        let wax = Material::translucent(Color {
+            r: 1.0,
+            g: 0.8,
+            b: 0.5,
+        });
+        assert!(wax.transparency > 0.0);
+        assert_eq!(wax.absorption.r, 0.0);
+        assert!(wax.absorption.g > 0.0 && wax.absorption.b > wax.absorption.g);
+        assert_eq!(wax.validate(), Ok(()));
+
+        // A lit wall behind the sphere, the light between the two: the
+        // sphere's front faces away from the light.
+        let brightness = |material: Material| {
+            let mut w = World::new();
+            w.lights.push(Light::point_light(
+                Point {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 3.0,
+                },
+                Color {
+                    r: 1.0,
+                    g: 1.0,
+                    b: 1.0,
+                },
+            ));
+            w.objects.push(Primitive::with(
+                Primitive::plane,
+                rotation_x(PI / 2.0).then(translation(0.0, 0.0, 5.0)),
+                Material::default(),
+            ));
+            w.objects.push(Primitive::with(
+                Primitive::sphere,
+                Matrix::identity(),
+                material,
+            ));
+            let r = Ray {
+                origin: Point {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -5.0,
+                },
+                direction: Vector {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+            };
+            let c = w.color_at(&r, 5);
+            c.r + c.g + c.b
+        };
+        let mut opaque = wax.clone();
+        opaque.set_transparency(0.0);
+        assert!(brightness(wax) > brightness(opaque));
+    }
+
     #[test]
     fn lightning_with_the_eye_between_the_light_and_the_surface() {
         let (m, position) = background();
@@ -401,6 +820,111 @@ mod tests {
         );
     }
     #[test]
+    fn spectral_lighting_of_a_gray_surface_matches_rgb() {
+        let (m, position) = background();
+        let eyev = Vector {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        };
+        let normalv = eyev;
+        let light = Light::point_light(
+            Point {
+                x: 0.0,
+                y: 10.0,
+                z: -10.0,
+            },
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        );
+        let rgb = lightning_surface(&m, m.color, light, position, eyev, normalv, 1.0);
+        let spectral = lightning_surface(
+            &m,
+            Spectrum::<16>::from_rgb(m.color),
+            light,
+            position,
+            eyev,
+            normalv,
+            1.0,
+        )
+        .to_rgb();
+        assert!((rgb.r - spectral.r).abs() < 0.01);
+        assert!((rgb.g - spectral.g).abs() < 0.01);
+        assert!((rgb.b - spectral.b).abs() < 0.01);
+    }
+    #[test]
+    fn a_shadowed_striped_surface_keeps_the_pattern_in_its_ambient_term() {
+        let (m, _) = background();
+        let mut material = m.clone();
+        material.set_pattern(Pattern::stripe_pattern(
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+            Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            },
+        ));
+        let mut object = Primitive::sphere();
+        object.set_material(material);
+        let eyev = Vector {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        };
+        let normalv = eyev;
+        let light = Light::point_light(
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: -10.0,
+            },
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        );
+        let white_stripe = Point {
+            x: 0.5,
+            y: 0.0,
+            z: 0.0,
+        };
+        let black_stripe = Point {
+            x: 1.5,
+            y: 0.0,
+            z: 0.0,
+        };
+        let lit = lightning(&object, light, white_stripe, eyev, normalv, 0.0);
+        let dark = lightning(&object, light, black_stripe, eyev, normalv, 0.0);
+        assert_eq!(
+            lit,
+            surface_color_at(&object, white_stripe) * object.material_ref().ambient
+        );
+        assert_eq!(
+            lit,
+            Color {
+                r: 0.1,
+                g: 0.1,
+                b: 0.1
+            }
+        );
+        assert_eq!(
+            dark,
+            Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0
+            }
+        );
+    }
+    #[test]
     fn lighting_with_a_pattern_applied() {
         let (m, _) = background();
         let mut material = m.clone();
@@ -508,7 +1032,7 @@ mod tests {
             },
         };
         let i = Intersection::new(sqrt(2.0), 0);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         assert_eq!(
             comps.reflectv,
             Vector {
@@ -60,6 +60,27 @@ pub const fn mul<const ROWS: usize, const COLS: usize>(
     m
 }
 
+impl Matrix<4, 4> {
+    // The linear part (rotation, scale, shear) of an affine transform, without
+    // its translation column or bottom row.
+    pub const fn upper_left_3x3(&self) -> Matrix<3, 3> {
+        let d = &self.data;
+        Matrix::new([
+            [d[0][0], d[0][1], d[0][2]],
+            [d[1][0], d[1][1], d[1][2]],
+            [d[2][0], d[2][1], d[2][2]],
+        ])
+    }
+}
+
+// What carries an object-space normal out through a transform, given that
+// transform's inverse: the transpose of the inverse's upper-left 3x3. A normal
+// is a direction, so translation never reaches it; the 3x3 skips that row and
+// column (9 multiplies a normal instead of 16).
+pub fn normal_matrix(inverse: &Matrix<4, 4>) -> Matrix<3, 3> {
+    transpose(&inverse.upper_left_3x3())
+}
+
 pub fn transpose<const ROWS: usize, const COLS: usize>(
     a: &Matrix<ROWS, COLS>,
 ) -> Matrix<COLS, ROWS> {
@@ -233,6 +254,46 @@ where
     }
 }
 
+#[test]
+fn a_projective_matrix_sums_every_row_including_w() {
+    // A point has no room for w, so carry (x, y, z, w) as a matrix's first
+    // column: each row of the product is the same sum `Matrix * Point` makes.
+    let column = |x: Number, y: Number, z: Number| {
+        Matrix::new([
+            [x, 0.0, 0.0, 0.0],
+            [y, 0.0, 0.0, 0.0],
+            [z, 0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+        ])
+    };
+    let general: Matrix<4, 4> = Matrix::new([
+        [1.0, 2.0, 3.0, 4.0],
+        [2.0, 4.0, 4.0, 2.0],
+        [8.0, 6.0, 4.0, 1.0],
+        [1.0, 1.0, 2.0, 3.0],
+    ]);
+    let h = general * column(1.0, 2.0, 3.0);
+    assert_eq!(
+        [h.get(0, 0), h.get(1, 0), h.get(2, 0), h.get(3, 0)],
+        [18.0, 24.0, 33.0, 12.0]
+    );
+    assert_eq!(
+        general * Point::from([1.0, 2.0, 3.0]),
+        Point::from([18.0, 24.0, 33.0])
+    );
+    // Perspective onto the plane z = 2: w picks up z / 2.
+    let m: Matrix<4, 4> = Matrix::new([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.5, 0.0],
+    ]);
+    let h = m * column(2.0, 4.0, 8.0);
+    let w = h.get(3, 0);
+    assert_eq!(w, 4.0);
+    let projected = Point::from([h.get(0, 0) / w, h.get(1, 0) / w, h.get(2, 0) / w]);
+    assert_eq!(projected, Point::from([0.5, 1.0, 2.0]));
+}
 #[test]
 fn constructing_and_inspecting_a_4x4_matrix() {
     let m: Matrix<4, 4> = Matrix::new([
@@ -538,3 +599,25 @@ fn multiplying_a_product_by_its_inverse() {
     assert_ne!(inverse(&b), None);
     assert_eq!(c * inverse(&b).unwrap(), a);
 }
+#[test]
+fn the_3x3_normal_matrix_matches_the_full_inverse_transpose() {
+    use crate::transformations::{rotation_x, rotation_z, scaling, shearing, translation};
+    use crate::tuples::Vector;
+    let transforms = [
+        translation(5.0, -3.0, 2.0),
+        scaling(2.0, 0.5, -1.0).then(translation(1.0, 2.0, 3.0)),
+        rotation_z(0.7)
+            .then(rotation_x(-1.2))
+            .then(translation(0.0, 4.0, 0.0)),
+        shearing(1.0, 0.0, 0.5, 0.0, 0.0, 2.0).then(scaling(1.0, 3.0, 1.0)),
+    ];
+    let normal = Vector::from([0.3, -0.8, 0.52]);
+    for m in transforms {
+        let inv = inverse(&m).unwrap();
+        assert_eq!(normal_matrix(&inv) * normal, transpose(&inv) * normal);
+    }
+    assert_eq!(
+        translation(5.0, -3.0, 2.0).upper_left_3x3(),
+        Matrix::<3, 3>::identity()
+    );
+}
@@ -34,6 +34,20 @@ pub struct Pattern {
     pub uv: UvFace,
     pub mapping: u32,
     pub faces: [UvFace; 6],
+    // The `GradientSpace` a gradient blends in, as a u32 tag for the GPU.
+    pub gradient_space: u32,
+}
+
+// The color space a gradient pattern blends its endpoints in. Rgb (the
+// default) is a straight linear blend, which can sag to a muddy midtone
+// between saturated colors; Hsl keeps the saturation and walks the hue the
+// short way round; Oklab blends perceptually evenly.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GradientSpace {
+    #[default]
+    Rgb = 0,
+    Hsl = 1,
+    Oklab = 2,
 }
 
 impl HasTransform for Pattern {
@@ -61,6 +75,7 @@ impl Pattern {
             uv: face,
             mapping: MAPPING_SPHERICAL,
             faces: [face; 6],
+            gradient_space: GradientSpace::Rgb as u32,
         }
     }
     fn base() -> Self {
@@ -88,6 +103,10 @@ impl Pattern {
             ..Pattern::base()
         }
     }
+    // Blend this gradient's endpoints in `space` instead of plain RGB.
+    pub fn set_gradient_space(&mut self, space: GradientSpace) {
+        self.gradient_space = space as u32;
+    }
     pub fn ring_pattern(a: Color, b: Color) -> Self {
         Pattern {
             kind: 3,
@@ -121,11 +140,99 @@ impl Pattern {
             ..Pattern::base()
         }
     }
+    // A copy of this pattern whose colors are passed through `f` (to invert or
+    // tint it, say), with the same kind and transform. The flat struct has no
+    // room for a closure, so `f` is applied up front to every color stored in
+    // it: exact for the banded kinds and UV faces, and for a gradient only
+    // when `f` is linear, since the gradient's endpoints are mapped rather than
+    // each blend. The test pattern stores no colors and comes back unchanged.
+    pub fn map_colors(&self, f: impl Fn(Color) -> Color) -> Self {
+        let mut faces = self.faces;
+        let mut fi = 0;
+        while fi < faces.len() {
+            faces[fi] = faces[fi].map_colors(&f);
+            fi += 1;
+        }
+        Pattern {
+            a: f(self.a),
+            b: f(self.b),
+            uv: self.uv.map_colors(&f),
+            faces,
+            ..*self
+        }
+    }
     pub fn pattern_at_shape(&self, object: &Primitive, world_point: Point) -> Color {
         let object_point = object.get_inverse_transform() * world_point;
         let pattern_point = self.inverse * object_point;
         self.pattern_at(pattern_point)
     }
+    // `pattern_at_shape` for a hit whose pixel covers about `footprint` world
+    // units around `world_point` (see `pattern_at_filtered`). The footprint is
+    // carried into pattern space by the largest stretch of the object and
+    // pattern transforms, so it errs toward blurring over aliasing.
+    pub fn pattern_at_shape_filtered(
+        &self,
+        object: &Primitive,
+        world_point: Point,
+        footprint: Number,
+    ) -> Color {
+        let to_pattern = self.inverse * object.get_inverse_transform();
+        let pattern_point = to_pattern * world_point;
+        let mut stretch: Number = 0.0;
+        let mut axis = 0;
+        while axis < 3 {
+            let mut v = Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            };
+            v.set(axis, 1.0);
+            stretch = stretch.max((to_pattern * v).magnitude());
+            axis += 1;
+        }
+        self.pattern_at_filtered(pattern_point, footprint * stretch)
+    }
+    // `pattern_at`, anti-aliased over a cube of side `footprint` (pattern
+    // space) centered on `point`. Where the pattern is the same at the center
+    // and all eight corners there is no edge to smooth and the center sample is
+    // returned as is; otherwise the result is the mean of a 3x3x3 grid, so a
+    // stripe or checker boundary blends instead of stair-stepping. A footprint
+    // of 0.0 (no estimate available) is a single sample.
+    pub fn pattern_at_filtered(&self, point: Point, footprint: Number) -> Color {
+        let center = self.pattern_at(point);
+        if footprint <= 0.0 {
+            return center;
+        }
+        let h = footprint / 2.0;
+        let mut straddles = false;
+        let mut corner = 0;
+        while corner < 8 && !straddles {
+            let offset = Vector {
+                x: if corner & 1 == 0 { -h } else { h },
+                y: if corner & 2 == 0 { -h } else { h },
+                z: if corner & 4 == 0 { -h } else { h },
+            };
+            straddles = self.pattern_at(point + offset) != center;
+            corner += 1;
+        }
+        if !straddles {
+            return center;
+        }
+        // The centers of a 3x3x3 split of the cube.
+        let step = footprint / 3.0;
+        let mut sum = black();
+        let mut i = 0;
+        while i < 27 {
+            let offset = Vector {
+                x: (i % 3) as Number - 1.0,
+                y: (i / 3 % 3) as Number - 1.0,
+                z: (i / 9) as Number - 1.0,
+            };
+            sum = sum + self.pattern_at(point + offset * step);
+            i += 1;
+        }
+        sum * (1.0 / 27.0)
+    }
     pub fn pattern_at(&self, point: Point) -> Color {
         match self.kind {
             7 => Color {
@@ -143,9 +250,8 @@ impl Pattern {
             }
             2 => {
                 // gradient
-                let distance = self.b - self.a;
                 let fraction = point.x - point.x.floor();
-                self.a + distance * fraction
+                gradient_mix(self.a, self.b, fraction, self.gradient_space)
             }
             3 => {
                 // ring
@@ -191,6 +297,94 @@ impl Pattern {
         }
     }
 }
+// Blend `a` toward `b` by `t` in the `GradientSpace` tagged `space`.
+fn gradient_mix(a: Color, b: Color, t: Number, space: u32) -> Color {
+    if space == GradientSpace::Hsl as u32 {
+        let (ha, sa, la) = rgb_to_hsl(a);
+        let (hb, sb, lb) = rgb_to_hsl(b);
+        // The shorter way round the hue circle.
+        let mut dh = hb - ha;
+        if dh > 0.5 {
+            dh -= 1.0;
+        } else if dh < -0.5 {
+            dh += 1.0;
+        }
+        let h = (ha + dh * t).rem_euclid(1.0);
+        hsl_to_rgb(h, sa + (sb - sa) * t, la + (lb - la) * t)
+    } else if space == GradientSpace::Oklab as u32 {
+        let la = linear_to_oklab(a);
+        let lb = linear_to_oklab(b);
+        oklab_to_linear(la + (lb - la) * t)
+    } else {
+        a + (b - a) * t
+    }
+}
+
+// Hue (as a fraction of a turn), saturation and lightness of `c`.
+fn rgb_to_hsl(c: Color) -> (Number, Number, Number) {
+    let max = c.r.max(c.g).max(c.b);
+    let min = c.r.min(c.g).min(c.b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d <= 0.0 {
+        return (0.0, 0.0, l);
+    }
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == c.r {
+        ((c.g - c.b) / d).rem_euclid(6.0)
+    } else if max == c.g {
+        (c.b - c.r) / d + 2.0
+    } else {
+        (c.r - c.g) / d + 4.0
+    };
+    (h / 6.0, s, l)
+}
+
+fn hsl_to_rgb(h: Number, s: Number, l: Number) -> Color {
+    let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h6 = h * 6.0;
+    let x = chroma * (1.0 - (h6.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match h6 as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = l - chroma / 2.0;
+    Color {
+        r: r + m,
+        g: g + m,
+        b: b + m,
+    }
+}
+
+// Oklab (L, a, b) packed into a Color's (r, g, b), from linear sRGB. See
+// https://bottosson.github.io/posts/oklab/ for the matrices.
+fn linear_to_oklab(c: Color) -> Color {
+    let l = (0.41222146 * c.r + 0.53633255 * c.g + 0.051445995 * c.b).cbrt();
+    let m = (0.2119035 * c.r + 0.6806995 * c.g + 0.10739696 * c.b).cbrt();
+    let s = (0.08830246 * c.r + 0.28171885 * c.g + 0.6299787 * c.b).cbrt();
+    Color {
+        r: 0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+        g: 1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        b: 0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+    }
+}
+
+fn oklab_to_linear(lab: Color) -> Color {
+    let l = lab.r + 0.39633778 * lab.g + 0.21580376 * lab.b;
+    let m = lab.r - 0.105561346 * lab.g - 0.06385417 * lab.b;
+    let s = lab.r - 0.08948418 * lab.g - 1.2914855 * lab.b;
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+    Color {
+        r: 4.0767417 * l - 3.3077116 * m + 0.23096994 * s,
+        g: -1.268438 * l + 2.6097574 * m - 0.34131938 * s,
+        b: -0.0041960864 * l - 0.7034186 * m + 1.7076147 * s,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::transformations::{scaling, translation};
@@ -217,6 +411,40 @@ mod tests {
         assert_eq!(pattern.b, black);
     }
     #[test]
+    fn mapping_colors_swaps_a_stripes_bands() {
+        let (black, white) = background();
+        let swap = |c: Color| if c == white { black } else { white };
+        let pattern = Pattern::stripe_pattern(white, black).map_colors(swap);
+        let at = |x| Point { x, y: 0.0, z: 0.0 };
+        assert_eq!(pattern.pattern_at(at(0.0)), black);
+        assert_eq!(pattern.pattern_at(at(1.0)), white);
+        let faces = [UvFace::checkers(2.0, 2.0, white, black); 6];
+        let cube = Pattern::cube_map(faces).map_colors(swap);
+        assert_eq!(cube.faces[3].a, black);
+        assert_eq!(cube.faces[3].b, white);
+    }
+    #[test]
+    fn a_footprint_across_a_stripe_edge_blends_the_stripes() {
+        let (black, white) = background();
+        let pattern = Pattern::stripe_pattern(white, black);
+        let edge = Point {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        assert_eq!(pattern.pattern_at_filtered(edge, 0.0), white);
+        // Samples at x = -1/6, 0, 1/6: one third fall in the black stripe.
+        let blended = pattern.pattern_at_filtered(edge, 0.5);
+        assert_almost_eq!(blended.r, 2.0 / 3.0);
+        assert!(blended.r > 0.0 && blended.r < 1.0);
+        let inside = Point {
+            x: 0.5,
+            y: 0.0,
+            z: 0.0,
+        };
+        assert_eq!(pattern.pattern_at_filtered(inside, 0.5), white);
+    }
+    #[test]
     fn a_stripe_pattern_is_constant_in_y() {
         let (black, white) = background();
         let pattern = Pattern::stripe_pattern(white, black);
@@ -504,6 +732,51 @@ mod tests {
         );
     }
     #[test]
+    fn a_gradient_can_blend_in_hsl_or_oklab() {
+        let red = Color {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        let green = Color {
+            r: 0.0,
+            g: 1.0,
+            b: 0.0,
+        };
+        let mid = Point {
+            x: 0.5,
+            y: 0.0,
+            z: 0.0,
+        };
+        let rgb = Pattern::gradient_pattern(red, green);
+        let mut hsl = rgb;
+        hsl.set_gradient_space(GradientSpace::Hsl);
+        let mut oklab = rgb;
+        oklab.set_gradient_space(GradientSpace::Oklab);
+        assert_eq!(
+            rgb.pattern_at(mid),
+            Color {
+                r: 0.5,
+                g: 0.5,
+                b: 0.0
+            }
+        );
+        // Halfway round the hue circle from red to green is full yellow.
+        assert_eq!(
+            hsl.pattern_at(mid),
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 0.0
+            }
+        );
+        assert_ne!(oklab.pattern_at(mid), rgb.pattern_at(mid));
+        // Every space starts on the first color.
+        for pattern in [rgb, hsl, oklab] {
+            assert_eq!(pattern.pattern_at(Point::default()), red);
+        }
+    }
+    #[test]
     fn a_ring_should_extend_in_both_x_and_y() {
         let (black, white) = background();
         let pattern = Pattern::ring_pattern(white, black);
@@ -653,6 +926,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn checkers_applied_through_a_cylindrical_texture_map() {
+        // A label wrapped around a can: 4 cells around, 2 per unit of height.
+        let (black, white) = background();
+        let pattern = Pattern::texture_map(
+            UvFace::checkers(4.0, 2.0, black, white),
+            MAPPING_CYLINDRICAL,
+        );
+        let cases = [
+            (Point { x: 0.0, y: 0.25, z: -1.0 }, black),
+            (Point { x: 1.0, y: 0.25, z: 0.0 }, white),
+            (Point { x: 0.0, y: 0.25, z: 1.0 }, black),
+            (Point { x: 0.0, y: 0.75, z: -1.0 }, white),
+            (Point { x: 0.0, y: 1.25, z: -1.0 }, black),
+        ];
+        for (p, expected) in cases {
+            assert_eq!(pattern.pattern_at(p), expected, "p={p:?}");
+        }
+    }
+
+    #[test]
+    fn checkers_applied_through_a_conical_texture_map() {
+        let (black, white) = background();
+        let pattern =
+            Pattern::texture_map(UvFace::checkers(4.0, 1.0, black, white), MAPPING_CONICAL);
+        // Same slant distance, a quarter turn apart: alternate cells.
+        let a = pattern.pattern_at(Point { x: 0.0, y: 0.5, z: -0.5 });
+        let b = pattern.pattern_at(Point { x: 0.5, y: 0.5, z: 0.0 });
+        assert_ne!(a, b);
+        // Half a turn around is two cells on, so the color comes back.
+        let c = pattern.pattern_at(Point { x: 0.0, y: 0.5, z: 0.5 });
+        assert_eq!(a, c);
+    }
+
     #[test]
     fn finding_the_colors_on_a_mapped_cube() {
         let red = Color { r: 1.0, g: 0.0, b: 0.0 };
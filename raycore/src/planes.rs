@@ -4,13 +4,22 @@ use crate::tuples::*;
 #[cfg(test)]
 use crate::shapes::*;
 
+// The t at which `ray` crosses the horizontal plane y = `plane_y`, behind the
+// origin as well as ahead of it; None if the ray runs parallel to the plane
+// (its y-direction is ~0).
+pub fn ray_plane_intersection(ray: &Ray, plane_y: Number) -> Option<Number> {
+    if ray.direction.y().abs() < EPSILON {
+        return None;
+    }
+    Some((plane_y - ray.origin.y) / ray.direction.y)
+}
+
 // The plane lies in the xz axis (y = 0). A ray hits it once, unless it runs
-// parallel (its y-direction is ~0).
+// parallel.
 pub fn plane_intersect(ray: &Ray, object_id: usize, xs: &mut Intersections) {
-    if ray.direction.y().abs() < EPSILON {
-        return;
+    if let Some(t) = ray_plane_intersection(ray, 0.0) {
+        xs.push(Intersection::new(t, object_id));
     }
-    xs.push(Intersection::new(-ray.origin.y / ray.direction.y, object_id));
 }
 
 // A plane's normal points straight up everywhere; the point is irrelevant.
@@ -25,6 +34,32 @@ pub fn plane_normal_at(_: &Point) -> Vector {
 mod tests {
     use super::*;
     #[test]
+    fn a_ray_crosses_a_horizontal_plane_unless_it_runs_parallel() {
+        let down = Ray {
+            origin: Point {
+                x: 1.0,
+                y: 4.0,
+                z: -2.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: -2.0,
+                z: 0.0,
+            },
+        };
+        assert_eq!(ray_plane_intersection(&down, 0.0), Some(2.0));
+        assert_eq!(ray_plane_intersection(&down, 6.0), Some(-1.0));
+        let level = Ray {
+            origin: down.origin,
+            direction: Vector {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        };
+        assert_eq!(ray_plane_intersection(&level, 0.0), None);
+    }
+    #[test]
     fn the_normal_of_a_plane_is_constant_everywhere() {
         let n1 = plane_normal_at(&Point {
             x: 0.0,
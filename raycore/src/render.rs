@@ -31,6 +31,9 @@ pub struct Cam {
     // (and dispatching only the tile's size). Both 0 for a full-frame render / CPU.
     pub row_offset: u32,
     pub col_offset: u32,
+    // 1 to encode the output with the sRGB curve (`linear_to_srgb`) when the
+    // shader packs it to 8 bits, 0 to write the linear values as they are.
+    pub srgb: u32,
 }
 
 impl Cam {
@@ -99,7 +102,9 @@ pub struct WfNode {
     pub object_id: u32,
     pub remaining: u32,
     pub active: u32, // 1 if this pixel has a hit to shade this round
-    pub _pad: u32,
+    // `Computations::on_cap` as a u32, so the shade kernel picks the cap
+    // material (`Primitive::material_for`) the way the CPU does.
+    pub on_cap: u32,
 }
 
 impl WfNode {
@@ -119,7 +124,7 @@ impl WfNode {
             object_id: c.object_id as u32,
             remaining,
             active: 1,
-            _pad: 0,
+            on_cap: c.on_cap as u32,
         }
     }
 
@@ -157,6 +162,11 @@ impl WfNode {
     // the refraction branch of the book's `color_at`; kept in raycore so the shade
     // kernel needs no float intrinsics of its own.
     pub fn refract_dir(&self) -> Vector {
+        // As in `Computations::refracted_direction`: equal indices aren't an
+        // interface, so the ray goes straight on.
+        if (self.n1 - self.n2).abs() < EPSILON {
+            return -self.eyev;
+        }
         let cos_i = self.cos_i();
         let n_ratio = self.n1 / self.n2;
         let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
@@ -200,6 +210,7 @@ mod tests {
             max_depth: 5,
             row_offset: 0,
             col_offset: 0,
+            srgb: 0,
         };
         let scene = w.scene();
         for &(x, y) in &[(5u32, 5u32), (0, 0), (10, 10), (3, 7)] {
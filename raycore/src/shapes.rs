@@ -1,10 +1,12 @@
 use crate::{
-    bounds::BoundingBox,
+    bounds::{BoundingBox, BoundingSphere},
     cones::cone_normal_at,
     cones::cone_intersect,
     csg::CsgOperation,
     cubes::{cube_intersect, cube_normal_at},
     cylinders::{cylinder_intersect, cylinder_normal_at},
+    disks::{disk_intersect, disk_normal_at},
+    slabs::{slab_intersect, slab_normal_at},
     intersections::*,
     materials::Material,
     matrices::*,
@@ -14,6 +16,7 @@ use crate::{
     triangles::{
         smooth_triangle_local_normal_at_uv, triangle_intersect, triangle_normal_at,
     },
+    transformations::PI,
     tuples::*,
 };
 
@@ -29,6 +32,8 @@ pub enum ShapeKind {
     Cube,
     Cylinder,
     Cone,
+    Disk,
+    Slab,
     Triangle,
     SmoothTriangle,
     Group,
@@ -43,7 +48,9 @@ pub struct Primitive {
     pub kind: ShapeKind,
     pub transform: TransformData,
     pub material: Material,
-    // cylinder / cone
+    // cylinder / cone: the y-range; disk: the inner and outer radius; slab: the
+    // heights of its two faces; plane: the horizon fade distances (see
+    // `set_horizon_fade`)
     pub minimum: Number,
     pub maximum: Number,
     pub closed: u32,
@@ -74,6 +81,43 @@ pub struct Primitive {
     // `set_bounds()` instead of touching the fields directly.
     pub bounds: BoundingBox,
     pub has_bounds: u32,
+    // group only: when `cull_with_sphere` is set, `World::compute_bounds` also
+    // caches `bounding_sphere` and the traversal culls against it instead of
+    // `bounds`. See `World::use_bounding_sphere`.
+    pub bounding_sphere: BoundingSphere,
+    pub cull_with_sphere: u32,
+    // closed cylinder / cone: the material the end caps are shaded with instead
+    // of `material`, when `has_cap_material` is set (the flag stands in for an
+    // Option, as `has_bounds` does). Use `cap_material()`/`set_cap_material()`.
+    pub cap_material: Material,
+    pub has_cap_material: u32,
+    // 1 to keep this object out of shadow rays, so it shows up but casts no
+    // shadow; 0 (the default) casts as usual. Only a top-level object's flag
+    // is read, and it covers everything under it. Use `casts_shadow()` /
+    // `set_casts_shadow()`.
+    pub shadowless: u32,
+}
+
+// Whether edges `e1` and `e2` (from a shared corner) are too close to parallel,
+// or too short, to span a plane: the sine of the angle between them is under
+// EPSILON, which holds at any scale.
+fn is_degenerate_face(e1: Vector, e2: Vector) -> bool {
+    e2.cross(e1).magnitude() <= EPSILON * e1.magnitude() * e2.magnitude()
+}
+
+// The unit normal of a triangle with edges `e1` and `e2`, or +y for a
+// degenerate one, whose cross product would normalize to NaN and poison every
+// shade it touched. Such a triangle is edge-on to every ray, so it is never hit
+// and any unit normal is safe.
+fn face_normal(e1: Vector, e2: Vector) -> Vector {
+    if is_degenerate_face(e1, e2) {
+        return Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+    }
+    e2.cross(e1).normalize()
 }
 
 // Sentinel for `left`/`right`: no child attached. (CSG nodes set both; every
@@ -81,11 +125,9 @@ pub struct Primitive {
 const NO_CHILD: u32 = u32::MAX;
 
 // Hand-written to mirror the old `Option<BoundingBox>` field: the cached bounds
-// only participate in equality when both sides actually have them. An unset box
-// is `BoundingBox::empty()` (min +inf / max -inf), and the tolerant `Point`
-// equality computes `inf - inf = NaN`, which compares unequal, so deriving
-// `PartialEq` would make two equal "no-bounds" primitives compare unequal. The
-// `bounds()` accessor returns `None` in that case, matching the old behavior.
+// only participate in equality when both sides actually have them, since an
+// unset box holds nothing meaningful. The `bounds()` accessor returns `None` in
+// that case, matching the old behavior.
 impl PartialEq for Primitive {
     fn eq(&self, other: &Self) -> bool {
         self.kind == other.kind
@@ -109,6 +151,9 @@ impl PartialEq for Primitive {
             && self.left == other.left
             && self.right == other.right
             && self.bounds() == other.bounds()
+            && self.bounding_sphere() == other.bounding_sphere()
+            && self.cap_material() == other.cap_material()
+            && self.shadowless == other.shadowless
     }
 }
 
@@ -149,6 +194,11 @@ impl Primitive {
             right: NO_CHILD,
             bounds: BoundingBox::empty(),
             has_bounds: 0,
+            bounding_sphere: BoundingSphere::new(origin, 0.0),
+            cull_with_sphere: 0,
+            cap_material: Material::default(),
+            has_cap_material: 0,
+            shadowless: 0,
         }
     }
     pub fn sphere() -> Primitive {
@@ -171,6 +221,27 @@ impl Primitive {
         p.closed = 0;
         p
     }
+    // A flat disk in the xz plane; `inner_radius > 0` makes an annulus.
+    pub fn disk(inner_radius: Number, outer_radius: Number) -> Primitive {
+        let mut p = Self::base(ShapeKind::Disk);
+        p.minimum = inner_radius;
+        p.maximum = outer_radius;
+        p
+    }
+    // A slab `thickness` thick, centered on the xz plane.
+    pub fn slab(thickness: Number) -> Primitive {
+        let mut p = Self::base(ShapeKind::Slab);
+        p.minimum = -thickness / 2.0;
+        p.maximum = thickness / 2.0;
+        p
+    }
+    // Everything below the xz plane: a slab with no bottom face.
+    pub fn half_space() -> Primitive {
+        let mut p = Self::base(ShapeKind::Slab);
+        p.minimum = Number::NEG_INFINITY;
+        p.maximum = 0.0;
+        p
+    }
     pub fn glass_sphere() -> Primitive {
         let mut sphere = Self::sphere();
         let mut glass = Material::default();
@@ -195,7 +266,7 @@ impl Primitive {
         let e1 = p2 - p1;
         let e2 = p3 - p1;
         // The triangle is flat, so a single normal serves every point on it.
-        let normal = e2.cross(e1).normalize();
+        let normal = face_normal(e1, e2);
         let mut p = Self::base(ShapeKind::Triangle);
         p.p1 = p1;
         p.p2 = p2;
@@ -219,11 +290,20 @@ impl Primitive {
         p.p3 = p3;
         p.e1 = p2 - p1;
         p.e2 = p3 - p1;
+        // The flat normal, for where the vertex normals blend to nothing.
+        p.normal = face_normal(p.e1, p.e2);
         p.n1 = n1;
         p.n2 = n2;
         p.n3 = n3;
         p
     }
+    // Whether this (flat or smooth) triangle has no area to speak of: its
+    // corners coincide or lie on one line, so it has no plane of its own and
+    // its normal is only `face_normal`'s stand-in. False for other kinds.
+    pub fn is_degenerate(&self) -> bool {
+        matches!(self.kind, ShapeKind::Triangle | ShapeKind::SmoothTriangle)
+            && is_degenerate_face(self.e1, self.e2)
+    }
     // The group this shape belongs to (an index into `World::objects`), or
     // `None` if it is a root. Mirrors the book's `parent` attribute.
     pub fn parent(&self) -> Option<usize> {
@@ -267,6 +347,131 @@ impl Primitive {
         self.bounds = bounds;
         self.has_bounds = 1;
     }
+    // The separate end-cap material of a closed cylinder or cone, if it has one.
+    pub fn cap_material(&self) -> Option<&Material> {
+        if self.has_cap_material != 0 {
+            Some(&self.cap_material)
+        } else {
+            None
+        }
+    }
+    pub fn set_cap_material(&mut self, material: Material) {
+        self.cap_material = material;
+        self.has_cap_material = 1;
+    }
+    pub fn casts_shadow(&self) -> bool {
+        self.shadowless == 0
+    }
+    // Cut a cylinder or cone off below `minimum` and above `maximum` (in
+    // object space, both exclusive). Both default to unbounded.
+    pub const fn set_minimum(&mut self, minimum: Number) {
+        self.minimum = minimum;
+    }
+    pub const fn set_maximum(&mut self, maximum: Number) {
+        self.maximum = maximum;
+    }
+    // Cap the cut ends of a cylinder or cone, making it a solid; open (the
+    // default) leaves a hollow tube. Only matters once it is cut.
+    pub const fn set_closed(&mut self, closed: bool) {
+        self.closed = closed as u32;
+    }
+    // Once the object is in a `World`, go through `World::set_casts_shadow`,
+    // which also refreshes the world's list of shadow casters.
+    pub fn set_casts_shadow(&mut self, casts: bool) {
+        self.shadowless = (!casts) as u32;
+    }
+    // Fade a plane into the background with distance from the eye: hits up to
+    // `start` along the ray are shaded as usual, past `end` they show only the
+    // background, with a smooth blend between, for a soft horizon in place of
+    // the hard line an infinite floor draws. An `end` of 0 (the default)
+    // turns it off; other kinds ignore it.
+    pub fn set_horizon_fade(&mut self, start: Number, end: Number) {
+        self.minimum = start;
+        self.maximum = end;
+    }
+    // How much of the surface is left at a hit `distance` along the ray: 1.0
+    // within the plane's horizon fade `start`, falling smoothly to 0.0 at `end`.
+    // Always 1.0 for anything but a plane with a fade set.
+    pub fn horizon_fade(&self, distance: Number) -> Number {
+        if self.kind != ShapeKind::Plane || self.maximum <= 0.0 {
+            return 1.0;
+        }
+        let span = (self.maximum - self.minimum).max(EPSILON);
+        let x = ((distance - self.minimum) / span).clamp(0.0, 1.0);
+        1.0 - x * x * (3.0 - 2.0 * x)
+    }
+    // The material a hit shades with: the cap material for a cap hit when one is
+    // set, `material` otherwise.
+    pub fn material_for(&self, on_cap: bool) -> &Material {
+        if on_cap && self.has_cap_material != 0 {
+            &self.cap_material
+        } else {
+            &self.material
+        }
+    }
+    // The cached bounding sphere of a group that culls with one (and has had
+    // `World::compute_bounds` run); None otherwise.
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        if self.cull_with_sphere != 0 && self.has_bounds != 0 {
+            Some(self.bounding_sphere)
+        } else {
+            None
+        }
+    }
+    // Whether a ray (in this group's space) can reach its children: tested
+    // against the bounding sphere if the group opted into one, else the box.
+    // A group with an unbounded child has an infinite sphere, which culls
+    // nothing, so it keeps to the box.
+    pub fn bounds_admit(&self, local_ray: &Ray) -> bool {
+        if self.cull_with_sphere != 0 && self.bounding_sphere.radius.is_finite() {
+            self.bounding_sphere.intersects(local_ray)
+        } else {
+            self.bounds.intersects(local_ray)
+        }
+    }
+    // Whether no straight ray leaving this shape's surface outward can hit it
+    // again: true for the closed convex shapes and the flat ones. A bounce off
+    // the outside of such a shape (or a refracted ray leaving it) can safely
+    // ignore it; see `Scene::intersect_world_ignoring`.
+    pub fn is_convex(&self) -> bool {
+        match self.kind {
+            ShapeKind::Sphere
+            | ShapeKind::Plane
+            | ShapeKind::Cube
+            | ShapeKind::Disk
+            | ShapeKind::Slab
+            | ShapeKind::Triangle
+            | ShapeKind::SmoothTriangle => true,
+            // Open cylinders and double-napped cones can see their own inner
+            // walls; groups and CSG nodes are never the object a ray hit.
+            ShapeKind::Cylinder | ShapeKind::Cone | ShapeKind::Group | ShapeKind::Csg => false,
+        }
+    }
+    // Whether `point` (in the same space as `normal_at`: this shape's transform
+    // applied, enclosing groups not) lies inside the solid this shape bounds,
+    // for placing objects and simple collision checks. The surface itself, to
+    // within EPSILON, counts as inside. A cylinder or cone is the volume within
+    // its radius between `minimum` and `maximum`, capped or not. Planes, disks
+    // and triangles enclose nothing, and groups and CSG nodes are not solids of
+    // their own, so they return false.
+    pub fn contains_point(&self, point: Point) -> bool {
+        let p = self.get_inverse_transform() * point;
+        let (x, y, z) = (p.x(), p.y(), p.z());
+        let within_y = y >= self.minimum - EPSILON && y <= self.maximum + EPSILON;
+        match self.kind {
+            ShapeKind::Sphere => x * x + y * y + z * z <= 1.0 + EPSILON,
+            ShapeKind::Cube => x.abs().max(y.abs()).max(z.abs()) <= 1.0 + EPSILON,
+            ShapeKind::Cylinder => within_y && x * x + z * z <= 1.0 + EPSILON,
+            ShapeKind::Cone => within_y && x * x + z * z <= y * y + EPSILON,
+            ShapeKind::Slab => within_y,
+            ShapeKind::Plane
+            | ShapeKind::Disk
+            | ShapeKind::Triangle
+            | ShapeKind::SmoothTriangle
+            | ShapeKind::Group
+            | ShapeKind::Csg => false,
+        }
+    }
     // The shape's normal in its own object space. Lifting it into world space
     // (accounting for any enclosing groups) is done by `World::normal_at`.
     pub fn local_normal_at(&self, point: &Point) -> Vector {
@@ -281,6 +486,8 @@ impl Primitive {
             ShapeKind::Cube => cube_normal_at(point),
             ShapeKind::Cylinder => cylinder_normal_at(self, point),
             ShapeKind::Cone => cone_normal_at(self, point),
+            ShapeKind::Disk => disk_normal_at(point),
+            ShapeKind::Slab => slab_normal_at(self, point),
             ShapeKind::Triangle => triangle_normal_at(self),
             ShapeKind::SmoothTriangle => smooth_triangle_local_normal_at_uv(self, u, v),
             // Groups and CSG nodes have no surface; the normal is resolved on the
@@ -338,6 +545,30 @@ impl Primitive {
                     },
                 )
             }
+            ShapeKind::Disk => BoundingBox::new(
+                Point {
+                    x: -self.maximum,
+                    y: 0.0,
+                    z: -self.maximum,
+                },
+                Point {
+                    x: self.maximum,
+                    y: 0.0,
+                    z: self.maximum,
+                },
+            ),
+            ShapeKind::Slab => BoundingBox::new(
+                Point {
+                    x: Number::NEG_INFINITY,
+                    y: self.minimum,
+                    z: Number::NEG_INFINITY,
+                },
+                Point {
+                    x: Number::INFINITY,
+                    y: self.maximum,
+                    z: Number::INFINITY,
+                },
+            ),
             // Like a group, a CSG node's real box (the union of its children) is
             // cached on the node by `World::compute_bounds`.
             ShapeKind::Group | ShapeKind::Csg => BoundingBox::empty(),
@@ -365,6 +596,149 @@ impl Primitive {
             ),
         }
     }
+    // The surface area after the shape's own transform (in its parent's space;
+    // enclosing groups are not applied). Flat faces are exact: a linear map
+    // scales a planar region's area by one factor. Curved surfaces are
+    // integrated numerically (`transformed_area`), exact to well under 0.01% of
+    // the area for any transform. Planes, slabs and uncapped infinite cylinders
+    // and cones have infinite area. Groups and CSG nodes have no surface of
+    // their own and report 0.0.
+    pub fn area(&self) -> Number {
+        let m = self.get_transform();
+        let x = Vector {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let y = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let z = Vector {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        // How much the transform scales an area lying in the plane of `a`, `b`.
+        let scale = |a: Vector, b: Vector| (m * a).cross(m * b).magnitude();
+        // A radius-`r` disk in the xz plane (a cylinder or cone cap).
+        let disk = |r: Number| PI * r * r * scale(x, z);
+        match self.kind {
+            ShapeKind::Plane | ShapeKind::Slab => Number::INFINITY,
+            ShapeKind::Group | ShapeKind::Csg => 0.0,
+            ShapeKind::Triangle | ShapeKind::SmoothTriangle => 0.5 * scale(self.e1, self.e2),
+            ShapeKind::Disk => {
+                PI * (self.maximum * self.maximum - self.minimum * self.minimum) * scale(x, z)
+            }
+            // Two faces of side 2 across each pair of axes.
+            ShapeKind::Cube => 8.0 * (scale(x, y) + scale(y, z) + scale(x, z)),
+            ShapeKind::Sphere => transformed_area(m, (0.0, PI), |theta, phi| {
+                let (st, ct, sp, cp) = (theta.sin(), theta.cos(), phi.sin(), phi.cos());
+                (
+                    Vector {
+                        x: ct * cp,
+                        y: -st,
+                        z: ct * sp,
+                    },
+                    Vector {
+                        x: -st * sp,
+                        y: 0.0,
+                        z: st * cp,
+                    },
+                )
+            }),
+            ShapeKind::Cylinder | ShapeKind::Cone => {
+                // The default, unbounded y-range runs Number::MIN..MAX, whose
+                // length overflows to infinity.
+                if !(self.maximum - self.minimum).is_finite() {
+                    return Number::INFINITY;
+                }
+                let cone = self.kind == ShapeKind::Cone;
+                let side = transformed_area(m, (self.minimum, self.maximum), |h, phi| {
+                    let (sp, cp) = (phi.sin(), phi.cos());
+                    // The wall at height h has radius 1 (cylinder) or h (cone).
+                    let (r, dr) = if cone { (h, 1.0) } else { (1.0, 0.0) };
+                    (
+                        Vector {
+                            x: dr * cp,
+                            y: 1.0,
+                            z: dr * sp,
+                        },
+                        Vector {
+                            x: -r * sp,
+                            y: 0.0,
+                            z: r * cp,
+                        },
+                    )
+                });
+                if self.closed == 0 {
+                    side
+                } else if cone {
+                    side + disk(self.minimum) + disk(self.maximum)
+                } else {
+                    side + 2.0 * disk(1.0)
+                }
+            }
+        }
+    }
+    // A point on the surface and the outward normal there for canonical (u, v)
+    // in [0, 1), in the same space as `area` (this shape's transform applied,
+    // enclosing groups not), for sampling the shape as a light or for global
+    // illumination. Uniform (u, v) spreads points uniformly by area over the
+    // untransformed shape; a non-uniform scale skews that. Spheres, cubes, disks
+    // and triangles are supported. Shapes with no finite surface of their own
+    // (planes, slabs, cylinders, cones, groups, CSG) return None.
+    pub fn sample_point(&self, u: Number, v: Number) -> Option<(Point, Vector)> {
+        let around = |r: Number, y: Number| {
+            let phi = 2.0 * PI * v;
+            Point {
+                x: r * phi.cos(),
+                y,
+                z: r * phi.sin(),
+            }
+        };
+        // The barycentric (u, v) a smooth triangle interpolates its normal with.
+        let mut bary = (0.0, 0.0);
+        let local = match self.kind {
+            ShapeKind::Sphere => {
+                // Height uniform in [-1, 1] and a uniform angle around +y is
+                // uniform by area (Archimedes' hat-box theorem).
+                let y = 1.0 - 2.0 * u;
+                around((1.0 - y * y).max(0.0).sqrt(), y)
+            }
+            ShapeKind::Disk => {
+                let (inner, outer) = (self.minimum * self.minimum, self.maximum * self.maximum);
+                around((inner + u * (outer - inner)).sqrt(), 0.0)
+            }
+            ShapeKind::Triangle | ShapeKind::SmoothTriangle => {
+                // Fold the unit square's upper half back onto the lower one.
+                bary = if u + v > 1.0 {
+                    (1.0 - u, 1.0 - v)
+                } else {
+                    (u, v)
+                };
+                self.p1 + self.e1 * bary.0 + self.e2 * bary.1
+            }
+            ShapeKind::Cube => {
+                // u picks one of the six equal faces, then spans it.
+                let face = ((u * 6.0) as usize).min(5);
+                let a = 2.0 * (u * 6.0 - face as Number) - 1.0;
+                let b = 2.0 * v - 1.0;
+                let side = [1.0, -1.0][face % 2];
+                let (x, y, z) = match face / 2 {
+                    0 => (side, a, b),
+                    1 => (a, side, b),
+                    _ => (a, b, side),
+                };
+                Point { x, y, z }
+            }
+            _ => return None,
+        };
+        let normal = self.local_normal_at_uv(&local, bary.0, bary.1);
+        let normal = (normal_matrix(&self.get_inverse_transform()) * normal).normalize();
+        Some((self.get_transform() * local, normal))
+    }
     pub fn with(
         shape: fn() -> Primitive,
         transform: Matrix<4, 4>,
@@ -391,6 +765,8 @@ impl Primitive {
             ShapeKind::Cube => cube_intersect(&local_ray, object_id, xs),
             ShapeKind::Cylinder => cylinder_intersect(self, &local_ray, object_id, xs),
             ShapeKind::Cone => cone_intersect(self, &local_ray, object_id, xs),
+            ShapeKind::Disk => disk_intersect(self, &local_ray, object_id, xs),
+            ShapeKind::Slab => slab_intersect(self, &local_ray, object_id, xs),
             ShapeKind::Triangle => triangle_intersect(self, &local_ray, object_id, xs),
             ShapeKind::SmoothTriangle => triangle_intersect(self, &local_ray, object_id, xs),
             // Groups and CSG nodes are traversed by `World::intersect_object`,
@@ -398,15 +774,41 @@ impl Primitive {
             ShapeKind::Group | ShapeKind::Csg => {}
         }
     }
+    // A mirroring transform (negative determinant, e.g. `scaling(-1, 1, 1)`) needs
+    // no special case: it flips the surface's local point and normal together, and
+    // the inverse transpose carries an outward local normal to an outward world
+    // normal. Negating it here on a negative determinant would point it inward.
     pub fn normal_at(&self, point: &Point) -> Vector {
         let inverse_transform = self.get_inverse_transform();
         let local_point = inverse_transform * point.clone();
         let local_normal = self.local_normal_at(&local_point);
-        let world_normal = transpose(&inverse_transform) * local_normal;
+        let world_normal = normal_matrix(&inverse_transform) * local_normal;
         world_normal.normalize()
     }
 }
 
+// The area of a surface parameterized by (s, phi), s over `s_range` and phi
+// once around, after the linear part of `m`: the midpoint rule over a grid of
+// |m*dP/ds x m*dP/dphi|, given the two partial derivatives at each point.
+fn transformed_area(
+    m: Matrix<4, 4>,
+    s_range: (Number, Number),
+    partials: impl Fn(Number, Number) -> (Vector, Vector),
+) -> Number {
+    const STEPS: usize = 128;
+    let ds = (s_range.1 - s_range.0) / STEPS as Number;
+    let dphi = 2.0 * PI / STEPS as Number;
+    let mut total = 0.0;
+    for i in 0..STEPS {
+        let s = s_range.0 + (i as Number + 0.5) * ds;
+        for j in 0..STEPS {
+            let (d_s, d_phi) = partials(s, (j as Number + 0.5) * dphi);
+            total += (m * d_s).cross(m * d_phi).magnitude();
+        }
+    }
+    total * ds * dphi
+}
+
 pub trait HasTransform {
     fn set_transform(&mut self, transform: Matrix<4, 4>) -> ();
     fn get_transform(&self) -> Matrix<4, 4>;
@@ -485,11 +887,17 @@ impl HasMaterial for Material {
     fn get_material(&self) -> Material {
         self.clone()
     }
+    fn material_ref(&self) -> &Material {
+        self
+    }
 }
 
+// `get_material` hands out an owned copy for editing and re-setting;
+// `material_ref` borrows it, which is what the per-point shading path wants.
 pub trait HasMaterial {
     fn set_material(&mut self, material: Material) -> ();
     fn get_material(&self) -> Material;
+    fn material_ref(&self) -> &Material;
 }
 
 impl HasTransform for Primitive {
@@ -510,6 +918,9 @@ impl HasMaterial for Primitive {
     fn get_material(&self) -> Material {
         self.material.clone()
     }
+    fn material_ref(&self) -> &Material {
+        &self.material
+    }
 }
 
 #[cfg(test)]
@@ -529,6 +940,72 @@ mod tests {
         assert_eq!(s.get_transform(), translation(2.0, 3.0, 4.0));
     }
     #[test]
+    fn every_shape_kind_shares_one_transform_store() {
+        let t = translation(2.0, 3.0, 4.0) * scaling(1.0, 2.0, 3.0);
+        let mut sphere = Primitive::sphere();
+        let mut plane = Primitive::plane();
+        sphere.set_transform(t);
+        plane.set_transform(t);
+        assert_eq!(sphere.get_transform(), plane.get_transform());
+        assert_eq!(sphere.get_inverse_transform(), plane.get_inverse_transform());
+        assert_eq!(sphere.get_inverse_transform() * t, Matrix::identity());
+    }
+    #[test]
+    fn a_sphere_contains_its_center_but_not_a_point_two_away() {
+        let s = Primitive::sphere();
+        assert!(s.contains_point(Point::from([0.0, 0.0, 0.0])));
+        assert!(!s.contains_point(Point::from([0.0, 2.0, 0.0])));
+        let mut moved = Primitive::sphere();
+        moved.set_transform(translation(0.0, 2.0, 0.0));
+        assert!(moved.contains_point(Point::from([0.0, 2.0, 0.0])));
+        assert!(!Primitive::plane().contains_point(Point::from([0.0, 0.0, 0.0])));
+    }
+    #[test]
+    fn sphere_samples_lie_on_the_sphere_facing_out() {
+        let s = Primitive::sphere();
+        for i in 0..8 {
+            for j in 0..8 {
+                let (u, v) = (i as Number / 8.0, j as Number / 8.0 + 0.05);
+                let (p, n) = s.sample_point(u, v).unwrap();
+                let radial = p - Point::default();
+                assert_almost_eq!(radial.magnitude(), 1.0);
+                assert_approx_eq!(n, radial);
+            }
+        }
+        assert!(Primitive::plane().sample_point(0.5, 0.5).is_none());
+    }
+    #[test]
+    fn triangle_samples_lie_inside_the_triangle() {
+        let (p1, p2, p3) = (
+            Point {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            Point {
+                x: -1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        );
+        let t = Primitive::triangle(p1, p2, p3);
+        for i in 0..10 {
+            for j in 0..10 {
+                let (u, v) = (i as Number / 10.0, j as Number / 10.0);
+                let (p, n) = t.sample_point(u, v).unwrap();
+                assert_almost_eq!(p.z, 0.0);
+                assert!(p.y >= -EPSILON && p.y <= 1.0 + EPSILON);
+                assert!(p.x.abs() <= 1.0 - p.y + EPSILON, "{p:?} for {u}, {v}");
+                assert_eq!(n, t.normal);
+            }
+        }
+    }
+    #[test]
     fn the_default_material() {
         let s = Primitive::sphere();
         assert_eq!(s.get_material(), Material::default());
@@ -542,6 +1019,15 @@ mod tests {
         assert_eq!(s.get_material(), m);
     }
     #[test]
+    fn material_ref_borrows_the_shapes_own_material() {
+        let mut s = Primitive::sphere();
+        let mut m = Material::default();
+        m.set_ambient(0.5);
+        s.set_material(m.clone());
+        assert!(core::ptr::eq(s.material_ref(), &s.material));
+        assert_eq!(*s.material_ref(), m);
+    }
+    #[test]
     fn intersecting_a_scaled_shape_with_a_ray() {
         // The transform must be applied (ray moved into object space) before the
         // local intersection. A unit sphere scaled by 2 along z, hit head-on from
@@ -611,6 +1097,40 @@ mod tests {
         )
     }
     #[test]
+    fn a_mirrored_sphere_keeps_outward_normals() {
+        let mut s = Primitive::sphere();
+        s.set_transform(scaling(-1.0, 1.0, 1.0));
+        let s3 = sqrt(3.0) / 3.0;
+        let points = [
+            Point { x: 1.0, y: 0.0, z: 0.0 },
+            Point { x: -1.0, y: 0.0, z: 0.0 },
+            Point { x: 0.0, y: 1.0, z: 0.0 },
+            Point { x: 0.0, y: 0.0, z: -1.0 },
+            Point { x: s3, y: -s3, z: s3 },
+        ];
+        for p in points {
+            let n = s.normal_at(&p);
+            let outward = Vector { x: p.x, y: p.y, z: p.z };
+            assert_approx_eq!(n, outward, "p={p:?}");
+        }
+    }
+    #[test]
+    fn a_mirrored_and_translated_sphere_keeps_outward_normals() {
+        // Mirrored in a world with a group parent too, through World::normal_at.
+        let mut w = crate::worlds::World::new();
+        let g = w.add_object(Primitive::group());
+        w.objects[g].set_transform(scaling(1.0, -2.0, 1.0));
+        let mut sphere = Primitive::sphere();
+        sphere.set_transform(translation(3.0, 0.0, 0.0).then(scaling(-1.0, 1.0, 1.0)));
+        let id = w.add_child(g, sphere);
+        // The sphere's center sits at (-3, 0, 0) in world space; its widest point
+        // on +x is (-2, 0, 0), where the outward normal is +x.
+        let n = w.normal_at(id, Point { x: -2.0, y: 0.0, z: 0.0 });
+        assert_eq!(n, Vector { x: 1.0, y: 0.0, z: 0.0 });
+        let n = w.normal_at(id, Point { x: -3.0, y: -2.0, z: 0.0 });
+        assert_eq!(n, Vector { x: 0.0, y: -1.0, z: 0.0 });
+    }
+    #[test]
     fn computing_the_normal_on_a_transformed_shape() {
         let mut s = Primitive::sphere();
         s.set_transform(rotation_z(PI / 5.0).then(scaling(1.0, 0.5, 1.0)));
@@ -619,7 +1139,7 @@ mod tests {
             y: sqrt(2.0) / 2.0,
             z: -sqrt(2.0) / 2.0,
         });
-        assert_eq!(
+        assert_approx_eq!(
             n,
             Vector {
                 x: 0.0,
@@ -628,4 +1148,52 @@ mod tests {
             }
         )
     }
+    #[test]
+    fn the_area_of_a_unit_sphere() {
+        assert_almost_eq!(Primitive::sphere().area(), 4.0 * PI, 1e-3);
+        let mut s = Primitive::sphere();
+        s.set_transform(translation(5.0, 0.0, 0.0).then(scaling(2.0, 2.0, 2.0)));
+        assert_almost_eq!(s.area(), 16.0 * PI, 1e-2);
+    }
+    #[test]
+    fn the_area_of_a_right_triangle() {
+        let t = Primitive::triangle(
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point {
+                x: 3.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point {
+                x: 0.0,
+                y: 4.0,
+                z: 0.0,
+            },
+        );
+        assert_almost_eq!(t.area(), 6.0);
+        let mut stretched = t.clone();
+        stretched.set_transform(scaling(2.0, 1.0, 1.0));
+        assert_almost_eq!(stretched.area(), 12.0);
+    }
+    #[test]
+    fn the_areas_of_the_other_shapes() {
+        assert_almost_eq!(Primitive::cube().area(), 24.0);
+        let mut cylinder = Primitive::cylinder();
+        assert_eq!(cylinder.area(), Number::INFINITY);
+        cylinder.minimum = 0.0;
+        cylinder.maximum = 1.0;
+        assert_almost_eq!(cylinder.area(), 2.0 * PI, 1e-3);
+        cylinder.closed = 1;
+        assert_almost_eq!(cylinder.area(), 4.0 * PI, 1e-3);
+        let mut cone = Primitive::cone();
+        cone.minimum = 0.0;
+        cone.maximum = 1.0;
+        assert_almost_eq!(cone.area(), sqrt(2.0) * PI, 1e-3);
+        assert_almost_eq!(Primitive::disk(1.0, 2.0).area(), 3.0 * PI, 1e-4);
+        assert_eq!(Primitive::plane().area(), Number::INFINITY);
+    }
 }
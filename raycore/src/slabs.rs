@@ -0,0 +1,172 @@
+use crate::intersections::*;
+use crate::rays::*;
+use crate::shapes::Primitive;
+use crate::tuples::*;
+
+// A slab: the solid region between two parallel planes at y = `minimum` and
+// y = `maximum`, unbounded in x and z, for a floor or wall with visible
+// thickness. A ray crossing it enters through one face and leaves through the
+// other. A ray parallel to the faces crosses neither: it reports nothing, like
+// a plane, unless it runs between them, and then it is inside from start to
+// end, which shows up as a single crossing infinitely far behind it so CSG
+// still sees it as inside. A `minimum` of -infinity makes it a half-space, all
+// of space below y = `maximum`, for cutting solids open with CSG (see
+// `World::add_clipped`). Its far face is at infinity: a crossing behind the
+// ray is kept, as CSG needs it to know the ray starts inside, but one ahead is
+// no surface anything can hit, so it is dropped.
+pub fn slab_intersect(prim: &Primitive, ray: &Ray, object_id: usize, xs: &mut Intersections) {
+    if ray.direction.y().abs() < EPSILON {
+        let y = ray.origin.y();
+        if y > prim.minimum + EPSILON && y < prim.maximum - EPSILON {
+            xs.push(Intersection::new(Number::NEG_INFINITY, object_id));
+        }
+        return;
+    }
+    let t0 = (prim.minimum - ray.origin.y()) / ray.direction.y();
+    let t1 = (prim.maximum - ray.origin.y()) / ray.direction.y();
+    let (near, far) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+    xs.push(Intersection::new(near, object_id));
+    if far.is_finite() {
+        xs.push(Intersection::new(far, object_id));
+    }
+}
+
+// Out of whichever face the point is nearer: up on the top face, down on the
+// bottom one. A half-space has only the top.
+pub fn slab_normal_at(prim: &Primitive, point: &Point) -> Vector {
+    let mid = (prim.minimum + prim.maximum) / 2.0;
+    Vector {
+        x: 0.0,
+        y: if point.y() >= mid { 1.0 } else { -1.0 },
+        z: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_ray_passes_through_a_slab() {
+        let slab = Primitive::slab(0.5);
+        let r = Ray {
+            origin: Point {
+                x: 0.3,
+                y: 2.0,
+                z: -1.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+        };
+        let mut xs = Intersections::empty();
+        slab_intersect(&slab, &r, 0, &mut xs);
+        assert_eq!(xs.count(), 2);
+        assert_eq!(xs[0].t, 1.75);
+        assert_eq!(xs[1].t, 2.25);
+    }
+
+    #[test]
+    fn a_half_space_is_entered_through_its_top_face_only() {
+        let below = Primitive::half_space();
+        let down = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+        };
+        let mut xs = Intersections::empty();
+        slab_intersect(&below, &down, 0, &mut xs);
+        assert_eq!(xs.count(), 1);
+        assert_eq!(xs[0].t, 2.0);
+        // Looking up from inside, it was entered infinitely far back.
+        let up = Ray {
+            origin: Point {
+                x: 0.0,
+                y: -2.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+        };
+        let mut xs = Intersections::empty();
+        slab_intersect(&below, &up, 0, &mut xs);
+        assert_eq!(xs.count(), 2);
+        assert_eq!(xs[0].t, Number::NEG_INFINITY);
+        assert_eq!(xs[1].t, 2.0);
+        assert_eq!(
+            below.local_normal_at(&Point {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0
+            }),
+            Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn a_grazing_ray_misses_a_slab() {
+        let slab = Primitive::slab(0.5);
+        let r = Ray {
+            origin: Point {
+                x: -2.0,
+                y: 0.25,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        };
+        let mut xs = Intersections::empty();
+        slab_intersect(&slab, &r, 0, &mut xs);
+        assert_eq!(xs.count(), 0);
+    }
+
+    #[test]
+    fn the_normal_on_a_slab_points_out_of_the_nearer_face() {
+        let slab = Primitive::slab(0.5);
+        let top = slab.local_normal_at(&Point {
+            x: 4.0,
+            y: 0.25,
+            z: 1.0,
+        });
+        let bottom = slab.local_normal_at(&Point {
+            x: -3.0,
+            y: -0.25,
+            z: 2.0,
+        });
+        assert_eq!(
+            top,
+            Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0
+            }
+        );
+        assert_eq!(
+            bottom,
+            Vector {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0
+            }
+        );
+    }
+}
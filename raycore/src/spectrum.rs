@@ -0,0 +1,169 @@
+//! Groundwork for spectral rendering. `Spectrum<N>` samples radiance in `N`
+//! equal-width bins across the visible range, and `Shade` is the arithmetic
+//! the Phong terms in `materials::lightning_surface` need, implemented by both
+//! `Color` (the default, and the only one the renderer uses today) and
+//! `Spectrum`.
+use core::ops::{Add, Mul};
+
+use crate::tuples::*;
+
+// The visible range the bins of a `Spectrum` divide evenly, in nanometres.
+pub const WAVELENGTH_MIN: Number = 380.0;
+pub const WAVELENGTH_MAX: Number = 780.0;
+
+// What shading needs from a color representation: add, scale, modulate by
+// another color, and convert to and from RGB (light and material colors are
+// authored in RGB; the canvas stores RGB).
+pub trait Shade: Copy + Add<Output = Self> + Mul<Number, Output = Self> + Mul<Output = Self> {
+    fn from_rgb(color: Color) -> Self;
+    fn to_rgb(&self) -> Color;
+}
+
+impl Shade for Color {
+    fn from_rgb(color: Color) -> Self {
+        color
+    }
+    fn to_rgb(&self) -> Color {
+        *self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spectrum<const N: usize> {
+    pub bins: [Number; N],
+}
+
+impl<const N: usize> Spectrum<N> {
+    pub const fn flat(value: Number) -> Self {
+        Spectrum { bins: [value; N] }
+    }
+    // Center wavelength of bin `i`.
+    pub fn wavelength(i: usize) -> Number {
+        let width = (WAVELENGTH_MAX - WAVELENGTH_MIN) / N as Number;
+        WAVELENGTH_MIN + width * (i as Number + 0.5)
+    }
+    // Integrate against the CIE 1931 color matching functions and convert the
+    // XYZ to linear sRGB, white-balanced so the equal-energy (flat) spectrum maps
+    // to neutral gray: `flat(v).to_rgb()` is (v, v, v).
+    fn raw_rgb(&self) -> Color {
+        let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+        for i in 0..N {
+            let (xb, yb, zb) = cie_xyz(Self::wavelength(i));
+            x += self.bins[i] * xb;
+            y += self.bins[i] * yb;
+            z += self.bins[i] * zb;
+        }
+        Color {
+            r: 3.2406 * x - 1.5372 * y - 0.4986 * z,
+            g: -0.9689 * x + 1.8758 * y + 0.0415 * z,
+            b: 0.0557 * x - 0.2040 * y + 1.0570 * z,
+        }
+    }
+}
+
+impl<const N: usize> Shade for Spectrum<N> {
+    // A crude three-band upsampling: blue below 490 nm, green up to 580 nm and
+    // red above. Enough to light spectral scenes with RGB-authored colors; not a
+    // round trip for saturated colors.
+    fn from_rgb(color: Color) -> Self {
+        let mut bins = [0.0; N];
+        for (i, bin) in bins.iter_mut().enumerate() {
+            let l = Self::wavelength(i);
+            *bin = if l < 490.0 {
+                color.b
+            } else if l < 580.0 {
+                color.g
+            } else {
+                color.r
+            };
+        }
+        Spectrum { bins }
+    }
+    fn to_rgb(&self) -> Color {
+        let rgb = self.raw_rgb();
+        let white = Self::flat(1.0).raw_rgb();
+        Color {
+            r: rgb.r / white.r,
+            g: rgb.g / white.g,
+            b: rgb.b / white.b,
+        }
+    }
+}
+
+impl<const N: usize> Add for Spectrum<N> {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self {
+        for i in 0..N {
+            self.bins[i] += rhs.bins[i];
+        }
+        self
+    }
+}
+
+impl<const N: usize> Mul for Spectrum<N> {
+    type Output = Self;
+    fn mul(mut self, rhs: Self) -> Self {
+        for i in 0..N {
+            self.bins[i] *= rhs.bins[i];
+        }
+        self
+    }
+}
+
+impl<const N: usize> Mul<Number> for Spectrum<N> {
+    type Output = Self;
+    fn mul(mut self, rhs: Number) -> Self {
+        for bin in self.bins.iter_mut() {
+            *bin *= rhs;
+        }
+        self
+    }
+}
+
+// The CIE 1931 2° color matching functions at wavelength `l` (nm), using the
+// multi-lobe Gaussian fit of Wyman, Sloan and Shirley (2013), accurate to well
+// under a percent of the tabulated curves.
+fn cie_xyz(l: Number) -> (Number, Number, Number) {
+    fn g(l: Number, mu: Number, below: Number, above: Number) -> Number {
+        let sigma = if l < mu { below } else { above };
+        let t = (l - mu) / sigma;
+        (-0.5 * t * t).exp()
+    }
+    let x = 1.056 * g(l, 599.8, 37.9, 31.0) + 0.362 * g(l, 442.0, 16.0, 26.7)
+        - 0.065 * g(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * g(l, 568.8, 46.9, 40.5) + 0.286 * g(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * g(l, 437.0, 11.8, 36.0) + 0.681 * g(l, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_flat_spectrum_converts_to_neutral_gray() {
+        let gray = Spectrum::<16>::flat(0.5).to_rgb();
+        assert!((gray.r - 0.5).abs() < 0.01);
+        assert!((gray.g - 0.5).abs() < 0.01);
+        assert!((gray.b - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn a_long_wavelength_spectrum_converts_to_red() {
+        let mut s = Spectrum::<16>::flat(0.0);
+        s.bins[11] = 1.0; // ~640 nm
+        let rgb = s.to_rgb();
+        assert!(rgb.r > rgb.g && rgb.r > rgb.b, "{rgb:?}");
+    }
+
+    #[test]
+    fn shade_arithmetic_is_per_bin() {
+        let a = Spectrum::<4> {
+            bins: [1.0, 2.0, 3.0, 4.0],
+        };
+        let b = Spectrum::<4>::flat(2.0);
+        assert_eq!((a + b).bins, [3.0, 4.0, 5.0, 6.0]);
+        assert_eq!((a * b).bins, [2.0, 4.0, 6.0, 8.0]);
+        assert_eq!((a * 0.5).bins, [0.5, 1.0, 1.5, 2.0]);
+    }
+}
@@ -1,6 +1,10 @@
 use crate::intersections::*;
 use crate::rays::*;
 use crate::tuples::*;
+#[cfg(feature = "std")]
+use crate::shapes::Primitive;
+#[cfg(feature = "std")]
+use crate::transformations::PI;
 #[cfg(test)]
 use crate::matrices::*;
 #[cfg(test)]
@@ -47,6 +51,54 @@ pub fn sphere_normal_at(point: &Point) -> Vector {
     }
 }
 
+// A UV-sphere mesh approximating the unit sphere: `lat` bands from pole to pole
+// by `lon` segments around the y axis, as smooth triangles whose vertex normals
+// are the exact sphere normals at the vertices. The bands touching the poles
+// collapse to one triangle per segment, so the mesh holds `2 * lon * (lat - 1)`
+// triangles. Drop it into a world with `World::add_tessellated_sphere`, e.g. to
+// compare the mesh against the analytic sphere or to export the geometry.
+#[cfg(feature = "std")]
+pub fn tessellated_sphere(lat: usize, lon: usize) -> Vec<Primitive> {
+    let lat = lat.max(2);
+    let lon = lon.max(3);
+    let vertex = |i: usize, j: usize| {
+        let theta = PI * i as Number / lat as Number;
+        let phi = 2.0 * PI * (j % lon) as Number / lon as Number;
+        Point {
+            x: theta.sin() * phi.sin(),
+            y: theta.cos(),
+            z: theta.sin() * phi.cos(),
+        }
+    };
+    let smooth = |a: Point, b: Point, c: Point| {
+        Primitive::smooth_triangle(
+            a,
+            b,
+            c,
+            sphere_normal_at(&a),
+            sphere_normal_at(&b),
+            sphere_normal_at(&c),
+        )
+    };
+
+    let mut triangles = Vec::with_capacity(2 * lon * (lat - 1));
+    for i in 0..lat {
+        for j in 0..lon {
+            let (a, b) = (vertex(i, j), vertex(i, j + 1));
+            let (c, d) = (vertex(i + 1, j), vertex(i + 1, j + 1));
+            // At the north pole a == b, at the south pole c == d; skip the
+            // triangle that would be degenerate there.
+            if i != lat - 1 {
+                triangles.push(smooth(a, d, c));
+            }
+            if i != 0 {
+                triangles.push(smooth(a, b, d));
+            }
+        }
+    }
+    triangles
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -287,7 +339,7 @@ mod tests {
             y: sqrt(3.0) / 3.0,
             z: sqrt(3.0) / 3.0,
         });
-        assert_eq!(n, n.normalize());
+        assert_approx_eq!(n, n.normalize());
     }
     #[test]
     fn computing_the_normal_on_a_translated_sphere() {
@@ -298,7 +350,7 @@ mod tests {
             y: 1.70711,
             z: -0.70711,
         });
-        assert_eq!(
+        assert_approx_eq!(
             n,
             Vector {
                 x: 0.0,
@@ -319,7 +371,7 @@ mod tests {
             y: sqrt(2.0) / 2.0,
             z: -sqrt(2.0) / 2.0,
         });
-        assert_eq!(
+        assert_approx_eq!(
             n,
             Vector {
                 x: 0.0,
@@ -349,4 +401,20 @@ mod tests {
         assert_eq!(s.get_material().transparency, 1.0);
         assert_eq!(s.get_material().refractive_index, 1.5);
     }
+
+    #[test]
+    fn tessellating_a_sphere_puts_every_vertex_on_the_unit_sphere() {
+        let triangles = tessellated_sphere(4, 4);
+        assert_eq!(triangles.len(), 24);
+        for t in &triangles {
+            assert_eq!(t.kind, ShapeKind::SmoothTriangle);
+            for (p, n) in [(t.p1, t.n1), (t.p2, t.n2), (t.p3, t.n3)] {
+                let radius = sphere_normal_at(&p).magnitude();
+                assert!((radius - 1.0).abs() < EPSILON, "{p:?} is off the sphere");
+                assert_eq!(n, sphere_normal_at(&p));
+            }
+            // Wound so the face normal points away from the center.
+            assert!(t.e2.cross(t.e1).dot(sphere_normal_at(&t.p1)) > 0.0);
+        }
+    }
 }
@@ -3,6 +3,7 @@
 // and cube-face mapping. The `Pattern::Texture`/`Pattern::Cube` variants in
 // `patterns.rs` glue these to the rest of the renderer.
 
+use crate::font::{glyph_pixel, GLYPH_HEIGHT, GLYPH_WIDTH};
 use crate::transformations::PI;
 use crate::tuples::*;
 
@@ -18,6 +19,7 @@ const fn black() -> Color {
 // Flat tagged struct for rust-gpu/SPIR-V compatibility:
 //   kind 0 = checkers (uses width/height/a/b)
 //   kind 1 = align_check (uses main/ul/ur/bl/br)
+//   kind 2 = text (uses text/a/b, width = character count)
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct UvFace {
@@ -31,8 +33,18 @@ pub struct UvFace {
     pub ur: Color,
     pub bl: Color,
     pub br: Color,
+    // Text only: up to MAX_TEXT_CHARS ASCII codes, four to a word, the first
+    // in the low byte.
+    pub text: [u32; MAX_TEXT_CHARS / 4],
 }
 
+// The longest string `UvFace::text` keeps; the rest is cut off.
+pub const MAX_TEXT_CHARS: usize = 16;
+// Each character of a text face is a cell this many glyph pixels across and
+// high: the 5x7 glyph with a one-pixel margin all round.
+const TEXT_CELL_WIDTH: usize = GLYPH_WIDTH + 2;
+const TEXT_CELL_HEIGHT: usize = GLYPH_HEIGHT + 2;
+
 impl UvFace {
     // A checkerboard of `width` x `height` cells across the 0..1 UV square.
     pub const fn checkers(width: Number, height: Number, a: Color, b: Color) -> Self {
@@ -47,6 +59,7 @@ impl UvFace {
             ur: black(),
             bl: black(),
             br: black(),
+            text: [0; MAX_TEXT_CHARS / 4],
         }
     }
     // A center color with a distinct color in each corner, used to verify that a
@@ -63,6 +76,40 @@ impl UvFace {
             ur,
             bl,
             br,
+            text: [0; MAX_TEXT_CHARS / 4],
+        }
+    }
+    // `text` stamped across the UV square in the built-in 5x7 font (see
+    // `font`), `fg` on `bg`: one cell per character left to right over u, each
+    // spanning the full height in v. For labeling test renders; beyond
+    // MAX_TEXT_CHARS the text is cut off.
+    pub fn text(text: &str, fg: Color, bg: Color) -> Self {
+        let mut packed = [0; MAX_TEXT_CHARS / 4];
+        let mut len = 0;
+        for (i, byte) in text.bytes().take(MAX_TEXT_CHARS).enumerate() {
+            packed[i / 4] |= (byte as u32) << (8 * (i % 4));
+            len += 1;
+        }
+        UvFace {
+            kind: 2,
+            width: len as Number,
+            a: fg,
+            b: bg,
+            text: packed,
+            ..UvFace::checkers(0.0, 0.0, black(), black())
+        }
+    }
+    // This face with every color it can show passed through `f`.
+    pub fn map_colors(&self, f: impl Fn(Color) -> Color) -> Self {
+        UvFace {
+            a: f(self.a),
+            b: f(self.b),
+            main: f(self.main),
+            ul: f(self.ul),
+            ur: f(self.ur),
+            bl: f(self.bl),
+            br: f(self.br),
+            ..*self
         }
     }
     pub fn uv_pattern_at(&self, u: Number, v: Number) -> Color {
@@ -76,6 +123,25 @@ impl UvFace {
                     self.b
                 }
             }
+            2 => {
+                let len = self.width as usize;
+                if len == 0 {
+                    return self.b;
+                }
+                // Which character cell u falls in, then which pixel of that
+                // cell; v runs bottom to top, the glyph rows top to bottom.
+                let x = u.clamp(0.0, 0.9999) * len as Number;
+                let cell = x as usize;
+                let cx = ((x - cell as Number) * TEXT_CELL_WIDTH as Number) as usize;
+                let cy = ((1.0 - v.clamp(0.0, 0.9999)) * TEXT_CELL_HEIGHT as Number) as usize;
+                let c = (self.text[cell / 4] >> (8 * (cell % 4))) & 0xff;
+                // The margin is the wrapping of 0 - 1, which is off the glyph.
+                if glyph_pixel(c, cx.wrapping_sub(1), cy.wrapping_sub(1)) {
+                    self.a
+                } else {
+                    self.b
+                }
+            }
             _ => {
                 // Corners get their own color; everything else is `main`.
                 if v > 0.8 {
@@ -100,27 +166,44 @@ impl UvFace {
 }
 
 // How a 3D point in pattern space is reduced to (u, v), as a u32 tag:
-//   0 = spherical, 1 = planar, 2 = cylindrical.
+//   0 = spherical, 1 = planar, 2 = cylindrical, 3 = conical.
 pub const MAPPING_SPHERICAL: u32 = 0;
 pub const MAPPING_PLANAR: u32 = 1;
 pub const MAPPING_CYLINDRICAL: u32 = 2;
+pub const MAPPING_CONICAL: u32 = 3;
 
 pub fn uv_map(p: Point, mapping: u32) -> (Number, Number) {
     match mapping {
         MAPPING_PLANAR => planar_map(p),
         MAPPING_CYLINDRICAL => cylindrical_map(p),
+        MAPPING_CONICAL => conical_map(p),
         _ => spherical_map(p),
     }
 }
 
+// The u shared by the wrapping maps: the angle around +y, 0 at -z and rising
+// through +x. `atan2` gives both +PI and -PI on the -z seam (for +0.0 and -0.0
+// in x, or after rounding), which would make u 0 or 1 for the same place; 1
+// is folded back to 0 so u is always in [0, 1) and the seam samples the first
+// cell or texel, the same one just past it does. A checker wrapped this way
+// alternates all the way round only with an even `width`.
+fn azimuth_u(p: Point) -> Number {
+    let theta = p.x.atan2(p.z);
+    let raw_u = theta / (2.0 * PI);
+    let u = 1.0 - (raw_u + 0.5);
+    if u >= 1.0 {
+        0.0
+    } else {
+        u
+    }
+}
+
 // Wrap a point on a unit sphere to (u, v): u from the angle around +y, v from the
 // angle down from +y.
 pub fn spherical_map(p: Point) -> (Number, Number) {
-    let theta = p.x.atan2(p.z);
     let radius = (p.x * p.x + p.y * p.y + p.z * p.z).sqrt();
     let phi = (p.y / radius).acos();
-    let raw_u = theta / (2.0 * PI);
-    let u = 1.0 - (raw_u + 0.5);
+    let u = azimuth_u(p);
     let v = 1.0 - phi / PI;
     (u, v)
 }
@@ -132,13 +215,71 @@ pub fn planar_map(p: Point) -> (Number, Number) {
 
 // Wrap around a unit cylinder: u from the angle around +y, v from height.
 pub fn cylindrical_map(p: Point) -> (Number, Number) {
-    let theta = p.x.atan2(p.z);
-    let raw_u = theta / (2.0 * PI);
-    let u = 1.0 - (raw_u + 0.5);
+    let u = azimuth_u(p);
     let v = p.y.rem_euclid(1.0);
     (u, v)
 }
 
+// Wrap around a unit (double) cone: u from the angle around +y, as for the
+// cylinder, but v from the distance along the slanted wall from the apex (signed
+// by which nappe the point is on). On the cone's 45-degree wall that distance is
+// |y| * sqrt(2), so measuring along it keeps checker cells square rather than
+// stretched the way a plain `y` height would.
+pub fn conical_map(p: Point) -> (Number, Number) {
+    let u = azimuth_u(p);
+    let slant = (p.x * p.x + p.y * p.y + p.z * p.z).sqrt();
+    let v = if p.y < 0.0 { -slant } else { slant };
+    (u, v.rem_euclid(1.0))
+}
+
+// The color infinitely far away in direction `d` from an environment image:
+// `texels` is a latitude/longitude image `width` texels wide, stored row by row
+// from the top, wrapped around +y with the same (u, v) as `spherical_map` (so
+// the top row is straight up). Nearest texel; black for an empty image.
+pub fn environment_at(texels: &[Color], width: u32, d: Vector) -> Color {
+    let width = width as usize;
+    if width == 0 || texels.len() < width {
+        return black();
+    }
+    let height = texels.len() / width;
+    let (u, v) = spherical_map(Point {
+        x: d.x,
+        y: d.y,
+        z: d.z,
+    });
+    let col = ((u * width as Number) as usize).min(width - 1);
+    let row = (((1.0 - v) * height as Number) as usize).min(height - 1);
+    texels[row * width + col]
+}
+
+// Where one image of a flat texel buffer (`World::texels`) lies: `width` x
+// `height` colors stored row by row from the top, starting at `offset`. Kept
+// apart from the texels so materials can name an image by index and still
+// upload to the GPU as plain data.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageTexture {
+    pub offset: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageTexture {
+    // The texel of this image at (u, v) in `texels`, v = 0 the bottom row and
+    // v = 1 the top, like the UV patterns. Nearest texel, clamped at the
+    // edges; black for an empty image or one past the end of `texels`.
+    pub fn image_at(&self, texels: &[Color], u: Number, v: Number) -> Color {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let end = self.offset as usize + width * height;
+        if width == 0 || height == 0 || texels.len() < end {
+            return black();
+        }
+        let col = ((u.clamp(0.0, 1.0) * width as Number) as usize).min(width - 1);
+        let row = (((1.0 - v.clamp(0.0, 1.0)) * height as Number) as usize).min(height - 1);
+        texels[self.offset as usize + row * width + col]
+    }
+}
+
 // The six faces of a cube, picked by which coordinate of a point is largest.
 // repr(u32) so the discriminant isn't u8 (which rust-gpu needs Int8 for).
 #[repr(u32)]
@@ -234,6 +375,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn a_checker_on_a_sphere_has_no_extra_stripe_at_the_seam() {
+        let checkers = UvFace::checkers(16.0, 8.0, black(), white());
+        let on_sphere = |angle: Number| {
+            // `angle` around +y from -z, toward +x.
+            let p = Point { x: angle.sin(), y: 0.1, z: -angle.cos() };
+            let (u, v) = spherical_map(p);
+            assert!((0.0..1.0).contains(&u), "u = {u}");
+            checkers.uv_pattern_at(u, v)
+        };
+        let cell = 2.0 * PI / 16.0;
+        // Just either side of the seam the cells alternate like everywhere else.
+        let before = on_sphere(-0.01);
+        let after = on_sphere(0.01);
+        assert_ne!(before, after);
+        assert_eq!(before, on_sphere(-cell / 2.0));
+        assert_eq!(after, on_sphere(cell / 2.0));
+        // Right on it, whichever way atan2 rounds, is the cell after the seam.
+        for x in [0.0, -0.0] {
+            let (u, v) = spherical_map(Point { x, y: 0.1, z: -1.0 });
+            assert_eq!(u, 0.0);
+            assert_eq!(checkers.uv_pattern_at(u, v), after);
+        }
+    }
+
     #[test]
     fn using_a_planar_mapping_on_a_3d_point() {
         let cases = [
@@ -274,6 +440,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn using_a_conical_mapping_on_a_3d_point() {
+        // Points on the unit cone's wall (radius == |y|): u wraps around +y like
+        // the cylinder, v is the slant distance from the apex modulo 1.
+        let s = sqrt(2.0);
+        let cases = [
+            (Point { x: 0.0, y: 0.5, z: -0.5 }, 0.0, 0.5 * s),
+            (Point { x: 0.5, y: 0.5, z: 0.0 }, 0.25, 0.5 * s),
+            (Point { x: 0.0, y: 0.5, z: 0.5 }, 0.5, 0.5 * s),
+            (Point { x: -0.5, y: 0.5, z: 0.0 }, 0.75, 0.5 * s),
+            (Point { x: 0.0, y: 1.0, z: -1.0 }, 0.0, s - 1.0),
+            (Point { x: 0.0, y: -0.5, z: -0.5 }, 0.0, 1.0 - 0.5 * s),
+            (Point { x: 0.0, y: 0.0, z: 0.0 }, 0.5, 0.0),
+        ];
+        for (p, eu, ev) in cases {
+            let (u, v) = conical_map(p);
+            assert_almost_eq!(u, eu, 1e-4);
+            assert_almost_eq!(v, ev, 1e-4);
+        }
+    }
+
+    #[test]
+    fn uv_map_dispatches_on_the_mapping_tag() {
+        let p = Point { x: 0.5, y: 0.75, z: -0.5 };
+        assert_eq!(uv_map(p, MAPPING_CYLINDRICAL), cylindrical_map(p));
+        assert_eq!(uv_map(p, MAPPING_CONICAL), conical_map(p));
+        assert_eq!(uv_map(p, MAPPING_PLANAR), planar_map(p));
+        assert_eq!(uv_map(p, MAPPING_SPHERICAL), spherical_map(p));
+    }
+
+    #[test]
+    fn the_glyph_for_i_is_a_vertical_bar() {
+        let fg = Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        let bg = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        let face = UvFace::text("I", fg, bg);
+        // The 5x7 glyph sits one pixel in from each side of a 7x9 cell; pixel
+        // (col, row) from the glyph's top-left is centered at these (u, v).
+        let at = |col: usize, row: usize| {
+            let u = (col as Number + 1.5) / 7.0;
+            let v = 1.0 - (row as Number + 1.5) / 9.0;
+            face.uv_pattern_at(u, v)
+        };
+        for row in 0..7 {
+            assert_eq!(at(2, row), fg, "row {row}");
+        }
+        for row in 1..6 {
+            assert_eq!(at(0, row), bg, "row {row}");
+            assert_eq!(at(4, row), bg, "row {row}");
+        }
+        // The serifs top and bottom, and nothing in the margin.
+        assert_eq!(at(1, 0), fg);
+        assert_eq!(at(3, 6), fg);
+        assert_eq!(face.uv_pattern_at(0.5, 0.99), bg);
+        assert_eq!(face.uv_pattern_at(0.02, 0.5), bg);
+        // A second character takes the right half of the square.
+        let two = UvFace::text("-I", fg, bg);
+        assert_eq!(two.uv_pattern_at(0.25, 0.5), fg);
+        assert_eq!(two.uv_pattern_at(0.75, 1.0 - 1.5 / 9.0), fg);
+        assert_eq!(two.uv_pattern_at(0.25, 1.0 - 1.5 / 9.0), bg);
+    }
+
     #[test]
     fn layout_of_the_align_check_pattern() {
         let main = Color { r: 1.0, g: 1.0, b: 1.0 };
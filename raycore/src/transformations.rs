@@ -120,9 +120,69 @@ pub const fn shearing(
     m
 }
 
+// One step of a declarative transform list, e.g. `["rotate-y", 1.57]` from a
+// scene file. Angles are radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransformSpec {
+    Translate(Number, Number, Number),
+    Scale(Number, Number, Number),
+    RotateX(Number),
+    RotateY(Number),
+    RotateZ(Number),
+    Shear(Number, Number, Number, Number, Number, Number),
+}
+
+impl TransformSpec {
+    // Build a step from its name and numeric arguments; None for an unknown
+    // name or the wrong number of arguments.
+    pub fn parse(name: &str, args: &[Number]) -> Option<TransformSpec> {
+        Some(match (name, args) {
+            ("translate", &[x, y, z]) => TransformSpec::Translate(x, y, z),
+            ("scale", &[x, y, z]) => TransformSpec::Scale(x, y, z),
+            ("rotate-x", &[r]) => TransformSpec::RotateX(r),
+            ("rotate-y", &[r]) => TransformSpec::RotateY(r),
+            ("rotate-z", &[r]) => TransformSpec::RotateZ(r),
+            ("shear", &[xy, xz, yx, yz, zx, zy]) => TransformSpec::Shear(xy, xz, yx, yz, zx, zy),
+            _ => return None,
+        })
+    }
+    pub fn matrix(&self) -> Matrix<4, 4> {
+        match *self {
+            TransformSpec::Translate(x, y, z) => translation(x, y, z),
+            TransformSpec::Scale(x, y, z) => scaling(x, y, z),
+            TransformSpec::RotateX(r) => rotation_x(r),
+            TransformSpec::RotateY(r) => rotation_y(r),
+            TransformSpec::RotateZ(r) => rotation_z(r),
+            TransformSpec::Shear(xy, xz, yx, yz, zx, zy) => shearing(xy, xz, yx, yz, zx, zy),
+        }
+    }
+}
+
+// Fold the list into one matrix, first entry applied first, the same as
+// chaining each step with `.then`. An empty list is the identity.
+pub fn apply_transforms(list: &[TransformSpec]) -> Matrix<4, 4> {
+    list.iter()
+        .fold(Matrix::identity(), |m, spec| m.then(spec.matrix()))
+}
+
+// An `up` (anti)parallel to the view direction leaves no left vector to build
+// the camera basis from, so a camera looking straight up or down would get a
+// collapsed matrix. In that case the world axis least aligned with the view
+// direction stands in for `up`.
 pub fn view_transform(from: Point, to: Point, up: Vector) -> Matrix<4, 4> {
     let forwardv = (to - from).normalize();
-    let leftv = forwardv.cross(up.normalize());
+    let mut leftv = forwardv.cross(up.normalize());
+    if leftv.magnitude() < EPSILON {
+        let (fx, fy, fz) = (forwardv.x().abs(), forwardv.y().abs(), forwardv.z().abs());
+        let axis = if fx <= fy && fx <= fz {
+            [1.0, 0.0, 0.0]
+        } else if fy <= fz {
+            [0.0, 1.0, 0.0]
+        } else {
+            [0.0, 0.0, 1.0]
+        };
+        leftv = forwardv.cross(Vector::from(axis)).normalize();
+    }
     let true_up = leftv.cross(forwardv);
     let orientation = Matrix::new([
         [leftv.x(), leftv.y(), leftv.z(), 0.0],
@@ -133,10 +193,116 @@ pub fn view_transform(from: Point, to: Point, up: Vector) -> Matrix<4, 4> {
     orientation * translation(-from.x(), -from.y(), -from.z())
 }
 
+// Split an affine transform built as translation * rotation * scaling back into
+// (translation, rotation, scale). The 3x3 part is factored by Gram-Schmidt over
+// its columns (a QR decomposition): the orthonormal factor is the rotation and
+// the triangular factor's diagonal the scale. Its off-diagonal, the shear, is
+// dropped, so a sheared matrix comes back as the nearest unsheared one. A mirror
+// (negative determinant) gives a negative z scale, keeping the rotation proper.
+// None for a projective bottom row or a 3x3 part that collapses an axis.
+pub fn decompose(m: &Matrix<4, 4>) -> Option<(Vector, Matrix<3, 3>, Vector)> {
+    let affine = almost_eq(m.get(3, 0), 0.0)
+        && almost_eq(m.get(3, 1), 0.0)
+        && almost_eq(m.get(3, 2), 0.0)
+        && almost_eq(m.get(3, 3), 1.0);
+    if !affine {
+        return None;
+    }
+    let column = |col| Vector {
+        x: m.get(0, col),
+        y: m.get(1, col),
+        z: m.get(2, col),
+    };
+    let translation = column(3);
+    let a = column(0);
+    let sx = a.magnitude();
+    if sx < EPSILON {
+        return None;
+    }
+    let x = a / sx;
+    let b = column(1) - x * x.dot(column(1));
+    let sy = b.magnitude();
+    if sy < EPSILON {
+        return None;
+    }
+    let y = b / sy;
+    let c = column(2) - x * x.dot(column(2)) - y * y.dot(column(2));
+    let mut sz = c.magnitude();
+    if sz < EPSILON {
+        return None;
+    }
+    let mut z = c / sz;
+    if x.cross(y).dot(z) < 0.0 {
+        z = -z;
+        sz = -sz;
+    }
+    let rotation = Matrix::new([[x.x, y.x, z.x], [x.y, y.y, z.y], [x.z, y.z, z.z]]);
+    let scale = Vector {
+        x: sx,
+        y: sy,
+        z: sz,
+    };
+    Some((translation, rotation, scale))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
+    fn a_transform_list_folds_like_a_then_chain() {
+        let list = [
+            TransformSpec::Scale(2.0, 2.0, 2.0),
+            TransformSpec::Translate(0.0, 1.0, 0.0),
+        ];
+        assert_eq!(
+            apply_transforms(&list),
+            scaling(2.0, 2.0, 2.0).then(translation(0.0, 1.0, 0.0))
+        );
+        let parsed = [
+            TransformSpec::parse("scale", &[2.0, 2.0, 2.0]).unwrap(),
+            TransformSpec::parse("rotate-y", &[1.57]).unwrap(),
+            TransformSpec::parse("translate", &[0.0, 1.0, 0.0]).unwrap(),
+        ];
+        assert_eq!(
+            apply_transforms(&parsed),
+            scaling(2.0, 2.0, 2.0)
+                .then(rotation_y(1.57))
+                .then(translation(0.0, 1.0, 0.0))
+        );
+        assert_eq!(TransformSpec::parse("scale", &[2.0]), None);
+        assert_eq!(TransformSpec::parse("spin", &[1.0]), None);
+        assert_eq!(apply_transforms(&[]), Matrix::identity());
+    }
+    #[test]
+    fn decomposing_a_transform_recovers_its_parts() {
+        let m = translation(1.0, -2.0, 3.0) * rotation_z(PI / 3.0) * scaling(2.0, 3.0, 4.0);
+        let (t, r, s) = decompose(&m).unwrap();
+        assert_eq!(
+            t,
+            Vector {
+                x: 1.0,
+                y: -2.0,
+                z: 3.0
+            }
+        );
+        let rz = rotation_z(PI / 3.0);
+        let expected = Matrix::new([
+            [rz.get(0, 0), rz.get(0, 1), rz.get(0, 2)],
+            [rz.get(1, 0), rz.get(1, 1), rz.get(1, 2)],
+            [rz.get(2, 0), rz.get(2, 1), rz.get(2, 2)],
+        ]);
+        assert_eq!(r, expected);
+        assert_approx_eq!(
+            s,
+            Vector {
+                x: 2.0,
+                y: 3.0,
+                z: 4.0
+            }
+        );
+        assert_eq!(decompose(&scaling(1.0, 0.0, 1.0)), None);
+    }
+    #[test]
     fn multiplying_by_a_translation_matrix() {
         const TRANSFORM: Matrix<4, 4> = translation(5.0, -3.0, 2.0);
         let p = Point {
@@ -267,7 +433,7 @@ mod tests {
                 z: sqrt(2.0) / 2.0
             }
         );
-        assert_eq!(
+        assert_approx_eq!(
             FULL_QUARTER * p,
             Point {
                 x: 0.0,
@@ -285,7 +451,7 @@ mod tests {
         };
         const HALF_QUARTER: Matrix<4, 4> = rotation_x(PI / 4.0);
         let inv = inverse(&HALF_QUARTER).unwrap();
-        assert_eq!(
+        assert_approx_eq!(
             inv * p,
             Point {
                 x: 0.0,
@@ -311,7 +477,7 @@ mod tests {
                 z: sqrt(2.0) / 2.0
             }
         );
-        assert_eq!(
+        assert_approx_eq!(
             FULL_QUARTER * p,
             Point {
                 x: 1.0,
@@ -337,7 +503,7 @@ mod tests {
                 z: 0.0
             }
         );
-        assert_eq!(
+        assert_approx_eq!(
             FULL_QUARTER * p,
             Point {
                 x: -1.0,
@@ -459,7 +625,7 @@ mod tests {
         const B: Matrix<4, 4> = scaling(5.0, 5.0, 5.0);
         const C: Matrix<4, 4> = translation(10.0, 5.0, 7.0);
         let p2 = A * p;
-        assert_eq!(
+        assert_approx_eq!(
             p2,
             Point {
                 x: 1.0,
@@ -468,7 +634,7 @@ mod tests {
             }
         );
         let p3 = B * p2;
-        assert_eq!(
+        assert_approx_eq!(
             p3,
             Point {
                 x: 5.0,
@@ -477,7 +643,7 @@ mod tests {
             }
         );
         let p4 = C * p3;
-        assert_eq!(
+        assert_approx_eq!(
             p4,
             Point {
                 x: 15.0,
@@ -497,7 +663,7 @@ mod tests {
         const B: Matrix<4, 4> = scaling(5.0, 5.0, 5.0);
         const C: Matrix<4, 4> = translation(10.0, 5.0, 7.0);
         const T: Matrix<4, 4> = A.then(B).then(C);
-        assert_eq!(
+        assert_approx_eq!(
             T * p,
             Point {
                 x: 15.0,
@@ -518,7 +684,7 @@ mod tests {
             .then(rotation_x(PI / 2.0))
             .then(scaling(5.0, 5.0, 5.0))
             .then(translation(10.0, 5.0, 7.0));
-        assert_eq!(
+        assert_approx_eq!(
             T * p,
             Point {
                 x: 15.0,
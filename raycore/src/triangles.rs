@@ -47,9 +47,15 @@ pub fn triangle_normal_at(prim: &Primitive) -> Vector {
 // Blend the three vertex normals by the hit's barycentric weights. The result
 // is normalized when it is lifted into world space by `World::normal_to_world`.
 // Without a hit there is no u/v (both zero), so it falls back to the first
-// vertex normal.
+// vertex normal. Where the blend cancels out (vertex normals pointing opposite
+// ways, or missing) normalizing would give NaN, so the flat face normal stands
+// in.
 pub fn smooth_triangle_local_normal_at_uv(prim: &Primitive, u: Number, v: Number) -> Vector {
-    prim.n2 * u + prim.n3 * v + prim.n1 * (1.0 - u - v)
+    let blended = prim.n2 * u + prim.n3 * v + prim.n1 * (1.0 - u - v);
+    if blended.magnitude() < EPSILON {
+        return prim.normal;
+    }
+    blended
 }
 
 #[cfg(test)]
@@ -310,6 +316,43 @@ mod tests {
         assert_almost_eq!(n.z, 0.0);
     }
 
+    #[test]
+    fn a_smooth_triangle_in_a_scaled_group_keeps_its_u_v_to_the_hit() {
+        use crate::shapes::HasTransform;
+        use crate::transformations::scaling;
+        use crate::worlds::World;
+        let mut w = World::new();
+        let mut group = Primitive::group();
+        group.set_transform(scaling(2.0, 2.0, 2.0));
+        let g = w.add_object(group);
+        let t = w.add_child(g, example_smooth_triangle());
+        // The same local ray as above, seen from outside the doubled group.
+        let r = Ray {
+            origin: Point {
+                x: -0.4,
+                y: 0.6,
+                z: -4.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        let xs = w.intersect_world(&r);
+        assert_eq!(xs.count(), 1);
+        let hit = xs[0];
+        assert_eq!(hit.object_id, t);
+        assert_almost_eq!(hit.t, 4.0);
+        assert_almost_eq!(hit.u, 0.45);
+        assert_almost_eq!(hit.v, 0.25);
+        // A uniform scale leaves the interpolated normal's direction alone.
+        let comps = hit.prepare_computations(&r, &w.scene(), &xs);
+        assert_almost_eq!(comps.normalv.x, -0.5547);
+        assert_almost_eq!(comps.normalv.y, 0.83205);
+        assert_almost_eq!(comps.normalv.z, 0.0);
+    }
+
     #[test]
     fn preparing_the_normal_on_a_smooth_triangle() {
         use crate::worlds::World;
@@ -335,4 +378,52 @@ mod tests {
         assert_almost_eq!(comps.normalv.y, 0.83205);
         assert_almost_eq!(comps.normalv.z, 0.0);
     }
+
+    #[test]
+    fn a_degenerate_triangle_has_a_finite_unit_normal() {
+        use crate::worlds::World;
+        let a = Point::from([0.0, 0.0, 0.0]);
+        let b = Point::from([1.0, 1.0, 1.0]);
+        let c = Point::from([2.0, 2.0, 2.0]);
+        // Collinear corners, and a triangle collapsed to a point.
+        for t in [Primitive::triangle(a, b, c), Primitive::triangle(b, b, b)] {
+            assert!(t.is_degenerate());
+            let n = triangle_normal_at(&t);
+            assert!(n.x.is_finite() && n.y.is_finite() && n.z.is_finite());
+            assert_almost_eq!(n.magnitude(), 1.0);
+            let mut w = World::new();
+            w.add_object(t);
+            let n = w.normal_at(0, b);
+            assert!(!n.x.is_nan() && !n.y.is_nan() && !n.z.is_nan());
+        }
+        assert!(!example_triangle().is_degenerate());
+        // A tiny but well-shaped triangle is not degenerate.
+        let tiny = Primitive::triangle(
+            Point::from([0.0, 1e-4, 0.0]),
+            Point::from([-1e-4, 0.0, 0.0]),
+            Point::from([1e-4, 0.0, 0.0]),
+        );
+        assert!(!tiny.is_degenerate());
+        assert_approx_eq!(tiny.normal, example_triangle().normal);
+    }
+
+    #[test]
+    fn a_smooth_triangle_falls_back_to_its_face_normal_where_the_blend_cancels() {
+        use crate::worlds::World;
+        let up = Vector::from([0.0, 1.0, 0.0]);
+        let t = Primitive::smooth_triangle(
+            Point::from([0.0, 1.0, 0.0]),
+            Point::from([-1.0, 0.0, 0.0]),
+            Point::from([1.0, 0.0, 0.0]),
+            up,
+            -up,
+            up,
+        );
+        // Halfway between n1 and n2 they cancel exactly.
+        assert_eq!(smooth_triangle_local_normal_at_uv(&t, 0.5, 0.0), t.normal);
+        let mut w = World::new();
+        w.add_object(t);
+        let n = w.normal_at_uv(0, Point::from([-0.5, 0.5, 0.0]), 0.5, 0.0);
+        assert_approx_eq!(n, Vector::from([0.0, 0.0, -1.0]));
+    }
 }
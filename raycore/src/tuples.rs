@@ -78,6 +78,20 @@ macro_rules! assert_almost_eq {
 #[cfg(test)]
 pub(crate) use assert_almost_eq;
 
+// `assert_eq!` for computed points and vectors, whose `==` is exact: passes when
+// they agree to within EPSILON (`approx_eq`).
+#[cfg(test)]
+macro_rules! assert_approx_eq {
+    ($a: expr, $b: expr) => {
+        assert_approx_eq!($a, $b, "assert_approx_eq failed: {:?} != {:?}", $a, $b)
+    };
+    ($a: expr, $b: expr, $($arg: tt)+) => {
+        assert!($a.approx_eq(&$b, $crate::tuples::EPSILON), $($arg)+)
+    };
+}
+#[cfg(test)]
+pub(crate) use assert_approx_eq;
+
 pub trait Tuple {
     fn x(&self) -> Number;
     fn y(&self) -> Number;
@@ -111,14 +125,14 @@ pub trait Tuple {
     }
 }
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vector {
     pub x: Number,
     pub y: Number,
     pub z: Number,
 }
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Point {
     pub x: Number,
     pub y: Number,
@@ -340,6 +354,9 @@ impl Vector {
             z: self.z() / self.magnitude(),
         }
     }
+    // `dot` and `cross` are only defined for vectors, as in the book. Points are
+    // a separate type with neither method, so mixing them in is a compile error
+    // rather than a meaningless w-blind result.
     pub fn dot(self, other: Vector) -> Number {
         self.x() * other.x() + self.y() * other.y() + self.z() * other.z()
     }
@@ -350,25 +367,67 @@ impl Vector {
             z: self.x() * other.y() - self.y() * other.x(),
         }
     }
+    // Mirror this vector about `normal`, which must be unit length: a longer
+    // one overshoots by the square of its length. Use `reflect_safe` when it
+    // may not be (e.g. an interpolated normal).
     pub fn reflect(self, normal: Vector) -> Vector {
         self - (normal * (2.0 * self.dot(normal)))
     }
+    // `reflect` off `normal` normalized first.
+    pub fn reflect_safe(self, normal: Vector) -> Vector {
+        self.reflect(normal.normalize())
+    }
 }
 
-impl PartialEq for Point {
-    fn eq(&self, other: &Self) -> bool {
-        (self.x() - other.x()).abs() <= EPSILON
-            && (self.y() - other.y()).abs() <= EPSILON
-            && (self.z() - other.z()).abs() <= EPSILON
-            && (self.w() - other.w()).abs() <= EPSILON
+// `==` on points and vectors is exact, so it agrees with hashing and dedup;
+// geometry that has been through any arithmetic should be compared with
+// `approx_eq` instead.
+impl Point {
+    // Whether every coordinate is within `eps` of `other`'s (EPSILON is the
+    // usual choice).
+    pub fn approx_eq(&self, other: &Point, eps: Number) -> bool {
+        (self.x - other.x).abs() <= eps
+            && (self.y - other.y).abs() <= eps
+            && (self.z - other.z).abs() <= eps
     }
 }
-impl PartialEq for Vector {
-    fn eq(&self, other: &Self) -> bool {
-        (self.x() - other.x()).abs() <= EPSILON
-            && (self.y() - other.y()).abs() <= EPSILON
-            && (self.z() - other.z()).abs() <= EPSILON
-            && (self.w() - other.w()).abs() <= EPSILON
+impl Vector {
+    // Whether every component is within `eps` of `other`'s (EPSILON is the
+    // usual choice).
+    pub fn approx_eq(&self, other: &Vector, eps: Number) -> bool {
+        (self.x - other.x).abs() <= eps
+            && (self.y - other.y).abs() <= eps
+            && (self.z - other.z).abs() <= eps
+    }
+}
+
+// The default grid step of `QuantizedPoint`.
+pub const WELD_GRID: Number = 1e-5;
+
+// A point snapped to a cubic grid, as integer cell coordinates, so it can be
+// hashed and compared exactly: a `HashMap` key for welding vertices that
+// should be the same but picked up float noise. Points within half a step of
+// the same grid node share a key; two very close points on either side of a
+// cell boundary still don't, so pick a step well above the noise and well
+// below the spacing of distinct vertices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuantizedPoint {
+    x: i64,
+    y: i64,
+    z: i64,
+}
+
+impl QuantizedPoint {
+    pub fn new(p: Point) -> Self {
+        Self::with_grid(p, WELD_GRID)
+    }
+    pub fn with_grid(p: Point, grid: Number) -> Self {
+        let q = |v: Number| (v / grid).round() as i64;
+        QuantizedPoint {
+            x: q(p.x),
+            y: q(p.y),
+            z: q(p.z),
+        }
     }
 }
 
@@ -379,6 +438,27 @@ impl PartialEq for Color {
             && (self.b - other.b).abs() <= EPSILON
     }
 }
+
+// The sRGB transfer function (IEC 61966-2-1) for one linear channel: a short
+// linear toe up to 0.0031308, then a 1/2.4 power curve. Shading works in
+// linear light; this is only for encoding the result for a display, which is
+// why 8-bit output that skips it looks too dark. Values outside 0..=1 are left
+// for the caller to clamp.
+pub fn linear_to_srgb(v: Number) -> Number {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+// The inverse of `linear_to_srgb`: an sRGB-encoded channel back to linear light.
+pub fn srgb_to_linear(v: Number) -> Number {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
 impl Default for Point {
     fn default() -> Self {
         Point {
@@ -398,10 +478,67 @@ impl Default for Vector {
         }
     }
 }
+// Shorthand for scene code: `Point::from([1.0, 2.0, 3.0])`, `(x, y, z).into()`
+// and back to `[x, y, z]` with `.into()`.
+impl From<[Number; 3]> for Point {
+    fn from([x, y, z]: [Number; 3]) -> Self {
+        Point { x, y, z }
+    }
+}
+impl From<(Number, Number, Number)> for Point {
+    fn from((x, y, z): (Number, Number, Number)) -> Self {
+        Point { x, y, z }
+    }
+}
+impl From<Point> for [Number; 3] {
+    fn from(t: Point) -> Self {
+        [t.x, t.y, t.z]
+    }
+}
+impl From<[Number; 3]> for Vector {
+    fn from([x, y, z]: [Number; 3]) -> Self {
+        Vector { x, y, z }
+    }
+}
+impl From<(Number, Number, Number)> for Vector {
+    fn from((x, y, z): (Number, Number, Number)) -> Self {
+        Vector { x, y, z }
+    }
+}
+impl From<Vector> for [Number; 3] {
+    fn from(t: Vector) -> Self {
+        [t.x, t.y, t.z]
+    }
+}
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
+    fn points_convert_from_and_into_arrays_and_tuples() {
+        let literal = Point {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        assert_eq!(Point::from([1.0, 2.0, 3.0]), literal);
+        assert_eq!(Point::from((1.0, 2.0, 3.0)), literal);
+        let back: [Number; 3] = literal.into();
+        assert_eq!(back, [1.0, 2.0, 3.0]);
+    }
+    #[test]
+    fn vectors_convert_from_and_into_arrays_and_tuples() {
+        let literal = Vector {
+            x: -1.0,
+            y: 0.5,
+            z: 4.0,
+        };
+        assert_eq!(Vector::from([-1.0, 0.5, 4.0]), literal);
+        let from_tuple: Vector = (-1.0, 0.5, 4.0).into();
+        assert_eq!(from_tuple, literal);
+        let back: [Number; 3] = literal.into();
+        assert_eq!(Vector::from(back), literal);
+    }
+    #[test]
     fn a_tuple_with_w_1_is_a_point() {
         let tuple = Point {
             x: 4.3,
@@ -707,13 +844,30 @@ mod tests {
         );
     }
     #[test]
+    fn points_compare_exactly_with_eq_and_within_a_tolerance_with_approx_eq() {
+        let a = Point::from([1.0, 2.0, 3.0]);
+        let b = Point::from([1.0005, 2.0, 3.0]);
+        assert_ne!(a, b);
+        assert!(a.approx_eq(&b, 1e-3));
+        assert!(!a.approx_eq(&b, EPSILON));
+        // Even a difference within EPSILON is a difference to `==`.
+        let c = Point::from([1.0, 2.0, 3.000005]);
+        assert_ne!(a, c);
+        assert!(a.approx_eq(&c, EPSILON));
+        assert_eq!(a, Point::from([1.0, 2.0, 3.0]));
+        let v = Vector::from([0.0, 1.0, 0.0]);
+        let w = Vector::from([0.0, 1.000005, 0.0]);
+        assert_ne!(v, w);
+        assert!(v.approx_eq(&w, EPSILON));
+    }
+    #[test]
     fn normalizing_vector_1_2_3() {
         let v = Vector {
             x: 1.0,
             y: 2.0,
             z: 3.0,
         };
-        assert_eq!(
+        assert_approx_eq!(
             v.normalize(),
             Vector {
                 x: 0.26726,
@@ -810,7 +964,7 @@ mod tests {
             z: 0.0,
         };
         let r = v.reflect(n);
-        assert_eq!(
+        assert_approx_eq!(
             r,
             Vector {
                 x: 1.0,
@@ -819,4 +973,44 @@ mod tests {
             }
         );
     }
+    #[test]
+    fn reflect_safe_normalizes_a_long_normal() {
+        let v = Vector {
+            x: 0.0,
+            y: -1.0,
+            z: 0.5,
+        };
+        let n = Vector {
+            x: 1.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let unit = v.reflect(n.normalize());
+        let long = n.normalize() * 2.0;
+        assert_approx_eq!(v.reflect_safe(long), unit);
+        // Plain `reflect` is off once the normal is not unit length.
+        assert!(!v.reflect(long).approx_eq(&unit, EPSILON));
+    }
+    #[test]
+    fn nearby_points_share_a_quantized_key_and_distant_ones_do_not() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let hash = |p: Point| {
+            let mut h = DefaultHasher::new();
+            QuantizedPoint::new(p).hash(&mut h);
+            h.finish()
+        };
+        let p = Point::from([1.0, 2.0, 3.0]);
+        let noisy = Point::from([1.0 + 2e-6, 2.0 - 3e-6, 3.0]);
+        let apart = Point::from([1.0 + 5e-5, 2.0, 3.0]);
+        assert_eq!(QuantizedPoint::new(p), QuantizedPoint::new(noisy));
+        assert_eq!(hash(p), hash(noisy));
+        assert_ne!(QuantizedPoint::new(p), QuantizedPoint::new(apart));
+        assert_ne!(hash(p), hash(apart));
+        // A coarser grid welds them too.
+        assert_eq!(
+            QuantizedPoint::with_grid(p, 1e-3),
+            QuantizedPoint::with_grid(apart, 1e-3)
+        );
+    }
 }
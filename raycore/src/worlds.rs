@@ -1,27 +1,37 @@
 // Used only by the std-only World (scene building / bounds).
 #[cfg(feature = "std")]
-use crate::bounds::BoundingBox;
+use crate::bounds::{BoundingBox, BoundingSphere};
 use crate::csg::intersection_allowed;
 use crate::intersections::Computations;
 #[cfg(test)]
 use crate::intersections::Intersection;
 use crate::intersections::Intersections;
+#[cfg(feature = "std")]
+use crate::intersections::json_number;
 use crate::lights::*;
-use crate::materials::lightning;
+use crate::materials::{lightning_parts, material_color_at, Material};
 #[cfg(feature = "std")]
-use crate::materials::Material;
-use crate::matrices::transpose;
+use crate::materials::MaterialError;
+use crate::matrices::normal_matrix;
 // Matrix the type is only named by std-side code (World tests/helpers); the
 // no_std trace path uses inverse matrices by value without naming the type.
 #[cfg(feature = "std")]
-use crate::matrices::Matrix;
-#[cfg(test)]
+use crate::matrices::{is_invertible, Matrix};
+#[cfg(feature = "std")]
 use crate::patterns::*;
 use crate::rays::Ray;
 use crate::shapes::*;
 #[cfg(feature = "std")]
+use crate::spheres::tessellated_sphere;
+use crate::texture_maps::{environment_at, uv_map, ImageTexture};
+use crate::transformations::PI;
+#[cfg(feature = "std")]
 use crate::transformations::*;
 use crate::tuples::*;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::sync::Arc;
 
 // Bounded scratch sizes for the iterative (recursion-free, GPU-compatible)
 // traversal and shading. The traversal stack is height-bounded (it iterates a
@@ -33,6 +43,22 @@ const MAX_TREE_DEPTH: usize = 32;
 // Shading fans out to <= 2 rays (reflect + refract) per hit, depth-limited by
 // `remaining` (default 5), so 2^(5+1) is a safe ceiling.
 const MAX_SHADE_STACK: usize = 16;
+// The `ignore_object` passed to `intersect_world_ignoring` to ignore nothing.
+pub const NO_OBJECT: usize = usize::MAX;
+// Distinguish the reflected and refracted roulette draws at the same hit so the
+// two decisions are independent.
+const ROULETTE_REFLECT: u32 = 0x9e37_79b9;
+const ROULETTE_REFRACT: u32 = 0x85eb_ca6b;
+// Rays averaged per glossy reflection, and the salt for their jitter draws.
+const GLOSSY_SAMPLES: usize = 8;
+const GLOSSY_SALT: u32 = 0xc2b2_ae35;
+// The roulette never gives a ray worse odds than this, which caps the 1/p
+// boost a surviving ray gets (and with it the added noise).
+const ROULETTE_MIN_SURVIVAL: Number = 0.05;
+
+// Bounce budget `World::trace_ray_debug` shades with; the camera's default.
+#[cfg(feature = "std")]
+const DEBUG_TRACE_DEPTH: usize = 5;
 
 const ZERO_RAY: Ray = Ray {
     origin: Point {
@@ -78,11 +104,16 @@ impl Default for Frame {
 
 // One pending shading ray in the iterative `color_at`: its contribution is
 // `weight * surface_at(hit)`, and it may spawn weighted reflect/refract children.
+// `ignore` is the object it was spawned from when that can't be hit again
+// (`Scene::secondary_ignore`), else `NO_OBJECT`. `absorption` is that of the
+// medium the ray travels through (`Material::absorption`; zero in open air).
 #[derive(Clone, Copy)]
 struct ShadeJob {
     ray: Ray,
     remaining: usize,
     weight: Color,
+    ignore: usize,
+    absorption: Color,
 }
 impl Default for ShadeJob {
     fn default() -> Self {
@@ -94,6 +125,12 @@ impl Default for ShadeJob {
                 g: 0.0,
                 b: 0.0,
             },
+            ignore: NO_OBJECT,
+            absorption: Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            },
         }
     }
 }
@@ -106,6 +143,9 @@ impl Default for ShadeJob {
 pub struct World {
     pub objects: Vec<Primitive>,
     pub lights: Vec<Light>,
+    // User-defined lights (`LightSource`), shaded after the built-in `lights`
+    // by `color_at` and `shade_hit`. Host-only; see `add_custom_light`.
+    pub custom_lights: Vec<CustomLight>,
     // The logical children list of every object, indexed by object id (kept the
     // same length as `objects`). This is the host-side adjacency the build
     // helpers read and mutate; the heap-free flat projection the trace reads is
@@ -115,10 +155,149 @@ pub struct World {
     // `child_indices[objects[id].child_start .. + objects[id].child_count]`. The
     // trace reads this (never `children`), so a GPU buffer can be a plain slice.
     pub child_indices: Vec<usize>,
+    // The top-level objects that cast shadows (`Primitive::casts_shadow`), by
+    // id: the only ones shadow rays test. Rebuilt by `rebake` along with
+    // `child_indices`; `shadow_casters_of` is how many objects there were
+    // then, and if the arena has since grown or shrunk without a rebake
+    // (objects pushed straight onto `objects`), shadow rays go back to testing
+    // every top-level object.
+    pub shadow_casters: Vec<usize>,
+    pub shadow_casters_of: usize,
+    // Names given to objects with `set_name`, by object id, for logs and scene
+    // references. A side table because `Primitive` is uploaded to the GPU as
+    // plain data and can't hold a `String`.
+    pub names: HashMap<usize, String>,
     // When true, a group culls its children against its bounding box before
     // recursing. Always correct to leave on; exposed only so a scene can render
     // the same world with it off to measure the speedup.
     pub use_bounds: bool,
+    // Lights whose attenuated contribution at a hit (`Light::contribution_at`)
+    // falls below this are skipped when shading it. 0.0 (the default) keeps every
+    // light; raise it for scenes with many distant, falling-off lights.
+    pub light_threshold: Number,
+    // Seed for Russian-roulette termination of reflected/refracted rays
+    // (`roulette_factor`). 0 (the default) disables it and every secondary ray
+    // is traced to the full depth; any other value makes dim bounces stop early
+    // at random, reproducibly for the same seed. CPU only: the GPU shader
+    // builds its scene with 0 and traces every bounce.
+    pub roulette_seed: u32,
+    // The angle one pixel subtends at the camera (`Camera::pixel_spread`), for
+    // anti-aliasing patterns: a hit at distance t sees a footprint of about
+    // t * pixel_spread, widened at grazing angles. 0.0 (the default) samples
+    // each pattern once per hit, as before.
+    pub pixel_spread: Number,
+    // What rays that escape the scene see; black (the book's background) by
+    // default. See `Environment`.
+    pub environment: Environment,
+    // The images materials read their maps from (`Material::albedo_map` and
+    // co.), all in one flat texel buffer so the trace reads plain slices:
+    // image `id` is `images[id]`, which says where in `texels` it lies. Add
+    // them with `add_image`.
+    pub images: Vec<ImageTexture>,
+    pub texels: Vec<Color>,
+    // Atmospheric fog over the whole scene; None (the default) for clear air.
+    pub fog: Option<Fog>,
+    // Scales every material's ambient term, scene-wide: gray dims the ambient
+    // light everywhere, a tint colors it. White (the default) leaves it as is.
+    pub ambient_light: Color,
+}
+
+// A problem `World::validate` found in a scene.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SceneWarning {
+    // Object `id`'s transform can't be inverted (a zero scale, say), so rays
+    // can't be taken into its space and it never shows up.
+    SingularTransform(usize),
+    // Neither `lights` nor `custom_lights` has anything in it: every surface
+    // renders black.
+    NoLights,
+    // Object `id`'s material (or cap material) fails `Material::validate`, e.g.
+    // a refractive index below 1.
+    InvalidMaterial(usize, MaterialError),
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for SceneWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneWarning::SingularTransform(id) => {
+                write!(f, "object {id} has a non-invertible transform")
+            }
+            SceneWarning::NoLights => write!(f, "the scene has no lights"),
+            SceneWarning::InvalidMaterial(id, e) => write!(f, "object {id}: {e}"),
+        }
+    }
+}
+
+// Which ray of its parent a `TraceEvent` followed.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceKind {
+    Primary,
+    Reflection,
+    Refraction,
+}
+
+// One ray in `World::trace_with_log`'s recursion tree. `hit` is the object
+// struck and where, None for a miss. `surface`, `reflected` and `refracted`
+// are the terms `shade_hit` sums at the hit (the last two weighted as they
+// are added), `color` what the ray shades to after fading and fog.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEvent {
+    pub kind: TraceKind,
+    pub depth: usize,
+    // Index into `TraceLog::events` of the hit this ray left; None for the
+    // primary ray.
+    pub parent: Option<usize>,
+    pub hit: Option<(usize, Point)>,
+    pub surface: Color,
+    pub reflected: Color,
+    pub refracted: Color,
+    pub color: Color,
+}
+
+// The rays `World::trace_with_log` followed, parents before their children.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TraceLog {
+    pub events: Vec<TraceEvent>,
+}
+
+#[cfg(feature = "std")]
+impl TraceLog {
+    // The rays that bounced off event `index`'s hit, with their indices.
+    pub fn children(&self, index: usize) -> impl Iterator<Item = (usize, &TraceEvent)> {
+        self.events
+            .iter()
+            .enumerate()
+            .filter(move |(_, e)| e.parent == Some(index))
+    }
+}
+
+// The background a ray sees when it misses every object. `ImageSphere` wraps a
+// latitude/longitude image of `width` x (texels.len() / width) colors, row by
+// row from the top, around the whole scene at infinity, so camera rays show it
+// and reflective and refractive surfaces pick it up (image-based lighting for
+// reflections; direct lighting still comes from `lights` only). Texels may go
+// above 1.0 for a bright sky. Build one from a canvas with
+// `DynCanvas::to_environment`. `Solid` is one color in every direction and
+// `Gradient` blends from `bottom` straight down to `top` straight up.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Environment {
+    #[default]
+    Black,
+    Solid(Color),
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    ImageSphere {
+        width: usize,
+        texels: Vec<Color>,
+    },
 }
 
 // A borrowed, heap-free view of the parts of a `World` the ray trace and shading
@@ -132,7 +311,81 @@ pub struct Scene<'a> {
     pub objects: &'a [Primitive],
     pub lights: &'a [Light],
     pub child_indices: &'a [usize],
+    // `World::shadow_casters`, read only when `use_shadow_casters` is set;
+    // otherwise shadow rays test every top-level object that casts.
+    pub shadow_casters: &'a [usize],
+    pub use_shadow_casters: bool,
     pub use_bounds: bool,
+    pub light_threshold: Number,
+    pub roulette_seed: u32,
+    pub pixel_spread: Number,
+    // The environment image escaping rays sample (`environment_at`), as a flat
+    // texel slice and its width; empty when there is none.
+    pub environment: &'a [Color],
+    pub environment_width: u32,
+    // `World::images` and `World::texels`, for the material maps.
+    pub images: &'a [ImageTexture],
+    pub texels: &'a [Color],
+    // The solid or gradient background, used when there is no environment
+    // image; `Sky::BLACK` for the book's black.
+    pub sky: Sky,
+    // `World::fog`, flat: `Fog::NONE` when there is none.
+    pub fog: Fog,
+    // `World::ambient_light`.
+    pub ambient_light: Color,
+}
+
+// A vertical gradient background, flat for the GPU: `bottom` straight down,
+// `top` straight up, blended linearly in between. A solid color has
+// `bottom == top`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sky {
+    pub bottom: Color,
+    pub top: Color,
+}
+
+impl Sky {
+    pub const BLACK: Sky = Sky {
+        bottom: CLEAR,
+        top: CLEAR,
+    };
+    pub fn at(&self, direction: Vector) -> Color {
+        let up = direction.normalize().y;
+        self.bottom + (self.top - self.bottom) * ((up + 1.0) / 2.0)
+    }
+}
+
+// Exponential fog: a surface `t` along a ray keeps exp(-density * t) of its own
+// light (`visibility`) and the rest turns to `color`, so distant objects fade
+// into it and a ray that escapes the scene sees pure fog. Density 0 is clear.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub color: Color,
+    pub density: Number,
+}
+
+impl Fog {
+    pub const NONE: Fog = Fog {
+        color: CLEAR,
+        density: 0.0,
+    };
+    pub const fn new(color: Color, density: Number) -> Self {
+        Fog { color, density }
+    }
+    // The fraction of a surface's light that reaches the eye through `t` of fog.
+    pub fn visibility(&self, t: Number) -> Number {
+        if self.density <= 0.0 {
+            return 1.0;
+        }
+        (-self.density * t).exp()
+    }
+    // `color` seen from `t` away through this fog.
+    pub fn apply(&self, color: Color, t: Number) -> Color {
+        let v = self.visibility(t);
+        color * v + self.color * (1.0 - v)
+    }
 }
 
 #[cfg(feature = "std")]
@@ -141,9 +394,25 @@ impl World {
         Self {
             objects: vec![],
             lights: vec![],
+            custom_lights: vec![],
             children: vec![],
             child_indices: vec![],
+            shadow_casters: vec![],
+            shadow_casters_of: 0,
+            names: HashMap::new(),
             use_bounds: true,
+            light_threshold: 0.0,
+            roulette_seed: 0,
+            pixel_spread: 0.0,
+            environment: Environment::Black,
+            images: vec![],
+            texels: vec![],
+            fog: None,
+            ambient_light: Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
         }
     }
     // Rebuild the flat `child_indices` projection from the logical `children`
@@ -164,20 +433,81 @@ impl World {
                 self.objects[id].child_count = 0;
             }
         }
+        self.shadow_casters = (0..self.objects.len())
+            .filter(|&id| self.objects[id].parent().is_none() && self.objects[id].casts_shadow())
+            .collect();
+        self.shadow_casters_of = self.objects.len();
+    }
+    // Store an image `width` texels wide, `texels` row by row from the top,
+    // for materials to read as a map, and return its id for
+    // `Material::from_maps` (or `albedo_map` and co., plus one). A trailing
+    // partial row is dropped.
+    pub fn add_image(&mut self, width: usize, texels: &[Color]) -> u32 {
+        let height = texels.len().checked_div(width).unwrap_or(0);
+        self.images.push(ImageTexture {
+            offset: self.texels.len() as u32,
+            width: width as u32,
+            height: height as u32,
+        });
+        self.texels.extend_from_slice(&texels[..width * height]);
+        (self.images.len() - 1) as u32
     }
     // Build a borrowed `Scene` view over this world's slices. The trace/shading
     // methods live on `Scene`; the forwarders below call `self.scene().<same>()`.
     pub fn scene(&self) -> Scene {
+        let (environment, environment_width) = match &self.environment {
+            Environment::ImageSphere { width, texels } => (&texels[..], *width as u32),
+            _ => (&[][..], 0),
+        };
+        let sky = match self.environment {
+            Environment::Solid(color) => Sky {
+                bottom: color,
+                top: color,
+            },
+            Environment::Gradient { bottom, top } => Sky { bottom, top },
+            _ => Sky::BLACK,
+        };
         Scene {
             objects: &self.objects,
             lights: &self.lights,
             child_indices: &self.child_indices,
+            shadow_casters: &self.shadow_casters,
+            use_shadow_casters: self.shadow_casters_of == self.objects.len(),
             use_bounds: self.use_bounds,
+            light_threshold: self.light_threshold,
+            roulette_seed: self.roulette_seed,
+            pixel_spread: self.pixel_spread,
+            environment,
+            environment_width,
+            images: &self.images,
+            texels: &self.texels,
+            sky,
+            fog: self.fog.unwrap_or(Fog::NONE),
+            ambient_light: self.ambient_light,
         }
     }
     pub fn intersect_world(&self, ray: &Ray) -> Intersections {
         self.scene().intersect_world(ray)
     }
+    // `intersect_world`'s hits split up by the object hit, for tracking down
+    // which object an artifact comes from: one entry per object with any hits,
+    // in id order, each with its own hits in t order. Group and CSG nodes
+    // never appear, only the leaves under them, after CSG filtering.
+    pub fn intersect_by_object(&self, ray: &Ray) -> Vec<(usize, Intersections)> {
+        let xs = self.intersect_world(ray);
+        let mut ids: Vec<usize> = xs.xs[..xs.len].iter().map(|i| i.object_id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter()
+            .map(|id| {
+                let mut own = Intersections::empty();
+                for i in xs.xs[..xs.len].iter().filter(|i| i.object_id == id) {
+                    own.push(*i);
+                }
+                (id, own)
+            })
+            .collect()
+    }
     // Dispatch a ray to the arena object `id`. For a group, move the ray into
     // the group's space and recurse into its children. For a leaf, hand off to
     // the primitive's own `Primitive::intersect`, which applies the leaf's
@@ -222,7 +552,7 @@ impl World {
     }
     // Object `id`'s box expressed in its parent's space: its own-space box lifted
     // through its transform.
-    fn parent_space_bounds(&self, id: usize) -> BoundingBox {
+    pub fn parent_space_bounds(&self, id: usize) -> BoundingBox {
         self.object_bounds(id)
             .transform(self.objects[id].get_transform())
     }
@@ -256,6 +586,9 @@ impl World {
             let child_transform = self.objects[child].get_transform();
             bb.add_box(&child_bounds.transform(child_transform));
         }
+        if self.objects[id].cull_with_sphere != 0 {
+            self.objects[id].bounding_sphere = BoundingSphere::around(&self.extreme_points(id));
+        }
         let obj = &mut self.objects[id];
         match obj.kind {
             ShapeKind::Group | ShapeKind::Csg => obj.set_bounds(bb),
@@ -263,6 +596,29 @@ impl World {
         }
         bb
     }
+    // Points in `id`'s own space that its geometry never extends past, used to
+    // fit a bounding sphere: a triangle's vertices, any other leaf's box corners,
+    // and for a group or CSG node its children's points in its space.
+    fn extreme_points(&self, id: usize) -> Vec<Point> {
+        let obj = &self.objects[id];
+        let children: Vec<usize> = match obj.kind {
+            ShapeKind::Group => self.children[id].clone(),
+            ShapeKind::Csg => obj.left().into_iter().chain(obj.right()).collect(),
+            ShapeKind::Triangle | ShapeKind::SmoothTriangle => return vec![obj.p1, obj.p2, obj.p3],
+            _ => return obj.local_bounds().corners().to_vec(),
+        };
+        let mut points = vec![];
+        for child in children {
+            let transform = self.objects[child].get_transform();
+            points.extend(self.extreme_points(child).into_iter().map(|p| transform * p));
+        }
+        points
+    }
+    // Make group `id` cull rays against a bounding sphere instead of its box.
+    // Takes effect at the next `compute_bounds`.
+    pub fn use_bounding_sphere(&mut self, id: usize) {
+        self.objects[id].cull_with_sphere = 1;
+    }
     // Split the children of group `id` by which half of the group's box they fall
     // entirely within. Children straddling the divide stay on the group; the
     // returned (left, right) lists are removed from it (to be re-homed by
@@ -385,6 +741,51 @@ impl World {
     pub fn world_to_object(&self, id: usize, point: Point) -> Point {
         self.scene().world_to_object(id, point)
     }
+    // The full object-to-world transform of `id`: its own transform preceded by
+    // every enclosing group's, outermost first. `world_to_object` applies the
+    // inverse of this one link at a time; this composes it for exporting or
+    // inspecting a placed instance.
+    pub fn world_transform(&self, id: usize) -> Matrix<4, 4> {
+        let mut transform = self.objects[id].get_transform();
+        let mut cur = id;
+        while let Some(parent) = self.objects[cur].parent() {
+            transform = self.objects[parent].get_transform() * transform;
+            cur = parent;
+        }
+        transform
+    }
+    // Every leaf primitive under object `id` (just `id` itself if it is a leaf),
+    // in order, each with its full object-to-world transform. `parent` is the
+    // transform `id` sits in: the identity for a root object, otherwise its
+    // parent's `world_transform`. Recurses through groups and both sides of CSG
+    // nodes, for mesh export and primitive counts.
+    pub fn flatten(&self, id: usize, parent: Matrix<4, 4>) -> Vec<(Matrix<4, 4>, &Primitive)> {
+        let mut leaves = vec![];
+        self.flatten_into(id, parent, &mut leaves);
+        leaves
+    }
+    fn flatten_into<'a>(
+        &'a self,
+        id: usize,
+        parent: Matrix<4, 4>,
+        leaves: &mut Vec<(Matrix<4, 4>, &'a Primitive)>,
+    ) {
+        let object = &self.objects[id];
+        let transform = parent * object.get_transform();
+        match object.kind {
+            ShapeKind::Group => {
+                for &child in &self.children[id] {
+                    self.flatten_into(child, transform, leaves);
+                }
+            }
+            ShapeKind::Csg => {
+                for child in [object.left(), object.right()].into_iter().flatten() {
+                    self.flatten_into(child, transform, leaves);
+                }
+            }
+            _ => leaves.push((transform, object)),
+        }
+    }
     pub fn normal_at(&self, id: usize, world_point: Point) -> Vector {
         self.scene().normal_at(id, world_point)
     }
@@ -399,6 +800,46 @@ impl World {
         self.rebake();
         id
     }
+    // Let object `id` cast shadows or not (see `Primitive::set_casts_shadow`)
+    // and refresh `shadow_casters`.
+    pub fn set_casts_shadow(&mut self, id: usize, casts: bool) {
+        self.objects[id].set_casts_shadow(casts);
+        self.rebake();
+    }
+    // Name object `id`, replacing any name it had.
+    pub fn set_name(&mut self, id: usize, name: impl Into<String>) {
+        self.names.insert(id, name.into());
+    }
+    pub fn name(&self, id: usize) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+    // The id of the object called `name`; the lowest if several share it.
+    pub fn object_by_name(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .filter(|(_, n)| n.as_str() == name)
+            .map(|(&id, _)| id)
+            .min()
+    }
+    // The arena as a mutable slice, for editing objects (materials, transforms)
+    // in place. It can't grow or shrink, so the hierarchy stays consistent;
+    // structural changes go through `add_object`/`add_child`.
+    pub fn objects_mut(&mut self) -> &mut [Primitive] {
+        &mut self.objects
+    }
+    // Scale every light in `lights` by `factor` (see `Light::scaled`). Custom
+    // lights compute their own color and are left alone.
+    pub fn dim_lights(&mut self, factor: Number) {
+        for light in &mut self.lights {
+            *light = light.scaled(factor);
+        }
+    }
+    // Apply `f` to every object's material in place.
+    pub fn each_material(&mut self, mut f: impl FnMut(&mut Material)) {
+        for object in self.objects_mut() {
+            f(&mut object.material);
+        }
+    }
     // Append `child` and attach it to the group at `group_id`: set the child's
     // parent and record its id in the group's children. Mirrors the book's
     // Group::add_child.
@@ -413,6 +854,53 @@ impl World {
         self.rebake();
         id
     }
+    // `Material::validate` every object's material (and cap material, if it has
+    // one), and check its image maps are in `images` (`Material::validate_maps`),
+    // returning the first object id whose material fails along with the reason.
+    pub fn validate_materials(&self) -> Result<(), (usize, MaterialError)> {
+        for (id, object) in self.objects.iter().enumerate() {
+            let cap = object.cap_material();
+            for material in [object.material_ref()].into_iter().chain(cap) {
+                material.validate().map_err(|e| (id, e))?;
+                material
+                    .validate_maps(self.images.len())
+                    .map_err(|e| (id, e))?;
+            }
+        }
+        Ok(())
+    }
+    // Pre-render sanity check: every likely mistake in the scene, not just the
+    // first. Nothing here stops a render; each warning names something that
+    // renders wrong or not at all (see `SceneWarning`).
+    pub fn validate(&self) -> Vec<SceneWarning> {
+        let mut warnings = vec![];
+        if self.lights.is_empty() && self.custom_lights.is_empty() {
+            warnings.push(SceneWarning::NoLights);
+        }
+        for (id, object) in self.objects.iter().enumerate() {
+            if !is_invertible(&object.get_transform()) {
+                warnings.push(SceneWarning::SingularTransform(id));
+            }
+            let cap = object.cap_material();
+            for material in [object.material_ref()].into_iter().chain(cap) {
+                let images = self.images.len();
+                if let Err(e) = material.validate().and(material.validate_maps(images)) {
+                    warnings.push(SceneWarning::InvalidMaterial(id, e));
+                }
+            }
+        }
+        warnings
+    }
+    // Add `tessellated_sphere(lat, lon)` as a new top-level group of smooth
+    // triangles and return the group's arena id. Like an OBJ model, call
+    // `compute_bounds()` afterwards so the triangles get culled.
+    pub fn add_tessellated_sphere(&mut self, lat: usize, lon: usize) -> usize {
+        let group = self.add_object(Primitive::group());
+        for triangle in tessellated_sphere(lat, lon) {
+            self.add_child(group, triangle);
+        }
+        group
+    }
     // Attach the two children of a CSG node. Each must already be in the arena
     // (added after the CSG node, so its id is higher, which keeps the reverse-id
     // ordering `compute_bounds` depends on). The book's `csg(op, left, right)`
@@ -426,14 +914,140 @@ impl World {
         }
         self.rebake();
     }
+    // Add `shape` cut open by the plane through `point` facing `normal`: a CSG
+    // intersection of the shape with a half-space, keeping only the part on
+    // the side `normal` points away from. The cut face takes the shape's
+    // material and looks along `normal`. Returns the CSG node's id; the shape
+    // and half-space are its children, added after it as `set_csg_children`
+    // requires.
+    pub fn add_clipped(&mut self, shape: Primitive, point: Point, normal: Vector) -> usize {
+        let n = normal.normalize();
+        let helper = if n.x().abs() < 0.9 {
+            Vector::from([1.0, 0.0, 0.0])
+        } else {
+            Vector::from([0.0, 0.0, 1.0])
+        };
+        let t = helper.cross(n).normalize();
+        let b = t.cross(n);
+        let mut half = Primitive::half_space();
+        // Local x, y, z onto t, n, b, and the local origin onto `point`.
+        half.set_transform(Matrix::new([
+            [t.x(), n.x(), b.x(), point.x()],
+            [t.y(), n.y(), b.y(), point.y()],
+            [t.z(), n.z(), b.z(), point.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ]));
+        half.set_material(shape.material_ref().clone());
+        let csg = self.add_object(Primitive::csg(crate::csg::CsgOperation::Intersection));
+        let left = self.add_object(shape);
+        let right = self.add_object(half);
+        self.set_csg_children(csg, left, right);
+        csg
+    }
     // The direct (local) surface color at a hit: the Phong contribution of every
     // light, shadow-tested independently, with no reflection/refraction. Shared
-    // by `shade_hit` and the iterative `color_at` so the two stay in lockstep.
+    // by `shade_hit` and the iterative `color_at` so the two stay in lockstep,
+    // custom lights included at this hit and every hit its bounces make.
     pub fn shade_hit(&self, comps: Computations, remaining: usize) -> Color {
-        self.scene().shade_hit(comps, remaining)
+        let custom = |c: &Computations| self.custom_light_color(c);
+        self.scene().shade_hit_with(comps, remaining, &custom)
     }
     pub fn color_at(&self, ray: &Ray, remaining: usize) -> Color {
-        self.scene().color_at(ray, remaining)
+        self.color_at_clipped(ray, remaining, 0.0, Number::INFINITY)
+    }
+    pub fn color_at_clipped(
+        &self,
+        ray: &Ray,
+        remaining: usize,
+        near: Number,
+        far: Number,
+    ) -> Color {
+        let scene = self.scene();
+        if self.custom_lights.is_empty() {
+            return scene.color_at_clipped(ray, remaining, near, far);
+        }
+        scene.trace(ray, remaining, NO_OBJECT, CLEAR, (near, far), &|comps: &Computations| {
+            self.custom_light_color(comps)
+        })
+    }
+    // Register a user-defined light; it is shaded from then on alongside
+    // `lights`.
+    pub fn add_custom_light(&mut self, light: impl LightSource + 'static) {
+        self.custom_lights.push(CustomLight(Arc::new(light)));
+    }
+    // Make object `id`'s emissive material light the rest of the scene: an area
+    // light of its own shape, sampled at the centers of a `usteps` x `vsteps`
+    // grid over `Primitive::sample_point`'s (u, v). Each sample becomes a point
+    // light carrying a share of the emission in proportion to the surface area
+    // it stands for (so a flattened cube's thin sides get little of it), nudged
+    // off the surface along its normal so the emitter doesn't shadow its own
+    // samples; the usual per-light shadow rays give soft shadows. The samples
+    // are taken now, so move the object before calling this, not after.
+    // Returns how many lights were added: none for a shape `sample_point`
+    // can't sample or a material with no emission.
+    pub fn add_emissive_light(&mut self, id: usize, usteps: usize, vsteps: usize) -> usize {
+        let object = &self.objects[id];
+        let emissive = object.material_ref().emissive;
+        let count = usteps * vsteps;
+        if count == 0 || max_channel(emissive) <= 0.0 {
+            return 0;
+        }
+        let groups = match object.parent() {
+            Some(parent) => self.world_transform(parent),
+            None => Matrix::identity(),
+        };
+        // A linear map M scales area at a surface point by |det M| * |M^-T n|
+        // for the local unit normal n; the determinant is common to every
+        // sample, so |M^-T n| alone weighs them.
+        let inverse = object.get_inverse_transform();
+        let mut samples = Vec::with_capacity(count);
+        let mut total_weight = 0.0;
+        for j in 0..vsteps {
+            for i in 0..usteps {
+                let u = (i as Number + 0.5) / usteps as Number;
+                let v = (j as Number + 0.5) / vsteps as Number;
+                let Some((point, normal)) = object.sample_point(u, v) else {
+                    return 0;
+                };
+                let local_normal = object.local_normal_at(&(inverse * point));
+                let weight = (normal_matrix(&inverse) * local_normal).magnitude();
+                total_weight += weight;
+                samples.push((groups * (point + normal * EPSILON), weight));
+            }
+        }
+        for (position, weight) in samples {
+            let share = emissive * (weight / total_weight);
+            self.lights.push(Light::point_light(position, share));
+        }
+        count
+    }
+    // The direct light the `custom_lights` add at a hit: each is shaded like a
+    // point light at its position, scaled by its own `intensity_at`.
+    fn custom_light_color(&self, comps: &Computations) -> Color {
+        let scene = self.scene();
+        let material = scene.material_at(comps);
+        let color = scene.albedo_at(comps);
+        let mut total = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        for custom in &self.custom_lights {
+            let source = &custom.0;
+            let light = Light::point_light(source.position(), source.intensity());
+            let intensity = source.intensity_at(comps.over_point, &scene);
+            let (ambient, direct) = lightning_parts(
+                material,
+                color,
+                light,
+                comps.point,
+                comps.eyev,
+                comps.normalv,
+                intensity,
+            );
+            total = total + ambient * scene.ambient_light + direct;
+        }
+        total
     }
     pub fn is_shadowed(&self, point: Point, light: &Light) -> bool {
         self.scene().is_shadowed(point, light)
@@ -444,11 +1058,336 @@ impl World {
     pub fn intensity_at(&self, point: Point, light: &Light) -> Number {
         self.scene().intensity_at(point, light)
     }
+    // The shadow term alone, for debugging shadows apart from shading: how
+    // much of the lights reach the first surface `ray` hits, as the mean of
+    // `intensity_at` over every light (1.0 fully lit, 0.0 in shadow from all,
+    // in between for a penumbra or some lights blocked). Color, falloff and
+    // the angle to the light are all ignored. 0.0 if the ray hits nothing or
+    // there are no lights.
+    pub fn shadow_at(&self, ray: &Ray) -> Number {
+        let xs = self.intersect_world(ray);
+        let Some(hit) = xs.hit() else {
+            return 0.0;
+        };
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let comps = hit.prepare_computations(ray, &self.scene(), &xs);
+        let scene = self.scene();
+        let lit: Number = self
+            .lights
+            .iter()
+            .map(|light| scene.intensity_at(comps.over_point, light))
+            .sum();
+        lit / self.lights.len() as Number
+    }
+    // What `ray` sees if it misses everything: `environment` evaluated along
+    // its direction, before any fog.
+    pub fn background_at(&self, ray: &Ray) -> Color {
+        self.scene().background_at(ray)
+    }
+    // Where each of `lights` sits and how big it is (`Light::extent`), in order,
+    // for drawing markers over a render while placing lights. Custom lights
+    // have no position and are left out.
+    pub fn light_gizmos(&self) -> Vec<(Point, Number)> {
+        self.lights
+            .iter()
+            .map(|light| (light.position(), light.extent()))
+            .collect()
+    }
+    // The lights worth shading at `point`: those whose attenuated contribution
+    // reaches `light_threshold`. `shade_hit` applies the same filter per light.
+    pub fn significant_lights(&self, point: Point) -> Vec<&Light> {
+        let scene = self.scene();
+        self.lights
+            .iter()
+            .filter(|light| scene.is_significant(light, point))
+            .collect()
+    }
     pub fn reflected_color(&self, comps: &Computations, remaining: usize) -> Color {
-        self.scene().reflected_color(comps, remaining)
+        let custom = |c: &Computations| self.custom_light_color(c);
+        self.scene().reflected_color_with(comps, remaining, &custom)
+    }
+    // A JSON diagnostic of one ray: every intersection (sorted, as
+    // `Intersections::to_debug_json`), the hit (or null) and the color the ray
+    // shades to with the camera's default bounce budget.
+    pub fn trace_ray_debug(&self, ray: &Ray) -> String {
+        let xs = self.intersect_world(ray);
+        let hit = match xs.hit() {
+            Some(hit) => format!(
+                "{{\"t\":{},\"object_id\":{}}}",
+                json_number(hit.t),
+                hit.object_id
+            ),
+            None => "null".to_string(),
+        };
+        let color = self.color_at(ray, DEBUG_TRACE_DEPTH);
+        format!(
+            "{{\"intersections\":{},\"hit\":{},\"color\":{{\"r\":{},\"g\":{},\"b\":{}}}}}",
+            xs.to_debug_json(),
+            hit,
+            json_number(color.r),
+            json_number(color.g),
+            json_number(color.b)
+        )
     }
     pub fn refracted_color(&self, comps: &Computations, remaining: usize) -> Color {
-        self.scene().refracted_color(comps, remaining)
+        let custom = |c: &Computations| self.custom_light_color(c);
+        self.scene().refracted_color_with(comps, remaining, &custom)
+    }
+    // `color_at` along with the tree of rays behind it, for working out why a
+    // pixel looks wrong. The log follows the mirror reflection and the
+    // refraction out of every hit that has them; a glossy reflection's
+    // jittered rays show only in the `reflected` total.
+    pub fn trace_with_log(&self, ray: &Ray, remaining: usize) -> (Color, TraceLog) {
+        let mut log = TraceLog::default();
+        self.log_ray(
+            ray,
+            remaining,
+            TraceKind::Primary,
+            None,
+            NO_OBJECT,
+            &mut log,
+        );
+        (self.color_at(ray, remaining), log)
+    }
+    fn log_ray(
+        &self,
+        ray: &Ray,
+        remaining: usize,
+        kind: TraceKind,
+        parent: Option<usize>,
+        ignore: usize,
+        log: &mut TraceLog,
+    ) {
+        let scene = self.scene();
+        let depth = parent.map_or(0, |p| log.events[p].depth + 1);
+        let xs = scene.intersect_world_ignoring(ray, ignore);
+        let Some(hit) = xs.hit() else {
+            let background = scene.fog.apply(scene.background_at(ray), Number::INFINITY);
+            log.events.push(TraceEvent {
+                kind,
+                depth,
+                parent,
+                hit: None,
+                surface: CLEAR,
+                reflected: CLEAR,
+                refracted: CLEAR,
+                color: background,
+            });
+            return;
+        };
+        let comps = hit.prepare_computations(ray, &scene, &xs);
+        let custom = |c: &Computations| self.custom_light_color(c);
+        let (surface, reflected, refracted) = scene.shade_parts(&comps, remaining, &custom);
+        let index = log.events.len();
+        log.events.push(TraceEvent {
+            kind,
+            depth,
+            parent,
+            hit: Some((comps.object_id, comps.point)),
+            surface,
+            reflected,
+            refracted,
+            color: scene.finish_hit(&comps, surface + reflected + refracted),
+        });
+        if remaining == 0 {
+            return;
+        }
+        let material = scene.material_at(&comps);
+        if scene.metalness_at(&comps) > 0.0 {
+            let bounce = Ray {
+                origin: comps.over_point,
+                direction: comps.reflectv,
+            };
+            let ignore = scene.secondary_ignore(&comps, true);
+            self.log_ray(
+                &bounce,
+                remaining - 1,
+                TraceKind::Reflection,
+                Some(index),
+                ignore,
+                log,
+            );
+        }
+        if material.transparency > 0.0 {
+            if let Some(direction) = comps.refracted_direction() {
+                let bounce = Ray {
+                    origin: comps.under_point,
+                    direction,
+                };
+                let ignore = scene.secondary_ignore(&comps, false);
+                self.log_ray(
+                    &bounce,
+                    remaining - 1,
+                    TraceKind::Refraction,
+                    Some(index),
+                    ignore,
+                    log,
+                );
+            }
+        }
+    }
+}
+
+// The `trace` clip range that keeps every hit in front of the ray.
+const UNCLIPPED: (Number, Number) = (0.0, Number::INFINITY);
+
+// The absorption of open air: none.
+const CLEAR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+};
+
+// Beer's law: the fraction of each channel left after `distance` through a
+// medium with `absorption`. 1.0 everywhere for a clear medium.
+fn transmittance(absorption: Color, distance: Number) -> Color {
+    Color {
+        r: (-absorption.r * distance).exp(),
+        g: (-absorption.g * distance).exp(),
+        b: (-absorption.b * distance).exp(),
+    }
+}
+
+// The medium a bounce off `comps` travels through: inside the object it hit
+// (its `absorption`) for a refraction into it or a reflection off its inside,
+// else open air. Media aren't nested, so light leaving one object travels
+// clear even if it's inside another.
+fn medium_after(comps: &Computations, material: &Material, reflected: bool) -> Color {
+    if reflected == comps.inside {
+        material.absorption
+    } else {
+        CLEAR
+    }
+}
+
+// The share of `material`'s reflection an opaque Fresnel surface sends back at
+// this hit: the Schlick reflectance when `fresnel_reflective` is set, else 1.
+// A transparent material gets 1 too, since `shade_hit` already splits its
+// reflection and refraction by Schlick.
+fn fresnel_weight(comps: &Computations, material: &Material) -> Number {
+    if material.fresnel_reflective != 0 && material.transparency == 0.0 {
+        comps.schlick()
+    } else {
+        1.0
+    }
+}
+
+// The shares of a hit's color that its reflection and its refraction carry:
+// split by Schlick when the surface does both, else `reflective` (less any
+// `fresnel_weight`) and the material's transparency whole. `trace` weights its
+// child rays by these, and it and the recursive `reflected_color` and
+// `refracted_color` use them as the roulette's survival odds, so a seed cuts
+// the same rays either way.
+fn bounce_weights(
+    comps: &Computations,
+    material: &Material,
+    reflective: Number,
+) -> (Number, Number) {
+    let transparency = material.transparency;
+    if reflective > 0.0 && transparency > 0.0 {
+        let reflectance = comps.schlick();
+        (reflective * reflectance, transparency * (1.0 - reflectance))
+    } else {
+        (reflective * fresnel_weight(comps, material), transparency)
+    }
+}
+
+// The `trace` extra term for a scene with only built-in lights.
+fn no_extra_light(_: &Computations) -> Color {
+    Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+    }
+}
+
+// Russian roulette for a secondary ray whose contribution to the pixel is at
+// most `survival`: it survives with probability p = `survival` (clamped to
+// [ROULETTE_MIN_SURVIVAL, 1]) and then returns 1/p, else 0.0. Scaling the
+// survivors by 1/p keeps the expected color unchanged, so the early exit is
+// unbiased; a ray that can still carry full weight (p = 1) always survives.
+// The draw is a hash of `seed`, `point` and `remaining`, so the same seed
+// makes the same decisions on every run, with no RNG state to share.
+pub fn roulette_factor(seed: u32, point: Point, remaining: usize, survival: Number) -> Number {
+    let p = survival.clamp(ROULETTE_MIN_SURVIVAL, 1.0);
+    if p >= 1.0 {
+        return 1.0;
+    }
+    let mut h = seed;
+    h = hash_u32(h ^ point.x().to_bits());
+    h = hash_u32(h ^ point.y().to_bits());
+    h = hash_u32(h ^ point.z().to_bits());
+    h = hash_u32(h ^ remaining as u32);
+    // The top 24 bits as a uniform draw in [0, 1).
+    let draw = (h >> 8) as Number / (1u32 << 24) as Number;
+    if draw < p {
+        1.0 / p
+    } else {
+        0.0
+    }
+}
+
+// The largest channel of an accumulated ray weight: the most it can add to any
+// channel of the pixel.
+fn max_channel(c: Color) -> Number {
+    c.r.max(c.g).max(c.b)
+}
+
+// A 32-bit integer finalizer (lowbias32): every input bit affects every output
+// bit, so nearby points give unrelated draws.
+fn hash_u32(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+// The `sample`th glossy reflection direction at `point`: the mirror direction
+// `reflectv` tilted by up to `glossiness` across the disk perpendicular to it.
+// The draw is hashed from the point and sample index like `roulette_factor`,
+// so a re-render gives the same blur. A tilt that would dip below the surface
+// falls back to the mirror direction.
+fn glossy_direction(
+    reflectv: Vector,
+    normalv: Vector,
+    glossiness: Number,
+    point: Point,
+    sample: usize,
+) -> Vector {
+    let mut h = GLOSSY_SALT ^ sample as u32;
+    h = hash_u32(h ^ point.x().to_bits());
+    h = hash_u32(h ^ point.y().to_bits());
+    h = hash_u32(h ^ point.z().to_bits());
+    let u = (h >> 8) as Number / (1u32 << 24) as Number;
+    let v = (hash_u32(h) >> 8) as Number / (1u32 << 24) as Number;
+
+    let r = reflectv.normalize();
+    let helper = if r.x.abs() < 0.9 {
+        Vector {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    } else {
+        Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        }
+    };
+    let t1 = r.cross(helper).normalize();
+    let t2 = r.cross(t1);
+    let radius = glossiness * u.sqrt();
+    let angle = 2.0 * PI * v;
+    let direction = (r + t1 * (radius * angle.cos()) + t2 * (radius * angle.sin())).normalize();
+    if direction.dot(normalv) > 0.0 {
+        direction
+    } else {
+        r
     }
 }
 
@@ -458,6 +1397,14 @@ impl World {
 // `std`. The `World` methods above forward here.
 impl<'a> Scene<'a> {
     pub fn intersect_world(&self, ray: &Ray) -> Intersections {
+        self.intersect_world_ignoring(ray, NO_OBJECT)
+    }
+    // `intersect_world` without any hits on `ignore_object` (`NO_OBJECT` keeps
+    // them all). Secondary rays pass the object they were spawned from when
+    // it cannot be in their way (`secondary_ignore`), so a ray grazing its own
+    // surface can't re-hit it through floating-point error even where
+    // `over_point`'s fixed offset is not enough.
+    pub fn intersect_world_ignoring(&self, ray: &Ray, ignore_object: usize) -> Intersections {
         let mut intersections = Intersections::empty();
         // Only roots are traversed here; children are reached by intersect_object,
         // so a child must not be intersected a second time. Index loop (not
@@ -471,13 +1418,33 @@ impl<'a> Scene<'a> {
             }
             id += 1;
         }
+        if ignore_object != NO_OBJECT {
+            intersections.remove_object(ignore_object);
+        }
         // Sort once, here, now that every root has contributed. `color_at` and the
         // tests rely on `intersect_world` returning hits in t-order.
         intersections.sort();
         intersections
     }
+    // The object a secondary ray from `comps` may ignore: the one it leaves,
+    // if that is convex and the ray heads away from it. That is a reflection
+    // off its outside or a refraction out of its inside; the other two (a
+    // reflection inside glass, a refraction into it) must hit it again.
+    fn secondary_ignore(&self, comps: &Computations, reflected: bool) -> usize {
+        let leaving = reflected != comps.inside;
+        if leaving && self.objects[comps.object_id].is_convex() {
+            comps.object_id
+        } else {
+            NO_OBJECT
+        }
+    }
     pub fn intersect_object(&self, id: usize, ray: &Ray) -> Intersections {
         let mut out = Intersections::empty();
+        self.intersect_object_into(id, ray, &mut out);
+        out
+    }
+    // `intersect_object`, appending to `out` so a caller can reuse one buffer.
+    fn intersect_object_into(&self, id: usize, ray: &Ray, out: &mut Intersections) {
         let mut stack = [Frame::default(); MAX_TRAVERSAL_STACK];
         let mut sp = 0usize;
         stack[sp] = Frame {
@@ -500,7 +1467,7 @@ impl<'a> Scene<'a> {
                             // Read the bounds fields directly (not Option<BoundingBox>,
                             // which rust-gpu can't lower).
                             if self.use_bounds && object.has_bounds != 0 {
-                                if !object.bounds.intersects(&local_ray) {
+                                if !object.bounds_admit(&local_ray) {
                                     continue;
                                 }
                             }
@@ -537,7 +1504,7 @@ impl<'a> Scene<'a> {
                             };
                             sp += 1;
                         }
-                        _ => object.intersect_into(&f.ray, f.id, &mut out),
+                        _ => object.intersect_into(&f.ray, f.id, out),
                     }
                 }
                 F_GROUP => {
@@ -580,11 +1547,10 @@ impl<'a> Scene<'a> {
                     };
                     sp += 1;
                 }
-                _ => self.filter_region(f.id, &mut out, f.next),
+                _ => self.filter_region(f.id, out, f.next),
             }
             debug_assert!(sp <= MAX_TRAVERSAL_STACK, "traversal stack overflow");
         }
-        out
     }
     fn filter_region(&self, csg_id: usize, out: &mut Intersections, start: usize) {
         let csg = &self.objects[csg_id];
@@ -681,12 +1647,14 @@ impl<'a> Scene<'a> {
         }
         p
     }
+    // Lift an object-space normal through every transform up the parent chain.
+    // Mirrored (negative-determinant) links need no flip; see `Primitive::normal_at`.
     fn normal_to_world(&self, id: usize, normal: Vector) -> Vector {
         let mut normal = normal;
         let mut cur = id;
         loop {
             let inverse = self.objects[cur].get_inverse_transform();
-            normal = (transpose(&inverse) * normal).normalize();
+            normal = (normal_matrix(&inverse) * normal).normalize();
             match self.objects[cur].parent() {
                 Some(parent) => cur = parent,
                 None => break,
@@ -702,52 +1670,224 @@ impl<'a> Scene<'a> {
         let local_normal = self.objects[id].local_normal_at_uv(&local_point, u, v);
         self.normal_to_world(id, local_normal)
     }
-    fn surface_at(&self, comps: &Computations) -> Color {
+    // The material shading the hit in `comps`: its object's, or the object's cap
+    // material for a cap hit (`Primitive::material_for`). Refraction indices
+    // (`prepare_computations`) always come from the main material, since the
+    // caps and wall bound the same solid.
+    fn material_at(&self, comps: &Computations) -> &'a Material {
+        self.objects[comps.object_id].material_for(comps.on_cap)
+    }
+    // The texel of material map `map` (an image id plus one, as in
+    // `Material::albedo_map`) under the hit in `comps`. A map naming an image
+    // the world lacks (see `Material::validate_maps`) reads as black rather
+    // than panicking mid-render.
+    fn map_at(&self, comps: &Computations, map: u32) -> Color {
+        let index = (map - 1) as usize;
+        if index >= self.images.len() {
+            return Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            };
+        }
+        let image = &self.images[index];
+        let object_point = self.world_to_object(comps.object_id, comps.point);
+        let (u, v) = uv_map(object_point, self.material_at(comps).map_mapping);
+        image.image_at(self.texels, u, v)
+    }
+    // The material's unlit color at the hit: its albedo map if it has one,
+    // otherwise its pattern or flat color (`material_color_at`).
+    pub(crate) fn albedo_at(&self, comps: &Computations) -> Color {
+        let material = self.material_at(comps);
+        if material.albedo_map != 0 {
+            return self.map_at(comps, material.albedo_map);
+        }
         let object = &self.objects[comps.object_id];
-        let mut surface = Color {
-            r: 0.0,
-            g: 0.0,
-            b: 0.0,
-        };
+        material_color_at(object, material, comps.point, self.footprint(comps))
+    }
+    // The material's `glossiness` at the hit, read from its roughness map if
+    // it has one.
+    pub fn roughness_at(&self, comps: &Computations) -> Number {
+        let material = self.material_at(comps);
+        if material.roughness_map == 0 {
+            return material.glossiness;
+        }
+        let texel = self.map_at(comps, material.roughness_map);
+        (texel.r + texel.g + texel.b) / 3.0
+    }
+    // The material's `reflective` at the hit, read from its metal map if it
+    // has one.
+    pub fn metalness_at(&self, comps: &Computations) -> Number {
+        let material = self.material_at(comps);
+        if material.metal_map == 0 {
+            return material.reflective;
+        }
+        let texel = self.map_at(comps, material.metal_map);
+        (texel.r + texel.g + texel.b) / 3.0
+    }
+    fn surface_at(&self, comps: &Computations) -> Color {
+        let material = self.material_at(comps);
+        let color = self.albedo_at(comps);
+        // An emissive surface glows whether or not any light reaches it.
+        let mut surface = material.emissive;
         // Index loop over lights (no slice iterator) for rust-gpu. Light is Copy.
         let mut li = 0;
         while li < self.lights.len() {
             let light = self.lights[li];
+            // Skip the shadow rays and shading for a light too dim to register.
+            if !self.is_significant(&light, comps.point) {
+                li += 1;
+                continue;
+            }
             let intensity = self.intensity_at(comps.over_point, &light);
-            surface = surface
-                + lightning(
-                    object,
-                    light,
-                    comps.point,
-                    comps.eyev,
-                    comps.normalv,
-                    intensity,
-                );
+            let (ambient, direct) = lightning_parts(
+                material,
+                color,
+                light,
+                comps.point,
+                comps.eyev,
+                comps.normalv,
+                intensity,
+            );
+            surface = surface + ambient * self.ambient_light + direct;
             li += 1;
         }
         surface
     }
+    // The approximate width (world units) of the surface one pixel covers at
+    // this hit: a ray differential treating the pixel as a cone of
+    // `pixel_spread` radians, stretched by 1/cos where the ray meets the
+    // surface at a slant (capped at 10x so a grazing hit doesn't smear the
+    // whole pattern). It measures only the last segment of a bounced ray, so
+    // it underestimates after reflection or refraction.
+    pub(crate) fn footprint(&self, comps: &Computations) -> Number {
+        let cos = comps.eyev.dot(comps.normalv).abs().max(0.1);
+        comps.t * self.pixel_spread / cos
+    }
+    // Whether `light` contributes enough at `point` to be worth shading.
+    pub fn is_significant(&self, light: &Light, point: Point) -> bool {
+        light.contribution_at(point) >= self.light_threshold
+    }
     pub fn shade_hit(&self, comps: Computations, remaining: usize) -> Color {
-        let surface = self.surface_at(&comps);
-        let reflected = self.reflected_color(&comps, remaining);
-        let refracted = self.refracted_color(&comps, remaining);
+        self.shade_hit_with(comps, remaining, &no_extra_light)
+    }
+    // `shade_hit` with `extra` light (as for `trace`) added at this hit and at
+    // every hit its reflected and refracted rays go on to make.
+    pub(crate) fn shade_hit_with<E: Fn(&Computations) -> Color>(
+        &self,
+        comps: Computations,
+        remaining: usize,
+        extra: &E,
+    ) -> Color {
+        let (surface, reflected, refracted) = self.shade_parts(&comps, remaining, extra);
+        self.finish_hit(&comps, surface + reflected + refracted)
+    }
+    // `shade_hit`'s three terms before fading and fog: the surface's own
+    // lighting (with `extra`) and the reflected and refracted light it passes
+    // on, the last two already weighted by the Schlick split for a reflective
+    // glass.
+    fn shade_parts<E: Fn(&Computations) -> Color>(
+        &self,
+        comps: &Computations,
+        remaining: usize,
+        extra: &E,
+    ) -> (Color, Color, Color) {
+        let surface = self.surface_at(comps) + extra(comps);
+        let reflected = self.reflected_color_with(comps, remaining, extra);
+        let refracted = self.refracted_color_with(comps, remaining, extra);
 
-        let material = self.objects[comps.object_id].get_material();
-        if material.reflective > 0.0 && material.transparency > 0.0 {
+        let material = self.material_at(comps);
+        if self.metalness_at(comps) > 0.0 && material.transparency > 0.0 {
             let reflectance = comps.schlick();
-            return surface + reflected * reflectance + refracted * (1.0 - reflectance);
+            (
+                surface,
+                reflected * reflectance,
+                refracted * (1.0 - reflectance),
+            )
+        } else {
+            (surface, reflected, refracted)
+        }
+    }
+    // The horizon fade and fog over a hit's summed light.
+    fn finish_hit(&self, comps: &Computations, lit: Color) -> Color {
+        let fade = self.objects[comps.object_id].horizon_fade(comps.t);
+        let lit = if fade < 1.0 {
+            lit * fade + self.background(-comps.eyev) * (1.0 - fade)
+        } else {
+            lit
+        };
+        self.fog.apply(lit, comps.t)
+    }
+    // What `ray` sees if it hits nothing: the environment image if there is
+    // one, else the sky. Fog is left to the caller.
+    pub fn background_at(&self, ray: &Ray) -> Color {
+        self.background(ray.direction)
+    }
+    fn background(&self, direction: Vector) -> Color {
+        if self.environment_width != 0 {
+            environment_at(self.environment, self.environment_width, direction)
+        } else {
+            self.sky.at(direction)
         }
-        surface + reflected + refracted
     }
     pub fn color_at(&self, ray: &Ray, remaining: usize) -> Color {
-        let mut total = Color {
-            r: 0.0,
-            g: 0.0,
-            b: 0.0,
+        self.trace(ray, remaining, NO_OBJECT, CLEAR, UNCLIPPED, &no_extra_light)
+    }
+    // `color_at` with the ray's own hits clipped to near < t <= far, for a
+    // camera's clip planes: surfaces closer than `near` are cut away, so the
+    // ray shades whatever lies behind them (the inside of a solid it cuts
+    // into). Reflected and refracted rays are not clipped.
+    pub fn color_at_clipped(
+        &self,
+        ray: &Ray,
+        remaining: usize,
+        near: Number,
+        far: Number,
+    ) -> Color {
+        self.trace(ray, remaining, NO_OBJECT, CLEAR, (near, far), &no_extra_light)
+    }
+    // `color_at` for a secondary ray whose first hit may skip `ignore` (see
+    // `intersect_world_ignoring`) and which travels through a medium with
+    // `absorption`, with `extra` light as for `trace` and `reserved` stack
+    // slots as for `trace_job`.
+    fn color_at_from<E: Fn(&Computations) -> Color>(
+        &self,
+        ray: &Ray,
+        remaining: usize,
+        ignore: usize,
+        absorption: Color,
+        reserved: usize,
+        extra: &E,
+    ) -> Color {
+        let first = ShadeJob {
+            ray: *ray,
+            remaining,
+            weight: Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+            ignore,
+            absorption,
         };
-        let mut stack = [ShadeJob::default(); MAX_SHADE_STACK];
-        let mut sp = 0usize;
-        stack[sp] = ShadeJob {
+        self.trace_job(first, UNCLIPPED, reserved, extra)
+    }
+    // The iterative shading loop behind the `color_at` variants: `ignore`,
+    // `absorption` and the (near, far) `clip` range apply to `ray` itself; its
+    // bounces work out their own from the surfaces they leave.
+    // `extra` adds light the scene's own `lights` don't cover at every hit
+    // (`World::custom_lights`); a plain generic so the GPU build never sees a
+    // trait object.
+    pub(crate) fn trace<E: Fn(&Computations) -> Color>(
+        &self,
+        ray: &Ray,
+        remaining: usize,
+        ignore: usize,
+        absorption: Color,
+        clip: (Number, Number),
+        extra: &E,
+    ) -> Color {
+        let first = ShadeJob {
             ray: *ray,
             remaining,
             weight: Color {
@@ -755,68 +1895,135 @@ impl<'a> Scene<'a> {
                 g: 1.0,
                 b: 1.0,
             },
+            ignore,
+            absorption,
+        };
+        self.trace_job(first, clip, 0, extra)
+    }
+    // `trace` from a prepared first job, with the bottom `reserved` slots of the
+    // stack counted as taken. `reflected_color` traces a glossy reflection's
+    // jittered rays one at a time and reserves a slot for each sibling `trace`
+    // would have kept under it, so every one has exactly the room to fan out
+    // again it would have had on `trace`'s own stack.
+    fn trace_job<E: Fn(&Computations) -> Color>(
+        &self,
+        first: ShadeJob,
+        clip: (Number, Number),
+        reserved: usize,
+        extra: &E,
+    ) -> Color {
+        let mut total = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
         };
+        let mut stack = [ShadeJob::default(); MAX_SHADE_STACK];
+        let mut sp = reserved;
+        stack[sp] = first;
         sp += 1;
 
-        while sp > 0 {
+        let mut primary = true;
+        while sp > reserved {
             sp -= 1;
             let job = stack[sp];
-            let xs = self.intersect_world(&job.ray);
-            let hi = xs.hit_index();
+            let xs = self.intersect_world_ignoring(&job.ray, job.ignore);
+            let hi = if primary {
+                xs.hit_index_within(clip.0, clip.1)
+            } else {
+                xs.hit_index()
+            };
+            primary = false;
             if hi == xs.len {
+                let background = self.background_at(&job.ray);
+                total = total + self.fog.apply(background, Number::INFINITY) * job.weight;
                 continue;
             }
             let hit = xs.xs[hi];
             let comps = hit.prepare_computations(&job.ray, self, &xs);
-            total = total + self.surface_at(&comps) * job.weight;
+            let weight = job.weight * transmittance(job.absorption, hit.t);
+            // Fog: this hit and everything seen in it fade by the same
+            // visibility, and the lost light is made up with fog color.
+            let visibility = self.fog.visibility(hit.t);
+            total = total + self.fog.color * weight * (1.0 - visibility);
+            let weight = weight * visibility;
+            // A fading plane gives way to the background, along with
+            // everything seen in it.
+            let fade = self.objects[hit.object_id].horizon_fade(hit.t);
+            total = total + self.background_at(&job.ray) * weight * (1.0 - fade);
+            let weight = weight * fade;
+            total = total + (self.surface_at(&comps) + extra(&comps)) * weight;
 
             if job.remaining == 0 {
                 continue;
             }
-            let material = self.objects[comps.object_id].get_material();
-            let reflective = material.reflective;
+            let material = self.material_at(&comps);
+            let reflective = self.metalness_at(&comps);
             let transparency = material.transparency;
             if reflective == 0.0 && transparency == 0.0 {
                 continue;
             }
-            let cos_i = comps.eyev.dot(comps.normalv);
-            let n_ratio = comps.n1 / comps.n2;
-            let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
-            let tir = sin2_t > 1.0;
-
-            let both = reflective > 0.0 && transparency > 0.0;
-            let reflectance = if both { comps.schlick() } else { 1.0 };
+            let refracted = comps.refracted_direction();
+            let (reflect_weight, refract_weight) = bounce_weights(&comps, material, reflective);
 
+            // Each child ray's weight is scaled by its roulette factor: 0 when
+            // the roulette stops it (it's dropped), 1/p when it survives.
             if reflective > 0.0 && sp < MAX_SHADE_STACK {
-                let w = if both { reflective * reflectance } else { reflective };
-                stack[sp] = ShadeJob {
-                    ray: Ray {
-                        origin: comps.over_point,
-                        direction: comps.reflectv,
-                    },
-                    remaining: job.remaining - 1,
-                    weight: job.weight * w,
-                };
-                sp += 1;
-            }
-            if transparency > 0.0 && !tir && sp < MAX_SHADE_STACK {
-                let cos_t = (1.0 - sin2_t).sqrt();
-                let direction =
-                    comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
-                let w = if both {
-                    transparency * (1.0 - reflectance)
+                let w = reflect_weight;
+                let w = w * self.roulette(comps.over_point, job.remaining, w, ROULETTE_REFLECT);
+                // A glossy surface fans out into GLOSSY_SAMPLES jittered rays
+                // sharing the weight, as long as that leaves a stack slot for
+                // the refraction; deeper down it sends one jittered ray.
+                let glossiness = self.roughness_at(&comps);
+                let glossy = glossiness > 0.0;
+                let samples = if glossy && sp + GLOSSY_SAMPLES < MAX_SHADE_STACK {
+                    GLOSSY_SAMPLES
                 } else {
-                    transparency
+                    1
                 };
-                stack[sp] = ShadeJob {
-                    ray: Ray {
-                        origin: comps.under_point,
-                        direction,
-                    },
-                    remaining: job.remaining - 1,
-                    weight: job.weight * w,
-                };
-                sp += 1;
+                let mut s = 0;
+                while w > 0.0 && s < samples {
+                    let direction = if glossy {
+                        glossy_direction(
+                            comps.reflectv,
+                            comps.normalv,
+                            glossiness,
+                            comps.over_point,
+                            s,
+                        )
+                    } else {
+                        comps.reflectv
+                    };
+                    stack[sp] = ShadeJob {
+                        ray: Ray {
+                            origin: comps.over_point,
+                            direction,
+                        },
+                        remaining: job.remaining - 1,
+                        weight: weight * (w / samples as Number),
+                        ignore: self.secondary_ignore(&comps, true),
+                        absorption: medium_after(&comps, material, true),
+                    };
+                    sp += 1;
+                    s += 1;
+                }
+            }
+            let room = transparency > 0.0 && sp < MAX_SHADE_STACK;
+            if let Some(direction) = refracted.filter(|_| room) {
+                let w = refract_weight;
+                let w = w * self.roulette(comps.under_point, job.remaining, w, ROULETTE_REFRACT);
+                if w > 0.0 {
+                    stack[sp] = ShadeJob {
+                        ray: Ray {
+                            origin: comps.under_point,
+                            direction,
+                        },
+                        remaining: job.remaining - 1,
+                        weight: weight * w,
+                        ignore: self.secondary_ignore(&comps, false),
+                        absorption: medium_after(&comps, material, false),
+                    };
+                    sp += 1;
+                }
             }
         }
         total
@@ -834,17 +2041,57 @@ impl<'a> Scene<'a> {
             direction,
         };
 
-        let xs = self.intersect_world(&r);
-        let hi = xs.hit_index();
-        if hi == xs.len {
-            false
+        self.intersect_world_shadow(&r, distance)
+    }
+    // Whether anything blocks `ray` between EPSILON and `distance`: the
+    // any-hit query behind every shadow ray. Unlike `intersect_world` it
+    // neither merges nor sorts the hits, and it stops at the first root object
+    // with one in range. Each root's own hits are still gathered in full, so a
+    // CSG node filters its region before anything is tested. A hit at or below
+    // EPSILON (the surface the ray leaves) is skipped, not taken as the answer.
+    pub fn intersect_world_shadow(&self, ray: &Ray, distance: Number) -> bool {
+        let mut xs = Intersections::empty();
+        let count = if self.use_shadow_casters {
+            self.shadow_casters.len()
         } else {
-            let t = xs.xs[hi].t;
-            t > EPSILON && t < distance
+            self.objects.len()
+        };
+        let mut index = 0;
+        while index < count {
+            let id = if self.use_shadow_casters {
+                self.shadow_casters[index]
+            } else {
+                index
+            };
+            // The flag is checked even for a listed caster, in case it was
+            // cleared on `objects` directly after the last rebake.
+            let object = &self.objects[id];
+            if object.parent().is_none() && object.casts_shadow() {
+                xs.len = 0;
+                self.intersect_object_into(id, ray, &mut xs);
+                let mut i = 0;
+                while i < xs.len {
+                    let t = xs.xs[i].t;
+                    if t > EPSILON && t < distance {
+                        return true;
+                    }
+                    i += 1;
+                }
+            }
+            index += 1;
         }
+        false
     }
     pub fn intensity_at(&self, point: Point, light: &Light) -> Number {
-        if light.kind == 0 {
+        if light.kind == 0 && light.softness > 0.0 {
+            let mut lit = 0;
+            for i in 0..SOFT_SHADOW_SAMPLES {
+                if !self.is_shadowed_at(light.soft_shadow_point(i, point), point) {
+                    lit += 1;
+                }
+            }
+            lit as Number / SOFT_SHADOW_SAMPLES as Number
+        } else if light.kind == 0 {
             if self.is_shadowed_at(light.position(), point) {
                 0.0
             } else {
@@ -863,47 +2110,131 @@ impl<'a> Scene<'a> {
         }
     }
     pub fn reflected_color(&self, comps: &Computations, remaining: usize) -> Color {
-        let material = self.objects[comps.object_id].get_material();
-        if material.reflective == 0.0 || remaining <= 0 {
+        self.reflected_color_with(comps, remaining, &no_extra_light)
+    }
+    // `reflected_color` with `extra` light added wherever the reflection lands.
+    pub(crate) fn reflected_color_with<E: Fn(&Computations) -> Color>(
+        &self,
+        comps: &Computations,
+        remaining: usize,
+        extra: &E,
+    ) -> Color {
+        let material = self.material_at(comps);
+        let reflective = self.metalness_at(comps);
+        // The depth is unsigned, so "out of bounces" is exactly 0. Bail out before
+        // the `remaining - 1` below so it can never underflow.
+        if reflective == 0.0 || remaining == 0 {
+            return Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            };
+        }
+        let (survival, _) = bounce_weights(comps, material, reflective);
+        let factor = self.roulette(comps.over_point, remaining, survival, ROULETTE_REFLECT);
+        if factor == 0.0 {
             return Color {
                 r: 0.0,
                 g: 0.0,
                 b: 0.0,
             };
         }
+        let factor = factor * fresnel_weight(comps, material);
+        let ignore = self.secondary_ignore(comps, true);
+        let medium = medium_after(comps, material, true);
+        let glossiness = self.roughness_at(comps);
+        if glossiness > 0.0 {
+            let mut sum = Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            };
+            // The fan-out `trace` makes at a glossy hit, with the rest of the
+            // fan counted against each ray's stack so deeper glossy hits send
+            // one jittered ray as they would there, not GLOSSY_SAMPLES more.
+            let mut s = 0;
+            while s < GLOSSY_SAMPLES {
+                // `trace` pops the fan last-pushed first, so ray s runs with
+                // the s pushed before it still below it on the stack.
+                let siblings = s;
+                let reflect_ray = Ray {
+                    origin: comps.over_point,
+                    direction: glossy_direction(
+                        comps.reflectv,
+                        comps.normalv,
+                        glossiness,
+                        comps.over_point,
+                        s,
+                    ),
+                };
+                let color =
+                    self.color_at_from(&reflect_ray, remaining - 1, ignore, medium, siblings, extra);
+                sum = sum + color;
+                s += 1;
+            }
+            let scale = reflective * factor / GLOSSY_SAMPLES as Number;
+            return sum * scale;
+        }
         let reflect_ray = Ray {
             origin: comps.over_point,
             direction: comps.reflectv,
         };
-        let color = self.color_at(&reflect_ray, remaining - 1);
-        color * material.reflective
+        let color = self.color_at_from(&reflect_ray, remaining - 1, ignore, medium, 0, extra);
+        color * (reflective * factor)
     }
     pub fn refracted_color(&self, comps: &Computations, remaining: usize) -> Color {
-        let object = &self.objects[comps.object_id];
-        if object.get_material().transparency == 0.0 || remaining <= 0 {
+        self.refracted_color_with(comps, remaining, &no_extra_light)
+    }
+    // `refracted_color` with `extra` light added wherever the refraction lands.
+    pub(crate) fn refracted_color_with<E: Fn(&Computations) -> Color>(
+        &self,
+        comps: &Computations,
+        remaining: usize,
+        extra: &E,
+    ) -> Color {
+        let material = self.material_at(comps);
+        // As in `reflected_color`: return before `remaining - 1` can underflow.
+        if material.transparency == 0.0 || remaining == 0 {
             return Color {
                 r: 0.0,
                 g: 0.0,
                 b: 0.0,
             };
         }
-        let n_ratio = comps.n1 / comps.n2;
-        let cos_i = comps.eyev.dot(comps.normalv);
-        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
-        if sin2_t > 1.0 {
+        let Some(direction) = comps.refracted_direction() else {
+            return Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            };
+        };
+        let transparency = material.transparency;
+        let (_, survival) = bounce_weights(comps, material, self.metalness_at(comps));
+        let factor = self.roulette(comps.under_point, remaining, survival, ROULETTE_REFRACT);
+        if factor == 0.0 {
             return Color {
                 r: 0.0,
                 g: 0.0,
                 b: 0.0,
             };
         }
-        let cos_t = (1.0 - sin2_t).sqrt();
-        let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
         let refract_ray = Ray {
             origin: comps.under_point,
             direction,
         };
-        self.color_at(&refract_ray, remaining - 1) * object.get_material().transparency
+        let ignore = self.secondary_ignore(comps, false);
+        let medium = medium_after(comps, material, false);
+        let color = self.color_at_from(&refract_ray, remaining - 1, ignore, medium, 0, extra);
+        color * (transparency * factor)
+    }
+    // The Russian-roulette weight for a secondary ray leaving `point` with
+    // `remaining` bounces whose contribution to the pixel is at most `survival`:
+    // 1.0 when the roulette is off, else `roulette_factor`.
+    fn roulette(&self, point: Point, remaining: usize, survival: Number, salt: u32) -> Number {
+        if self.roulette_seed == 0 {
+            return 1.0;
+        }
+        roulette_factor(self.roulette_seed ^ salt, point, remaining, survival)
     }
 }
 #[cfg(feature = "std")]
@@ -936,12 +2267,94 @@ impl Default for World {
         World {
             objects: vec![s1, s2],
             lights: vec![light],
+            custom_lights: vec![],
             children: vec![vec![], vec![]],
             child_indices: vec![],
+            shadow_casters: vec![],
+            shadow_casters_of: 0,
+            names: HashMap::new(),
             use_bounds: true,
+            light_threshold: 0.0,
+            roulette_seed: 0,
+            pixel_spread: 0.0,
+            environment: Environment::Black,
+            images: vec![],
+            texels: vec![],
+            fog: None,
+            ambient_light: Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
         }
     }
 }
+#[cfg(feature = "std")]
+impl World {
+    // A demo scene that looks good on a first render, unlike the book's
+    // two-sphere test world: a reflective checkered floor, a glass sphere in
+    // front of a matte red one, and a single white point light up and to the
+    // left. Render it through `showcase_view` with a PI / 3 field of view.
+    pub fn showcase() -> Self {
+        let mut world = World::new();
+        world.lights.push(Light::point_light(
+            Point::from([-10.0, 10.0, -10.0]),
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        ));
+
+        let mut floor = Primitive::plane();
+        let mut floor_material = Material::default();
+        floor_material.set_pattern(Pattern::checker_pattern(
+            Color {
+                r: 0.9,
+                g: 0.9,
+                b: 0.9,
+            },
+            Color {
+                r: 0.15,
+                g: 0.15,
+                b: 0.2,
+            },
+        ));
+        floor_material.set_specular(0.0);
+        floor_material.set_reflective(0.3);
+        floor.set_material(floor_material);
+        world.add_object(floor);
+
+        let mut matte = Primitive::sphere();
+        matte.set_transform(translation(1.2, 1.0, 1.5));
+        let mut matte_material = Material::default();
+        matte_material.set_color(Color {
+            r: 0.8,
+            g: 0.2,
+            b: 0.15,
+        });
+        matte_material.set_diffuse(0.8);
+        matte_material.set_specular(0.1);
+        matte.set_material(matte_material);
+        world.add_object(matte);
+
+        let mut glass = Primitive::glass_sphere();
+        glass.set_transform(translation(-0.8, 0.75, -0.5) * scaling(0.75, 0.75, 0.75));
+        world.add_object(glass);
+
+        world.compute_bounds();
+        world
+    }
+    // The camera transform `showcase` is framed for: slightly above the floor,
+    // looking at the spheres.
+    pub fn showcase_view() -> Matrix<4, 4> {
+        view_transform(
+            Point::from([0.0, 1.5, -5.0]),
+            Point::from([0.0, 1.0, 0.0]),
+            Vector::from([0.0, 1.0, 0.0]),
+        )
+    }
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -953,6 +2366,116 @@ mod tests {
         assert_eq!(w.lights, vec![]);
     }
     #[test]
+    fn a_named_object_can_be_found_by_its_name() {
+        let mut w = World::default();
+        let floor = w.add_object(Primitive::plane());
+        w.set_name(floor, "floor");
+        assert_eq!(w.object_by_name("floor"), Some(floor));
+        assert_eq!(w.name(floor), Some("floor"));
+        assert_eq!(w.name(0), None);
+        assert_eq!(w.object_by_name("ceiling"), None);
+    }
+    #[test]
+    fn clipping_a_sphere_through_its_center_leaves_a_hemisphere() {
+        let mut w = World::new();
+        let up = Vector::from([0.0, 1.0, 0.0]);
+        let origin = Point::from([0.0, 0.0, 0.0]);
+        let csg = w.add_clipped(Primitive::sphere(), origin, up);
+        let down = Ray {
+            origin: Point::from([0.0, 5.0, 0.0]),
+            direction: -up,
+        };
+        let xs = w.intersect_world(&down);
+        // In through the flat cut face, out through the bottom of the sphere.
+        assert_eq!(xs.count(), 2);
+        assert_almost_eq!(xs[0].t, 5.0);
+        assert_almost_eq!(xs[1].t, 6.0);
+        assert_eq!(w.normal_at(xs[0].object_id, origin), up);
+        // Above the cut there is nothing left; below it the sphere is whole.
+        let across = |y| Ray {
+            origin: Point::from([-5.0, y, 0.0]),
+            direction: Vector::from([1.0, 0.0, 0.0]),
+        };
+        assert_eq!(w.intersect_world(&across(0.5)).count(), 0);
+        let xs = w.intersect_world(&across(-0.5));
+        assert_eq!(xs.count(), 2);
+        assert_almost_eq!(xs[0].t, 5.0 - (0.75 as Number).sqrt());
+        assert_eq!(w.objects[csg].kind, ShapeKind::Csg);
+    }
+    #[test]
+    fn validating_a_worlds_materials_names_the_bad_object() {
+        let mut w = World::default();
+        assert_eq!(w.validate_materials(), Ok(()));
+        w.objects[1].material.set_ambient(-0.1);
+        let (id, _) = w.validate_materials().unwrap_err();
+        assert_eq!(id, 1);
+    }
+    #[test]
+    fn a_map_naming_a_missing_image_fails_validation_and_shades_black() {
+        let mut w = World::default();
+        w.objects[0].set_material(Material::from_maps(0, None, Some(3)));
+        let (id, e) = w.validate_materials().unwrap_err();
+        assert_eq!(id, 0);
+        assert_eq!(
+            e,
+            MaterialError::MissingMap {
+                field: "albedo_map",
+                image: 0,
+            }
+        );
+        let r = Ray {
+            origin: Point::from([0.0, 0.0, -5.0]),
+            direction: Vector::from([0.0, 0.0, 1.0]),
+        };
+        // No panic; the unlit albedo is black, leaving only the specular.
+        let c = w.color_at(&r, 5);
+        assert!(c.r < 0.01 && c.g < 0.01 && c.b < 0.01, "{c:?}");
+        w.add_image(1, &[CLEAR]);
+        let (_, e) = w.validate_materials().unwrap_err();
+        assert_eq!(
+            e,
+            MaterialError::MissingMap {
+                field: "metal_map",
+                image: 3,
+            }
+        );
+    }
+    #[test]
+    fn validating_a_world_lists_every_problem() {
+        assert_eq!(World::default().validate(), vec![]);
+        let mut w = World::default();
+        w.lights.clear();
+        w.objects[0].set_transform(scaling(1.0, 0.0, 1.0));
+        w.objects[1].material.refractive_index = 0.5;
+        let warnings = w.validate();
+        assert_eq!(warnings.len(), 3, "{warnings:?}");
+        assert!(warnings.contains(&SceneWarning::NoLights));
+        assert!(warnings.contains(&SceneWarning::SingularTransform(0)));
+        assert!(matches!(
+            warnings[2],
+            SceneWarning::InvalidMaterial(1, MaterialError::OutOfRange { field, .. })
+                if field == "refractive_index"
+        ));
+        assert_eq!(
+            warnings[1].to_string(),
+            "object 0 has a non-invertible transform"
+        );
+    }
+    #[test]
+    fn the_showcase_world_has_several_objects_and_a_light() {
+        let w = World::showcase();
+        assert!(w.objects.len() >= 3);
+        assert!(!w.lights.is_empty());
+        // Looking through the center of the suggested view hits something.
+        let from = Point::from([0.0, 1.5, -5.0]);
+        let to = Point::from([0.0, 1.0, 0.0]);
+        let r = Ray {
+            origin: from,
+            direction: (to - from).normalize(),
+        };
+        assert!(w.intersect_world(&r).hit().is_some());
+    }
+    #[test]
     fn the_default_world() {
         let light = Light::point_light(Point {
                 x: -10.0,
@@ -984,6 +2507,37 @@ mod tests {
         assert_eq!(w.objects[1], s2);
     }
     #[test]
+    fn intersect_world_sorts_hits_from_many_objects() {
+        // A 10x10 grid of spheres added far row first, so the arena order is
+        // the reverse of the hit order. `intersect_world` appends every root's
+        // hits unsorted and sorts once at the end.
+        let mut w = World::new();
+        for row in (0..10).rev() {
+            for col in 0..10 {
+                let mut s = Primitive::sphere();
+                s.set_transform(translation(col as Number * 3.0, 0.0, row as Number * 3.0));
+                w.add_object(s);
+            }
+        }
+        let r = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        let xs = w.intersect_world(&r);
+        assert_eq!(xs.count(), 20);
+        assert!(xs.xs[..xs.len].windows(2).all(|pair| pair[0].t <= pair[1].t));
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[19].t, 33.0);
+    }
+    #[test]
     fn intersect_a_world_with_a_ray() {
         let w = World::default();
         let r = Ray {
@@ -1006,6 +2560,200 @@ mod tests {
         assert_eq!(xs[3].t, 6.0);
     }
     #[test]
+    fn intersections_grouped_by_object_keep_each_objects_own_hits() {
+        let w = World::default();
+        let r = Ray {
+            origin: Point::from([0.0, 0.0, -5.0]),
+            direction: Vector::from([0.0, 0.0, 1.0]),
+        };
+        let grouped = w.intersect_by_object(&r);
+        assert_eq!(grouped.len(), 2);
+        let (outer, outer_xs) = &grouped[0];
+        let (inner, inner_xs) = &grouped[1];
+        assert_eq!((*outer, *inner), (0, 1));
+        assert_eq!(outer_xs.count(), 2);
+        assert_eq!((outer_xs[0].t, outer_xs[1].t), (4.0, 6.0));
+        assert_eq!(inner_xs.count(), 2);
+        assert_eq!((inner_xs[0].t, inner_xs[1].t), (4.5, 5.5));
+        let miss = Ray {
+            origin: Point::from([0.0, 5.0, -5.0]),
+            direction: Vector::from([0.0, 0.0, 1.0]),
+        };
+        assert!(w.intersect_by_object(&miss).is_empty());
+    }
+    #[test]
+    fn a_tessellated_sphere_is_hit_close_to_the_analytic_sphere() {
+        let mut w = World::new();
+        let group = w.add_tessellated_sphere(16, 32);
+        w.compute_bounds();
+        assert_eq!(group_children(&w, group).len(), 2 * 32 * 15);
+        // Off the axes, so the ray crosses triangle interiors rather than the
+        // edges they share.
+        let r = Ray {
+            origin: Point {
+                x: 0.13,
+                y: 0.21,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        let mut analytic = Intersections::empty();
+        crate::spheres::sphere_intersect(&r, 0, &mut analytic);
+        let xs = w.intersect_world(&r);
+        assert_eq!(xs.len, 2);
+        assert!((xs[0].t - analytic[0].t).abs() < 0.01);
+        assert!((xs[1].t - analytic[1].t).abs() < 0.01);
+    }
+    #[test]
+    fn a_bounding_sphere_skips_a_group_the_ray_misses() {
+        let mut w = World::new();
+        let group = w.add_tessellated_sphere(8, 16);
+        w.use_bounding_sphere(group);
+        w.compute_bounds();
+        let sphere = w.objects[group].bounding_sphere().unwrap();
+        assert_almost_eq!(sphere.radius, 1.0);
+
+        let ray = |x: Number, y: Number| Ray {
+            origin: Point { x, y, z: -5.0 },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        // Through the corner of the group's box: inside the box, outside the
+        // sphere, so the group is culled without visiting a triangle.
+        let corner = ray(0.9, 0.9);
+        assert!(w.objects[group].bounds().unwrap().intersects(&corner));
+        assert!(!w.objects[group].bounds_admit(&corner));
+        assert_eq!(w.intersect_world(&corner).len, 0);
+        // A ray just inside the silhouette still enters and hits the mesh.
+        let grazing = ray(0.0, 0.95);
+        assert!(w.objects[group].bounds_admit(&grazing));
+        assert!(w.intersect_world(&grazing).hit().is_some());
+    }
+    #[test]
+    fn a_bounding_sphere_around_an_unbounded_child_culls_nothing_it_holds() {
+        let mut w = World::new();
+        let group = w.add_object(Primitive::group());
+        w.add_child(group, Primitive::sphere());
+        w.add_child(group, Primitive::plane());
+        w.use_bounding_sphere(group);
+        w.compute_bounds();
+        let sphere = w.objects[group].bounding_sphere().unwrap();
+        assert_eq!(sphere.radius, Number::INFINITY);
+        // Far from the unit sphere, the ray still reaches the plane.
+        let down = Ray {
+            origin: Point::from([10.0, 1.0, 0.0]),
+            direction: Vector::from([0.0, -1.0, 0.0]),
+        };
+        let xs = w.intersect_world(&down);
+        assert_eq!(xs.len, 1);
+        assert_eq!(xs[0].t, 1.0);
+    }
+    #[test]
+    fn a_soft_point_light_casts_a_fractional_shadow_at_the_penumbra_edge() {
+        let mut w = World::new();
+        let hard = Light::point_light(
+            Point {
+                x: 0.0,
+                y: 10.0,
+                z: 0.0,
+            },
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        );
+        let mut occluder = Primitive::cube();
+        occluder.set_transform(translation(0.0, 5.0, 0.0) * scaling(1.0, 0.1, 1.0));
+        w.add_object(occluder);
+        let at = |x: Number, light: &Light| {
+            w.intensity_at(
+                Point {
+                    x,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                light,
+            )
+        };
+        // No softness is the plain boolean shadow.
+        for x in [0.0, 1.5, 2.0, 2.5, 10.0] {
+            let p = Point { x, y: 0.0, z: 0.0 };
+            let lit = if w.is_shadowed(p, &hard) { 0.0 } else { 1.0 };
+            assert_eq!(at(x, &hard), lit);
+        }
+        let soft = hard.with_softness(1.0);
+        assert_eq!(at(0.0, &soft), 0.0);
+        let edge = at(2.0, &soft);
+        assert!(edge > 0.0 && edge < 1.0, "{edge}");
+        assert_eq!(at(10.0, &soft), 1.0);
+    }
+    #[test]
+    fn a_sphere_light_casts_a_fractional_shadow_at_the_penumbra_edge() {
+        let mut w = World::new();
+        w.lights.push(Light::sphere_light(
+            Point {
+                x: 0.0,
+                y: 10.0,
+                z: 0.0,
+            },
+            1.0,
+            8,
+            8,
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        ));
+        let mut occluder = Primitive::cube();
+        occluder.set_transform(translation(0.0, 5.0, 0.0) * scaling(1.0, 0.1, 1.0));
+        w.add_object(occluder);
+        let light = w.lights[0];
+        let at = |x: Number| {
+            w.intensity_at(
+                Point {
+                    x,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                &light,
+            )
+        };
+        assert_eq!(at(0.0), 0.0);
+        let edge = at(2.0);
+        assert!(edge > 0.0 && edge < 1.0, "{edge}");
+        assert_eq!(at(10.0), 1.0);
+    }
+    #[test]
+    fn tracing_a_ray_for_debugging_reports_every_intersection() {
+        let w = World::default();
+        let r = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        let report = w.trace_ray_debug(&r);
+        assert!(report.starts_with(
+            r#"{"intersections":[{"t":4,"object_id":0},{"t":4.5,"object_id":1},{"t":5.5,"object_id":1},{"t":6,"object_id":0}],"hit":{"t":4,"object_id":0},"color":{"#
+        ), "{report}");
+        assert_eq!(report.matches("\"t\"").count(), 5);
+    }
+    #[test]
     fn shading_an_intersection() {
         let w = World::default();
         let r = Ray {
@@ -1021,7 +2769,7 @@ mod tests {
             },
         };
         let i = Intersection::new(4.0, 0);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         assert_eq!(
             w.shade_hit(comps, 0),
             Color {
@@ -1032,6 +2780,126 @@ mod tests {
         );
     }
     #[test]
+    fn a_glowing_quad_lights_the_floor_beneath_it_most() {
+        let mut w = World::new();
+        w.add_object(Primitive::plane());
+        let mut quad = Primitive::cube();
+        quad.set_transform(scaling(1.0, 0.01, 1.0).then(translation(0.0, 2.0, 0.0)));
+        let mut glow = Material::default();
+        glow.set_emissive(Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        });
+        quad.set_material(glow);
+        let quad = w.add_object(quad);
+        assert_eq!(w.add_emissive_light(quad, 12, 4), 48);
+        assert_eq!(w.add_emissive_light(0, 12, 4), 0);
+
+        let floor_at = |x: Number| {
+            let r = Ray {
+                origin: Point::from([x, 1.0, -0.5]),
+                direction: Vector::from([0.0, -1.0, 0.0]),
+            };
+            w.color_at(&r, 0).r
+        };
+        let (beneath, edge, far) = (floor_at(0.0), floor_at(2.0), floor_at(6.0));
+        assert!(beneath > edge && edge > far, "{beneath} {edge} {far}");
+        assert!(far > 0.0);
+    }
+    // A user-defined light: a point emitter that ignores shadows and dims by
+    // a fixed factor.
+    #[derive(Debug)]
+    struct Lantern {
+        position: Point,
+        dimming: Number,
+    }
+    impl LightProperties for Lantern {
+        fn position(&self) -> Point {
+            self.position
+        }
+        fn intensity(&self) -> Color {
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            }
+        }
+    }
+    impl LightSource for Lantern {
+        fn intensity_at(&self, _: Point, _: &Scene) -> Number {
+            self.dimming
+        }
+    }
+    #[test]
+    fn shading_with_a_custom_light() {
+        let mut w = World::default();
+        let builtin = w.lights.pop().unwrap();
+        w.add_custom_light(Lantern {
+            position: builtin.position,
+            dimming: 1.0,
+        });
+        let r = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        // The lantern stands where the default light was, so the book's value.
+        let expected = Color {
+            r: 0.38066,
+            g: 0.47583,
+            b: 0.2855,
+        };
+        assert_eq!(w.color_at(&r, 5), expected);
+        let i = Intersection::new(4.0, 0);
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
+        assert_eq!(w.shade_hit(comps, 0), expected);
+        // A light is a `LightSource` too, and adds to a world's own lights.
+        w.lights.push(builtin);
+        w.custom_lights.clear();
+        w.add_custom_light(builtin);
+        assert_eq!(w.color_at(&r, 5), expected + expected);
+    }
+    #[test]
+    fn a_custom_light_reaches_what_a_mirror_reflects() {
+        let mut w = World::default();
+        let builtin = w.lights.pop().unwrap();
+        w.add_custom_light(Lantern {
+            position: builtin.position,
+            dimming: 1.0,
+        });
+        let mut floor = Primitive::plane();
+        floor.material.set_reflective(0.5);
+        floor.set_transform(translation(0.0, -1.0, 0.0));
+        let floor = w.add_object(floor);
+        let half = (2.0 as Number).sqrt() / 2.0;
+        let r = Ray {
+            origin: Point::from([0.0, 0.0, -3.0]),
+            direction: Vector::from([0.0, -half, half]),
+        };
+        let xs = w.intersect_world(&r);
+        let hit = xs[xs.hit_index()];
+        assert_eq!(hit.object_id, floor);
+        let comps = || hit.prepare_computations(&r, &w.scene(), &xs);
+        let shaded = w.shade_hit(comps(), 5);
+        assert_eq!(shaded, w.color_at(&r, 5));
+        // The reflected sphere is lit by the lantern, not left black.
+        let reflected = w.reflected_color(&comps(), 5);
+        assert!(reflected.g > 0.1, "{reflected:?}");
+        // Swapping the lantern back for the built-in light changes nothing.
+        let mut lit = w.clone();
+        lit.custom_lights.clear();
+        lit.lights.push(builtin);
+        assert_eq!(lit.shade_hit(comps(), 5), shaded);
+    }
+    #[test]
     fn shading_an_intersection_from_the_inside() {
         let mut w = World::default();
         w.lights = vec![Light::point_light(Point {
@@ -1057,7 +2925,7 @@ mod tests {
             },
         };
         let i = Intersection::new(0.5, 1);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         assert_eq!(
             w.shade_hit(comps, 0),
             Color {
@@ -1118,14 +2986,19 @@ mod tests {
         );
     }
     #[test]
+    fn each_material_edits_every_object_in_place() {
+        let mut w = World::default();
+        w.each_material(|m| m.set_ambient(1.0));
+        assert_eq!(w.objects[0].get_material().ambient, 1.0);
+        assert_eq!(w.objects[1].get_material().ambient, 1.0);
+        w.objects_mut()[1].material.set_ambient(0.5);
+        assert_eq!(w.objects[1].get_material().ambient, 0.5);
+        assert_eq!(w.objects[0].get_material().ambient, 1.0);
+    }
+    #[test]
     fn the_color_with_an_intersection_behind_the_ray() {
         let mut w = World::default();
-        let mut object_material0 = w.objects[0].get_material();
-        object_material0.set_ambient(1.0);
-        w.objects[0].set_material(object_material0);
-        let mut object_material1 = w.objects[1].get_material();
-        object_material1.set_ambient(1.0);
-        w.objects[1].set_material(object_material1);
+        w.each_material(|m| m.set_ambient(1.0));
 
         let r = Ray {
             origin: Point {
@@ -1231,6 +3104,16 @@ mod tests {
         );
     }
     #[test]
+    fn a_view_transformation_looking_straight_down_the_up_vector() {
+        let from = Point::from([0.0, 5.0, 0.0]);
+        let to = Point::from([0.0, 0.0, 0.0]);
+        let t = view_transform(from, to, Vector::from([0.0, 1.0, 0.0]));
+        assert!(is_invertible(&t));
+        // The eye still sits at the origin, looking down -z, in camera space.
+        assert_eq!(t * from, Point::from([0.0, 0.0, 0.0]));
+        assert_eq!(t * to, Point::from([0.0, 0.0, -5.0]));
+    }
+    #[test]
     fn the_area_light_intensity_function() {
         let w = World::default();
         let light = Light::area_light(
@@ -1284,10 +3167,51 @@ mod tests {
         }
     }
     #[test]
-    fn root_of_walks_to_the_top_level_object() {
-        let mut w = World::new();
-        let g1 = w.add_object(Primitive::group());
-        let g2 = w.add_child(g1, Primitive::group());
+    fn significant_lights_skip_a_distant_attenuated_light() {
+        let mut w = World::default();
+        let white = Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        let near = Light::point_light(Point { x: 0.0, y: 2.0, z: -2.0 }, white)
+            .with_attenuation(1.0, 0.0, 0.1);
+        let far = Light::point_light(Point { x: 0.0, y: 500.0, z: 0.0 }, white)
+            .with_attenuation(1.0, 0.0, 0.1);
+        w.lights = vec![near, far];
+        w.light_threshold = 0.01;
+        let point = Point { x: 0.0, y: 0.0, z: -1.0 };
+        assert_eq!(w.significant_lights(point), vec![&near]);
+        // With no threshold every light is kept.
+        w.light_threshold = 0.0;
+        assert_eq!(w.significant_lights(point).len(), 2);
+    }
+    #[test]
+    fn shade_hit_ignores_an_insignificant_light() {
+        let r = Ray {
+            origin: Point { x: 0.0, y: 0.0, z: -5.0 },
+            direction: Vector { x: 0.0, y: 0.0, z: 1.0 },
+        };
+        let i = Intersection::new(4.0, 0);
+        let mut w = World::default();
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
+        let expected = w.shade_hit(comps, 0);
+        w.light_threshold = 0.01;
+        w.lights.push(
+            Light::point_light(
+                Point { x: 0.0, y: 0.0, z: -1000.0 },
+                Color { r: 1.0, g: 1.0, b: 1.0 },
+            )
+            .with_attenuation(1.0, 1.0, 1.0),
+        );
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
+        assert_eq!(w.shade_hit(comps, 0), expected);
+    }
+    #[test]
+    fn root_of_walks_to_the_top_level_object() {
+        let mut w = World::new();
+        let g1 = w.add_object(Primitive::group());
+        let g2 = w.add_child(g1, Primitive::group());
         let s = w.add_child(g2, Primitive::sphere());
         assert_eq!(w.root_of(s), g1);
         assert_eq!(w.root_of(g2), g1);
@@ -1470,6 +3394,80 @@ mod tests {
         assert_eq!(w.is_shadowed(p, &w.lights[0]), false);
     }
     #[test]
+    fn an_object_that_casts_no_shadow_is_left_out_of_shadow_rays_only() {
+        let mut w = World::default();
+        w.objects.clear();
+        w.children.clear();
+        let outer = w.add_object(Primitive::sphere());
+        let mut inner = Primitive::sphere();
+        inner.set_transform(scaling(0.5, 0.5, 0.5));
+        let inner = w.add_object(inner);
+        let p = Point::from([10.0, -10.0, 10.0]);
+        w.set_casts_shadow(outer, false);
+        assert_eq!(w.shadow_casters, vec![inner]);
+        // The small sphere still blocks the light; the big one no longer does.
+        assert!(w.is_shadowed(p, &w.lights[0]));
+        w.set_casts_shadow(inner, false);
+        assert!(w.shadow_casters.is_empty());
+        assert!(!w.is_shadowed(p, &w.lights[0]));
+        // Camera rays see it as before.
+        let r = Ray {
+            origin: Point::from([0.0, 0.0, -5.0]),
+            direction: Vector::from([0.0, 0.0, 1.0]),
+        };
+        let xs = w.intersect_world(&r);
+        assert_eq!(xs[xs.hit_index()].object_id, outer);
+        assert_ne!(w.color_at(&r, 0), CLEAR);
+    }
+    #[test]
+    fn the_early_out_shadow_test_agrees_with_the_full_intersection() {
+        let grouped = {
+            let mut w = World::default();
+            let g = w.add_object(Primitive::group());
+            for x in [-2.0, 2.0] {
+                let mut s = Primitive::sphere();
+                s.set_transform(translation(x, 1.0, 0.0));
+                w.add_child(g, s);
+            }
+            w
+        };
+        let carved = {
+            // A sphere with a bite taken out of its top.
+            let mut w = World::default();
+            let csg = w.add_object(Primitive::csg(crate::csg::CsgOperation::Difference));
+            let mut left = Primitive::sphere();
+            left.set_transform(scaling(2.0, 2.0, 2.0));
+            let left = w.add_object(left);
+            let mut right = Primitive::sphere();
+            right.set_transform(translation(0.0, 2.0, 0.0).then(scaling(1.5, 1.5, 1.5)));
+            let right = w.add_object(right);
+            w.set_csg_children(csg, left, right);
+            w
+        };
+        for w in [World::default(), grouped, carved] {
+            let scene = w.scene();
+            let light = w.lights[0].position;
+            for i in 0..9 {
+                for j in 0..9 {
+                    for y in [-3.0, 0.3, 2.5] {
+                        let point = Point::from([i as Number - 4.0, y, j as Number - 4.0]);
+                        let v = light - point;
+                        let ray = Ray {
+                            origin: point,
+                            direction: v.normalize(),
+                        };
+                        let full = w
+                            .intersect_world(&ray)
+                            .hit()
+                            .is_some_and(|hit| hit.t > EPSILON && hit.t < v.magnitude());
+                        let fast = scene.intersect_world_shadow(&ray, v.magnitude());
+                        assert_eq!(fast, full, "at {point:?}");
+                    }
+                }
+            }
+        }
+    }
+    #[test]
     fn shade_hit_is_given_an_intersection_in_shadow() {
         let mut w = World::default();
         let light = Light::point_light(Point {
@@ -1499,7 +3497,7 @@ mod tests {
             },
         };
         let i = Intersection::new(4.0, 1);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         w.objects.extend(vec![s1, s2.clone()]);
         let c = w.shade_hit(comps, 0);
         assert_eq!(
@@ -1531,7 +3529,7 @@ mod tests {
         let i = Intersection::new(5.0, 0);
         let mut w = World::new();
         w.objects.append(&mut vec![shape]);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         assert_eq!(comps.over_point.z() < -EPSILON / 2.0, true);
         assert_eq!(comps.point.z() > comps.over_point.z(), true);
     }
@@ -1554,7 +3552,7 @@ mod tests {
         second_object_material.set_ambient(1.0);
         w.objects[1].set_material(second_object_material);
         let i = Intersection::new(1.0, 1);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         let color = w.reflected_color(&comps, 0);
         assert_eq!(
             color,
@@ -1590,7 +3588,7 @@ mod tests {
         };
 
         let i = Intersection::new(sqrt(2.0), 2);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         let color = w.reflected_color(&comps, 1);
         // Book value, published to 5 decimals; compare within that precision.
         assert_almost_eq!(color.r, 0.19032, 1e-4);
@@ -1598,6 +3596,353 @@ mod tests {
         assert_almost_eq!(color.b, 0.14274, 1e-4);
     }
     #[test]
+    fn a_trace_log_records_the_bounce_off_a_mirror_floor() {
+        let mut w = World::default();
+        let mut shape = Primitive::plane();
+        let mut material = Material::default();
+        material.set_reflective(0.5);
+        shape.set_material(material);
+        shape.set_transform(translation(0.0, -1.0, 0.0));
+        w.objects.push(shape);
+        let r = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -3.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: -sqrt(2.0) / 2.0,
+                z: sqrt(2.0) / 2.0,
+            },
+        };
+        let (color, log) = w.trace_with_log(&r, 1);
+        let floor = log.events[0];
+        assert_eq!(floor.kind, TraceKind::Primary);
+        assert_eq!(floor.hit.map(|(id, _)| id), Some(2));
+        assert_eq!(floor.color, color);
+        let bounces: Vec<_> = log.children(0).collect();
+        assert_eq!(bounces.len(), 1);
+        let (_, bounce) = bounces[0];
+        assert_eq!(bounce.kind, TraceKind::Reflection);
+        assert_eq!(bounce.depth, 1);
+        // The reflection lands on the outer sphere, which tints the floor.
+        assert_eq!(bounce.hit.map(|(id, _)| id), Some(0));
+        assert_almost_eq!(floor.reflected.r, 0.19032, 1e-4);
+        assert_almost_eq!(floor.reflected.g, 0.2379, 1e-4);
+        assert_almost_eq!(floor.reflected.b, 0.14274, 1e-4);
+        assert_eq!(floor.surface + floor.reflected, floor.color);
+    }
+    #[test]
+    fn a_uniform_gray_roughness_map_gives_constant_roughness_across_a_plane() {
+        let mut w = World::new();
+        let red = Color {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        let gray = Color {
+            r: 0.5,
+            g: 0.5,
+            b: 0.5,
+        };
+        let albedo = w.add_image(2, &[red; 4]);
+        let roughness = w.add_image(3, &[gray; 9]);
+        let mut material = Material::from_maps(albedo, Some(roughness), None);
+        material.map_mapping = crate::texture_maps::MAPPING_PLANAR;
+        let mut floor = Primitive::plane();
+        floor.set_material(material);
+        w.add_object(floor);
+        let scene = w.scene();
+        for (x, z) in [(0.1, 0.2), (0.9, 0.6), (-3.3, 7.25), (12.0, -0.5)] {
+            let r = Ray {
+                origin: Point::from([x, 1.0, z]),
+                direction: Vector::from([0.0, -1.0, 0.0]),
+            };
+            let xs = w.intersect_world(&r);
+            let comps = xs[xs.hit_index()].prepare_computations(&r, &scene, &xs);
+            assert_eq!(scene.roughness_at(&comps), 0.5);
+            assert_eq!(scene.albedo_at(&comps), red);
+            // No metal map leaves the flat reflectivity.
+            assert_eq!(scene.metalness_at(&comps), 0.0);
+        }
+    }
+    #[test]
+    fn shade_hit_fans_out_between_glossy_mirrors_as_color_at_does() {
+        // Two facing glossy mirrors: every bounce is glossy, so the recursive
+        // path must cap its fan-out the way the iterative one does to end up
+        // with the same rays.
+        let mut w = World::default();
+        let mut glossy = Material::default();
+        glossy.set_reflective(0.8);
+        glossy.set_glossiness(0.2);
+        for y in [-1.0, 3.0] {
+            let mut mirror = Primitive::plane();
+            mirror.set_material(glossy.clone());
+            mirror.set_transform(translation(0.0, y, 0.0));
+            w.add_object(mirror);
+        }
+        let r = Ray {
+            origin: Point::from([5.0, 2.0, -5.0]),
+            direction: Vector::from([0.0, -0.6, 0.8]),
+        };
+        let scene = w.scene();
+        let xs = scene.intersect_world(&r);
+        let comps = || xs[xs.hit_index()].prepare_computations(&r, &scene, &xs);
+        // `extra` runs once per hit shaded, so it counts the rays.
+        let hits = std::cell::Cell::new(0);
+        let count = |_: &Computations| {
+            hits.set(hits.get() + 1);
+            CLEAR
+        };
+        let recursive = scene.shade_hit_with(comps(), 5, &count);
+        let recursive_hits = hits.replace(0);
+        let iterative = scene.trace(&r, 5, NO_OBJECT, CLEAR, UNCLIPPED, &count);
+        assert_eq!(recursive_hits, hits.get());
+        assert_almost_eq!(recursive.r, iterative.r, 1e-4);
+        assert_almost_eq!(recursive.g, iterative.g, 1e-4);
+        assert_almost_eq!(recursive.b, iterative.b, 1e-4);
+    }
+    #[test]
+    fn glossiness_blurs_a_reflected_edge() {
+        // A black mirror floor under a bright slab that covers only x < 0: the
+        // reflection straight up switches from slab to sky at x = 0.
+        let mut w = World::new();
+        w.lights.push(Light::point_light(
+            Point::from([-10.0, 10.0, -10.0]),
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        ));
+        let mut floor = Primitive::plane();
+        let mut mirror = Material::default();
+        mirror.set_color(Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        });
+        mirror.set_specular(0.0);
+        mirror.set_reflective(1.0);
+        floor.set_material(mirror);
+        let mut slab = Primitive::cube();
+        let mut bright = Material::default();
+        bright.set_ambient(1.0);
+        bright.set_diffuse(0.0);
+        bright.set_specular(0.0);
+        slab.set_material(bright);
+        slab.set_transform(scaling(10.0, 0.5, 10.0).then(translation(-10.0, 2.5, 0.0)));
+        w.objects = vec![floor, slab];
+
+        let down_at = |x: Number| Ray {
+            origin: Point::from([x, 1.0, 0.0]),
+            direction: Vector {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+        };
+        let (under, beside) = (down_at(-0.05), down_at(0.05));
+
+        // Glossiness 0 is the plain mirror: one ray, a hard edge.
+        assert_eq!(w.objects[0].get_material().glossiness, 0.0);
+        assert_almost_eq!(w.color_at(&under, 5).r, 1.0, 1e-4);
+        assert_almost_eq!(w.color_at(&beside, 5).r, 0.0, 1e-4);
+
+        let mut glossy = w.objects[0].get_material();
+        glossy.set_glossiness(0.5);
+        w.objects[0].set_material(glossy);
+        let (a, b) = (w.color_at(&under, 5).r, w.color_at(&beside, 5).r);
+        assert!(a > 0.0 && a < 1.0, "under the slab: {a}");
+        assert!(b > 0.0 && b < 1.0, "beside the slab: {b}");
+        assert_eq!(w.color_at(&under, 5), w.color_at(&under, 5));
+    }
+    #[test]
+    fn a_reflected_ray_ignores_the_sphere_it_leaves() {
+        let mut w = World::new();
+        w.add_object(Primitive::sphere());
+        // Another sphere straight down the reflection.
+        let mut behind = Primitive::sphere();
+        behind.set_transform(translation(1.45, 0.0, 5.0));
+        w.add_object(behind);
+        // Almost tangent, so the bounce leaves the surface at a grazing angle.
+        let r = Ray {
+            origin: Point {
+                x: 0.999,
+                y: 0.0,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        let xs = w.intersect_world(&r);
+        let comps = xs.hit().unwrap().prepare_computations(&r, &w.scene(), &xs);
+        assert!(!comps.inside);
+        let scene = w.scene();
+        let ignore = scene.secondary_ignore(&comps, true);
+        assert_eq!(ignore, 0);
+        let reflect_ray = Ray {
+            origin: comps.point,
+            direction: comps.reflectv,
+        };
+        // Left alone, the ray finds the sphere again a hair from where it
+        // started; ignoring it, the ray goes on to the sphere behind.
+        let xs = scene.intersect_world_ignoring(&reflect_ray, NO_OBJECT);
+        assert_eq!(xs.hit().unwrap().object_id, 0);
+        let xs = scene.intersect_world_ignoring(&reflect_ray, ignore);
+        let hit = xs.hit().unwrap();
+        assert_eq!(hit.object_id, 1);
+        assert!(hit.t > 1.0);
+        // Inside glass the reflection has to find the sphere again.
+        let inner = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        let xs = w.intersect_world(&inner);
+        let comps = xs.hit().unwrap().prepare_computations(&inner, &w.scene(), &xs);
+        assert!(comps.inside);
+        assert_eq!(scene.secondary_ignore(&comps, true), NO_OBJECT);
+    }
+    #[test]
+    fn roulette_decisions_are_reproducible_for_a_fixed_seed() {
+        let point = |i: usize| Point {
+            x: i as Number * 0.37,
+            y: -1.0,
+            z: i as Number * 0.11,
+        };
+        let draws = |seed: u32| -> Vec<Number> {
+            (0..2000).map(|i| roulette_factor(seed, point(i), 3, 0.3)).collect()
+        };
+        let first = draws(7);
+        assert_eq!(first, draws(7));
+        assert_ne!(first, draws(8));
+        // Survivors are boosted by 1/p, so on average nothing is lost.
+        let mean = first.iter().sum::<Number>() / first.len() as Number;
+        assert_almost_eq!(mean, 1.0, 0.1);
+        let survivors = first.iter().filter(|&&f| f > 0.0).count();
+        assert!((500..700).contains(&survivors), "{survivors}");
+    }
+    #[test]
+    fn shade_hit_and_color_at_cut_the_same_rays_for_a_seed() {
+        // Facing Fresnel mirrors: each bounce sends back only the Schlick
+        // share of a clear reflection, and that share is what both paths must
+        // stake the roulette on.
+        let mut w = World::default();
+        let mut mirror = Material::default();
+        mirror.set_reflective(1.0);
+        mirror.set_fresnel_reflective(true);
+        for y in [-1.0, 3.0] {
+            let mut plane = Primitive::plane();
+            plane.set_material(mirror.clone());
+            plane.set_transform(translation(0.0, y, 0.0));
+            w.add_object(plane);
+        }
+        let r = Ray {
+            origin: Point::from([5.0, 2.0, -5.0]),
+            direction: Vector::from([0.0, -0.6, 0.8]),
+        };
+        for seed in 1..40 {
+            w.roulette_seed = seed;
+            let scene = w.scene();
+            let xs = scene.intersect_world(&r);
+            let comps = xs[xs.hit_index()].prepare_computations(&r, &scene, &xs);
+            let hits = std::cell::Cell::new(0);
+            let count = |_: &Computations| {
+                hits.set(hits.get() + 1);
+                CLEAR
+            };
+            let recursive = scene.shade_hit_with(comps, 8, &count);
+            let recursive_hits = hits.replace(0);
+            let iterative = scene.trace(&r, 8, NO_OBJECT, CLEAR, UNCLIPPED, &count);
+            assert_eq!(recursive_hits, hits.get(), "seed {seed}");
+            assert_eq!(recursive, iterative, "seed {seed}");
+        }
+    }
+    #[test]
+    fn a_fully_reflective_surface_is_never_cut_by_the_roulette() {
+        for i in 0..1000 {
+            let p = Point {
+                x: i as Number,
+                y: 0.5,
+                z: -(i as Number),
+            };
+            assert_eq!(roulette_factor(42, p, 4, 1.0), 1.0);
+        }
+        let mut w = World::default();
+        let mut shape = Primitive::plane();
+        let mut material = Material::default();
+        material.set_reflective(1.0);
+        shape.set_material(material);
+        shape.set_transform(translation(0.0, -1.0, 0.0));
+        w.objects.push(shape);
+        w.children.push(vec![]);
+        w.rebake();
+        let r = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -3.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: -sqrt(2.0) / 2.0,
+                z: sqrt(2.0) / 2.0,
+            },
+        };
+        let i = Intersection::new(sqrt(2.0), 2);
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
+        let full = w.reflected_color(&comps, 3);
+        let traced = w.color_at(&r, 3);
+        w.roulette_seed = 42;
+        assert_eq!(w.reflected_color(&comps, 3), full);
+        assert_eq!(w.color_at(&r, 3), traced);
+    }
+    #[test]
+    fn the_reflected_color_at_the_maximum_recursive_depth() {
+        let mut w = World::default();
+        let mut shape = Primitive::plane();
+        let mut material = Material::default();
+        material.set_reflective(0.5);
+        shape.set_material(material);
+        shape.set_transform(translation(0.0, -1.0, 0.0));
+        w.objects.append(&mut vec![shape]);
+        let r = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -3.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: -sqrt(2.0) / 2.0,
+                z: sqrt(2.0) / 2.0,
+            },
+        };
+        let i = Intersection::new(sqrt(2.0), 2);
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
+        let black = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        assert_eq!(w.reflected_color(&comps, 0), black);
+        // shade_hit forwards the same exhausted depth to both bounce terms.
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
+        assert_eq!(w.shade_hit(comps, 0), w.color_at(&r, 0));
+    }
+    #[test]
     fn shade_hit_with_a_reflective_material() {
         let mut w = World::default();
         let mut shape = Primitive::plane();
@@ -1619,7 +3964,7 @@ mod tests {
             },
         };
         let i = Intersection::new(sqrt(2.0), 2);
-        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![]));
+        let comps = i.prepare_computations(&r, &w.scene(), &Intersections::new(vec![i]));
         let color = w.shade_hit(comps, 1);
         // Book value, published to 5 decimals; compare within that precision.
         assert_almost_eq!(color.r, 0.87677, 1e-4);
@@ -1765,6 +4110,374 @@ mod tests {
         );
     }
 
+    // White light seen through a slab of glass that absorbs green and blue.
+    fn through_absorbing_slab(thickness: Number) -> Color {
+        let mut w = World::new();
+        w.lights.push(Light::point_light(
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: -10.0,
+            },
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        ));
+        let mut backdrop = Primitive::plane();
+        backdrop.set_transform(rotation_x(PI / 2.0).then(translation(0.0, 0.0, 5.0)));
+        backdrop.material.set_ambient(1.0);
+        backdrop.material.set_diffuse(0.0);
+        backdrop.material.set_specular(0.0);
+        w.add_object(backdrop);
+        let mut slab = Primitive::slab(thickness);
+        slab.set_transform(rotation_x(PI / 2.0));
+        slab.material.set_ambient(0.0);
+        slab.material.set_diffuse(0.0);
+        slab.material.set_specular(0.0);
+        slab.material.set_transparency(1.0);
+        slab.material.set_absorption(Color {
+            r: 0.0,
+            g: 1.0,
+            b: 1.0,
+        });
+        w.add_object(slab);
+        let r = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        w.color_at(&r, 5)
+    }
+    #[test]
+    fn absorbing_glass_tints_light_by_its_thickness() {
+        let thick = through_absorbing_slab(2.0);
+        assert_almost_eq!(thick.r, 1.0);
+        assert_almost_eq!(thick.g, (-2.0 as Number).exp());
+        assert_almost_eq!(thick.b, (-2.0 as Number).exp());
+        let thin = through_absorbing_slab(0.01);
+        assert_almost_eq!(thin.r, 1.0);
+        assert!(thin.g > 0.98 && thin.b > 0.98, "{thin:?}");
+    }
+    #[test]
+    fn a_mirror_sphere_reflects_the_environment_behind_the_camera() {
+        let color = |r, g, b| Color { r, g, b };
+        // One texel per quarter turn around +y: from -z to +x, +x to +z, +z to
+        // -x and -x back to -z (see `spherical_map`).
+        let texels = vec![
+            color(1.0, 0.0, 0.0),
+            color(0.0, 1.0, 0.0),
+            color(0.0, 0.0, 1.0),
+            color(2.0, 2.0, 2.0),
+        ];
+        let mut w = World::new();
+        w.environment = Environment::ImageSphere {
+            width: 4,
+            texels: texels.clone(),
+        };
+        let mut mirror = Primitive::sphere();
+        mirror.material.set_ambient(0.0);
+        mirror.material.set_diffuse(0.0);
+        mirror.material.set_specular(0.0);
+        mirror.material.set_reflective(1.0);
+        w.add_object(mirror);
+        let ray = |x, direction| Ray {
+            origin: Point { x, y: 0.0, z: 5.0 },
+            direction,
+        };
+        let ahead = Vector {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        };
+        // A miss shows the environment straight along the ray.
+        let past = Vector {
+            x: 0.5,
+            y: 0.0,
+            z: -1.0,
+        };
+        assert_eq!(w.color_at(&ray(0.0, past.normalize()), 5), texels[0]);
+        // Hitting the mirror at x = +-0.5 bounces the view 120 degrees around,
+        // back past the camera on that side: towards (+-0.87, 0, 0.5).
+        assert_eq!(w.color_at(&ray(0.5, ahead), 5), texels[1]);
+        assert_eq!(w.color_at(&ray(-0.5, ahead), 5), texels[2]);
+    }
+    #[test]
+    fn a_gradient_background_is_the_top_color_straight_up_and_the_bottom_straight_down() {
+        let top = Color {
+            r: 0.2,
+            g: 0.4,
+            b: 1.0,
+        };
+        let bottom = Color {
+            r: 0.6,
+            g: 0.5,
+            b: 0.3,
+        };
+        let mut w = World::new();
+        w.environment = Environment::Gradient { bottom, top };
+        let ray = |y| Ray {
+            origin: Point::default(),
+            direction: Vector::from([0.0, y, 0.0]),
+        };
+        assert_eq!(w.background_at(&ray(1.0)), top);
+        assert_eq!(w.background_at(&ray(-1.0)), bottom);
+        let level = Ray {
+            origin: Point::default(),
+            direction: Vector::from([1.0, 0.0, 0.0]),
+        };
+        assert_eq!(w.background_at(&level), (top + bottom) * 0.5);
+        // A miss and a mirror's reflection both see it.
+        assert_eq!(w.color_at(&ray(1.0), 5), top);
+        let mut mirror = Primitive::plane();
+        mirror.material.set_ambient(0.0);
+        mirror.material.set_diffuse(0.0);
+        mirror.material.set_specular(0.0);
+        mirror.material.set_reflective(1.0);
+        w.add_object(mirror);
+        let down = Ray {
+            origin: Point::from([0.0, 1.0, 0.0]),
+            direction: Vector::from([0.0, -1.0, 0.0]),
+        };
+        assert_eq!(w.color_at(&down, 5), top);
+        w.environment = Environment::Solid(bottom);
+        assert_eq!(w.color_at(&down, 5), bottom);
+    }
+    #[test]
+    fn an_opaque_fresnel_floor_reflects_more_at_grazing_angles() {
+        let white = Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        let mut w = World::new();
+        w.environment = Environment::ImageSphere {
+            width: 4,
+            texels: vec![white; 4],
+        };
+        let mut floor = Primitive::plane();
+        floor.material.set_ambient(0.0);
+        floor.material.set_diffuse(0.0);
+        floor.material.set_specular(0.0);
+        floor.material.set_reflective(1.0);
+        floor.material.set_refractive_index(1.5);
+        floor.material.set_fresnel_reflective(true);
+        w.add_object(floor);
+        let ray = |z, direction: Vector| Ray {
+            origin: Point { x: 0.0, y: 1.0, z },
+            direction: direction.normalize(),
+        };
+        let head_on = ray(0.0, Vector::from([0.0, -1.0, 0.0]));
+        let grazing = ray(-20.0, Vector::from([0.0, -1.0, 20.0]));
+        // Straight down only r0 = ((1 - 1.5) / (1 + 1.5))^2 comes back.
+        assert_almost_eq!(w.color_at(&head_on, 5).r, 0.04);
+        let xs = w.intersect_world(&head_on);
+        let comps = xs[0].prepare_computations(&head_on, &w.scene(), &xs);
+        assert_almost_eq!(w.reflected_color(&comps, 5).r, 0.04);
+        let glancing = w.color_at(&grazing, 5).r;
+        assert!(glancing > 0.5, "{glancing}");
+        // Without the flag the floor is a plain mirror at any angle.
+        w.objects[0].material.set_fresnel_reflective(false);
+        assert_almost_eq!(w.color_at(&head_on, 5).r, 1.0);
+        assert_almost_eq!(w.color_at(&grazing, 5).r, 1.0);
+    }
+    #[test]
+    fn the_shadow_term_is_white_under_the_light_and_black_behind_an_occluder() {
+        let mut w = World::default();
+        w.add_object(Primitive::with(
+            Primitive::plane,
+            translation(0.0, -1.0, 0.0),
+            Material::default(),
+        ));
+        let down = Vector::from([0.0, -1.0, 0.0]);
+        // Straight under the light at (-10, 10, -10).
+        let under_light = Ray {
+            origin: Point::from([-10.0, 5.0, -10.0]),
+            direction: down,
+        };
+        assert_eq!(w.shadow_at(&under_light), 1.0);
+        // (1, -1, 1) is on the line from the light through the spheres' center.
+        let behind_spheres = Ray {
+            origin: Point::from([1.0, -0.5, 1.0]),
+            direction: down,
+        };
+        assert_eq!(w.shadow_at(&behind_spheres), 0.0);
+        let miss = Ray {
+            origin: Point::from([0.0, 5.0, 0.0]),
+            direction: Vector::from([0.0, 1.0, 0.0]),
+        };
+        assert_eq!(w.shadow_at(&miss), 0.0);
+    }
+    #[test]
+    fn a_t_min_past_the_front_sphere_reveals_the_one_inside() {
+        let w = World::default();
+        let r = Ray {
+            origin: Point::from([0.0, 0.0, -5.0]),
+            direction: Vector::from([0.0, 0.0, 1.0]),
+        };
+        // The outer sphere's front is at t = 4, the inner sphere's at 4.5.
+        let xs = w.intersect_world(&r);
+        assert_eq!(xs.hit().unwrap().object_id, 0);
+        let inner = xs.hit_after(4.2).unwrap();
+        assert_eq!((inner.object_id, inner.t), (1, 4.5));
+        let comps = inner.prepare_computations(&r, &w.scene(), &xs);
+        let behind = w.shade_hit(comps, 5);
+        assert_eq!(w.color_at_clipped(&r, 5, 4.2, Number::INFINITY), behind);
+        assert_ne!(w.color_at(&r, 5), behind);
+    }
+    #[test]
+    fn a_plane_with_a_horizon_fade_tints_far_hits_toward_the_background() {
+        let sky = Color {
+            r: 0.2,
+            g: 0.4,
+            b: 0.8,
+        };
+        let mut w = World::default();
+        w.environment = Environment::ImageSphere {
+            width: 4,
+            texels: vec![sky; 4],
+        };
+        w.objects = vec![Primitive::plane()];
+        w.objects[0].set_horizon_fade(10.0, 50.0);
+        w.objects[0].material.set_color(Color {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+        });
+        let toward = |x: Number| Ray {
+            origin: Point::from([0.0, 1.0, 0.0]),
+            direction: (Point::from([x, 0.0, 0.0]) - Point::from([0.0, 1.0, 0.0])).normalize(),
+        };
+        let near = w.color_at(&toward(2.0), 5);
+        let middle = w.color_at(&toward(30.0), 5);
+        let far = w.color_at(&toward(80.0), 5);
+        // Within `start` it's the plain red floor; past `end` it's all sky.
+        assert_eq!(near.b, 0.0);
+        assert!(middle.r > 0.0 && middle.b > 0.0, "{middle:?}");
+        assert!(middle.b / middle.r < far.b / far.r.max(EPSILON));
+        assert_eq!(far, sky);
+        // The recursive shade_hit fades the same way.
+        let r = toward(30.0);
+        let xs = w.intersect_world(&r);
+        let comps = xs[0].prepare_computations(&r, &w.scene(), &xs);
+        assert_eq!(w.shade_hit(comps, 5), middle);
+    }
+    #[test]
+    fn a_cylinder_shades_its_caps_with_the_cap_material() {
+        let mut w = World::new();
+        w.lights.push(Light::point_light(
+            Point {
+                x: 0.0,
+                y: 10.0,
+                z: -10.0,
+            },
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        ));
+        let flat = |r, g, b| {
+            let mut m = Material::default();
+            m.color = Color { r, g, b };
+            m.set_ambient(1.0);
+            m.set_diffuse(0.0);
+            m.set_specular(0.0);
+            m
+        };
+        let mut can = Primitive::cylinder();
+        can.minimum = -1.0;
+        can.maximum = 1.0;
+        can.closed = 1;
+        can.set_material(flat(1.0, 0.0, 0.0));
+        can.set_cap_material(flat(0.5, 0.5, 0.5));
+        w.add_object(can);
+        let down = Ray {
+            origin: Point {
+                x: 0.2,
+                y: 5.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+        };
+        let across = Ray {
+            origin: Point {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        };
+        assert!(w.intersect_world(&down).hit().unwrap().on_cap != 0);
+        assert!(w.intersect_world(&across).hit().unwrap().on_cap == 0);
+        assert_eq!(w.color_at(&down, 5), flat(0.5, 0.5, 0.5).color);
+        assert_eq!(w.color_at(&across, 5), flat(1.0, 0.0, 0.0).color);
+    }
+    #[test]
+    fn fog_tints_a_distant_hit_more_than_a_near_one() {
+        // A red sphere lit only by its ambient term, its front `distance` - 1
+        // down +z, seen through white fog.
+        let seen_at = |distance, fog| {
+            let mut w = World::new();
+            w.fog = fog;
+            w.lights.push(Light::point_light(
+                Point::default(),
+                Color {
+                    r: 1.0,
+                    g: 1.0,
+                    b: 1.0,
+                },
+            ));
+            let mut ball = Primitive::sphere();
+            ball.set_transform(translation(0.0, 0.0, distance));
+            ball.material.color = Color {
+                r: 1.0,
+                g: 0.0,
+                b: 0.0,
+            };
+            ball.material.set_ambient(1.0);
+            ball.material.set_diffuse(0.0);
+            ball.material.set_specular(0.0);
+            w.add_object(ball);
+            let ray = Ray {
+                origin: Point::default(),
+                direction: Vector {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+            };
+            w.color_at(&ray, 5)
+        };
+        let white = Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        let fog = Some(Fog::new(white, 0.1));
+        let near = seen_at(3.0, fog);
+        let far = seen_at(20.0, fog);
+        assert_almost_eq!(near.r, 1.0);
+        assert_almost_eq!(near.g, 1.0 - (-0.2 as Number).exp());
+        assert!(far.g > near.g && far.g < 1.0, "{near:?} {far:?}");
+        assert_eq!(seen_at(20.0, None).g, 0.0);
+    }
     #[test]
     fn the_refracted_color_with_a_refracted_ray() {
         let mut w = World::default();
@@ -1803,6 +4516,114 @@ mod tests {
         assert_almost_eq!(c.b, 0.04725, 1e-4);
     }
     #[test]
+    fn refraction_between_touching_glass_of_equal_index_goes_straight_on() {
+        let mut w = World::default();
+        w.objects = vec![
+            Primitive::with(
+                Primitive::glass_sphere,
+                translation(-0.9, 0.0, 0.0),
+                Material::glass(),
+            ),
+            Primitive::with(
+                Primitive::glass_sphere,
+                translation(0.9, 0.0, 0.0),
+                Material::glass(),
+            ),
+        ];
+        let r = Ray {
+            origin: Point::from([-5.0, 0.1, 0.0]),
+            direction: Vector::from([1.0, 0.0, 0.0]),
+        };
+        let xs = w.intersect_world(&r);
+        assert_eq!(xs.count(), 4);
+        // Entering the second sphere and leaving the first both happen inside
+        // glass of the same index: no bend at all, not even by rounding.
+        for index in 1..3 {
+            let comps = xs[index].prepare_computations(&r, &w.scene(), &xs);
+            assert_eq!((comps.n1, comps.n2), (1.5, 1.5));
+            let direction = comps.refracted_direction().unwrap();
+            assert_eq!(
+                (direction.x, direction.y, direction.z),
+                (r.direction.x, r.direction.y, r.direction.z)
+            );
+        }
+        // The outer surfaces still bend it.
+        let comps = xs[0].prepare_computations(&r, &w.scene(), &xs);
+        assert_ne!(comps.refracted_direction().unwrap(), r.direction);
+    }
+    #[test]
+    fn dimming_the_lights_by_half_halves_the_diffuse_term() {
+        let mut w = World::default();
+        w.objects[0].material.set_ambient(0.0);
+        w.objects[0].material.set_specular(0.0);
+        let r = Ray {
+            origin: Point::from([0.0, 0.0, -5.0]),
+            direction: Vector::from([0.0, 0.0, 1.0]),
+        };
+        let full = w.color_at(&r, 0);
+        assert!(full.r > 0.1, "{full:?}");
+        w.dim_lights(0.5);
+        assert_eq!(
+            w.lights[0].intensity(),
+            Color {
+                r: 0.5,
+                g: 0.5,
+                b: 0.5
+            }
+        );
+        assert_eq!(w.color_at(&r, 0), full * 0.5);
+    }
+    #[test]
+    fn halving_the_ambient_light_halves_the_ambient_term() {
+        // Lit from straight behind, the spheres' fronts show only ambient.
+        let mut w = World::default();
+        w.lights[0] = Light::point_light(
+            Point::from([0.0, 0.0, 10.0]),
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+            },
+        );
+        w.objects[1].material.set_ambient(0.4);
+        // The second ray starts inside the outer sphere, in front of the inner.
+        let rays = [
+            Ray {
+                origin: Point::from([0.0, 0.0, -5.0]),
+                direction: Vector::from([0.0, 0.0, 1.0]),
+            },
+            Ray {
+                origin: Point::from([0.0, 0.0, -0.75]),
+                direction: Vector::from([0.0, 0.0, 1.0]),
+            },
+        ];
+        let full: Vec<Color> = rays.iter().map(|r| w.color_at(r, 0)).collect();
+        w.ambient_light = Color {
+            r: 0.5,
+            g: 0.5,
+            b: 0.5,
+        };
+        for (r, full) in rays.iter().zip(&full) {
+            assert_eq!(w.color_at(r, 0), *full * 0.5);
+        }
+        assert_eq!(
+            full[0],
+            Color {
+                r: 0.08,
+                g: 0.1,
+                b: 0.06
+            }
+        );
+        assert_eq!(
+            full[1],
+            Color {
+                r: 0.4,
+                g: 0.4,
+                b: 0.4
+            }
+        );
+    }
+    #[test]
     fn shade_hit_with_a_transparent_material() {
         let mut w = World::default();
         let mut glass = Material::default();
@@ -12,6 +12,7 @@
 
 use crate::camera::Camera;
 use crate::matrices::Matrix;
+use crate::planes::ray_plane_intersection;
 use crate::rays::Ray;
 use crate::shapes::HasTransform;
 use crate::transformations::{rotation_y, scaling, translation, view_transform, PI};
@@ -211,10 +212,7 @@ fn center_out_tiles(w: usize, h: usize, tile: usize) -> Vec<(u32, u32, u32, u32)
 // Where a ray crosses the horizontal plane y = `plane_y`, going forward. Used to
 // drag a picked object across a horizontal plane at the height it was grabbed.
 fn ray_ground_hit(ray: &Ray, plane_y: Number) -> Option<Point> {
-    if ray.direction.y.abs() < EPSILON {
-        return None;
-    }
-    let t = (plane_y - ray.origin.y) / ray.direction.y;
+    let t = ray_plane_intersection(ray, plane_y)?;
     if t < 0.0 {
         return None;
     }